@@ -0,0 +1,165 @@
+use std::{hint::black_box, io::Cursor};
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use rust_http::message::{Connection, Request, Response, StatusCode, StreamReader};
+use rust_http::test_util::BatchReader;
+use tokio::runtime::Runtime;
+
+/// A request with the headers a browser usually sends
+fn typical_request() -> Vec<u8> {
+    b"GET /index.html HTTP/1.1\r\n\
+    Host: localhost:42069\r\n\
+    User-Agent: Mozilla/5.0 (X11; Linux x86_64; rv:128.0) Gecko/20100101 Firefox/128.0\r\n\
+    Accept: text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8\r\n\
+    Accept-Language: en-US,en;q=0.5\r\n\
+    Accept-Encoding: gzip, deflate, br\r\n\
+    Connection: keep-alive\r\n\
+    Upgrade-Insecure-Requests: 1\r\n\
+    Sec-Fetch-Dest: document\r\n\
+    Sec-Fetch-Mode: navigate\r\n\
+    Sec-Fetch-Site: none\r\n\
+    Priority: u=0, i\r\n\r\n"
+        .to_vec()
+}
+
+fn headers_heavy_request() -> Vec<u8> {
+    let mut input = b"GET /some/path?query=value HTTP/1.1\r\nHost: localhost:42069\r\n".to_vec();
+    for i in 0..30 {
+        input.extend_from_slice(format!("X-Header-{i}: some value for header {i}\r\n").as_bytes());
+    }
+    input.extend_from_slice(b"\r\n");
+    input
+}
+
+fn chunked_request() -> Vec<u8> {
+    let mut input = b"POST /upload HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n".to_vec();
+    for _ in 0..64 {
+        input.extend_from_slice(b"100\r\n");
+        input.extend_from_slice(&[b'a'; 0x100]);
+        input.extend_from_slice(b"\r\n");
+    }
+    input.extend_from_slice(b"0\r\n\r\n");
+    input
+}
+
+fn large_body_request() -> Vec<u8> {
+    let len = 1 << 20;
+    let mut input = format!("POST /upload HTTP/1.1\r\nContent-Length: {len}\r\n\r\n").into_bytes();
+    input.resize(input.len() + len, b'a');
+    input
+}
+
+fn bench_request_parsing(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("request_parsing");
+
+    let inputs = [
+        ("typical", typical_request()),
+        ("headers_heavy", headers_heavy_request()),
+        ("chunked", chunked_request()),
+        ("large_body", large_body_request()),
+    ];
+    for (name, input) in inputs {
+        group.throughput(Throughput::Bytes(input.len() as u64));
+        for batch_size in [64, 1500] {
+            group.bench_with_input(BenchmarkId::new(name, batch_size), &input, |b, input| {
+                b.to_async(&rt).iter(|| async {
+                    let reader = BatchReader::new(input.clone(), batch_size);
+                    let mut connection =
+                        Connection::<_, _, Request>::new(reader, tokio::io::sink());
+                    black_box(connection.read().await.unwrap())
+                })
+            });
+        }
+    }
+
+    group.finish();
+}
+
+/// Requests per second on one keep-alive connection, where the buffers of the reader are reused
+fn bench_keep_alive(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("keep_alive");
+
+    let requests = 100;
+    let input = typical_request().repeat(requests);
+    group.throughput(Throughput::Elements(requests as u64));
+    for batch_size in [64, 1500] {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(batch_size),
+            &input,
+            |b, input| {
+                b.to_async(&rt).iter(|| async {
+                    let reader = BatchReader::new(input.clone(), batch_size);
+                    let mut connection =
+                        Connection::<_, _, Request>::new(reader, tokio::io::sink());
+                    for _ in 0..requests {
+                        black_box(connection.read().await.unwrap());
+                    }
+                })
+            },
+        );
+    }
+
+    group.finish();
+}
+
+fn bench_response_serialization(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("response_serialization");
+
+    for len in [0, 1024, 1 << 20] {
+        group.throughput(Throughput::Bytes(len as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(len), &len, |b, &len| {
+            b.to_async(&rt).iter(|| async move {
+                let mut response = Response::new(StatusCode::Ok);
+                response.headers.add("Content-Type", "text/plain");
+                response.headers.add("Cache-Control", "no-cache");
+                response.body = vec![b'a'; len].into();
+                let mut out = Vec::with_capacity(len + 256);
+                response.write_to(&mut out).await.unwrap();
+                black_box(out)
+            })
+        });
+    }
+
+    group.finish();
+}
+
+fn bench_stream_reader(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("stream_reader");
+
+    let lines = b"a line that is read by the stream reader\r\n".repeat(100);
+    group.throughput(Throughput::Bytes(lines.len() as u64));
+    group.bench_function("read_line", |b| {
+        b.to_async(&rt).iter(|| async {
+            let mut reader = StreamReader::new(Cursor::new(&lines));
+            for _ in 0..100 {
+                black_box(reader.read_line().await.unwrap());
+            }
+        })
+    });
+
+    let bytes = vec![b'a'; 1 << 16];
+    group.throughput(Throughput::Bytes(bytes.len() as u64));
+    group.bench_function("read_n", |b| {
+        b.to_async(&rt).iter(|| async {
+            let mut reader = StreamReader::new(BatchReader::new(bytes.clone(), 1500));
+            for _ in 0..16 {
+                black_box(reader.read_n(1 << 12).await.unwrap());
+            }
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_request_parsing,
+    bench_keep_alive,
+    bench_response_serialization,
+    bench_stream_reader
+);
+criterion_main!(benches);
@@ -3,11 +3,11 @@ use rust_http::{self, client, client::error::ClientError};
 
 #[tokio::main]
 async fn main() -> Result<(), ClientError> {
-    let mut req = RequestBuilder::new(Get, "/")
+    let req = RequestBuilder::new(Get, "/")
         .header("Connection", "keep-alive")
         .build();
 
-    let resp = client::send_request("google.com", &mut req).await?;
+    let resp = client::send_request("google.com", &req).await?;
 
     println!(
         "Got response:\nStatus: {}\nBody: {}",
@@ -0,0 +1,35 @@
+use rust_http::message::{Request, Response, ResponseBuilder};
+use rust_http::server::{Router, Server, ServerError};
+
+#[tokio::main]
+async fn main() {
+    let router = Router::new()
+        .get("/", index)
+        .get("/hello", hello)
+        .post("/upload", upload);
+
+    let server = Server::new("localhost:42069", router).await;
+    let r = server.listen_and_serve().await;
+    if let Err(e) = r {
+        eprint!("Error while listening: {e}")
+    }
+}
+
+fn index(req: &Request) -> Result<Response, ServerError> {
+    let resp = Response::file(req, "examples/simple.html")?;
+    Ok(resp)
+}
+
+fn hello(_: &Request) -> Result<Response, ServerError> {
+    let mut builder = ResponseBuilder::new();
+    builder.add_to_body(b"Hello World")?;
+    Ok(builder.build())
+}
+
+fn upload(req: &Request) -> Result<Response, ServerError> {
+    println!(
+        "Uploaded body: {}",
+        String::from_utf8_lossy(req.get_body()).escape_debug()
+    );
+    Ok(ResponseBuilder::new().build())
+}
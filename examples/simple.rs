@@ -1,13 +1,21 @@
+use std::time::Duration;
+
 use rust_http::message::Method;
 use rust_http::message::ResponseBuilder;
-use rust_http::message::StatusCode;
 use rust_http::server::{Server, ServerError};
 
 use rust_http::message::{Request, Response};
 
 #[tokio::main]
 async fn main() {
-    let server = Server::new("localhost:42069", handle_request).await;
+    let server = Server::builder()
+        .addr("localhost:42069")
+        .handler(handle_request)
+        .max_body_size(1024 * 1024)
+        .read_timeout(Duration::from_secs(30))
+        .build()
+        .await
+        .expect("Could not bind to localhost:42069");
     let r = server.listen_and_serve().await;
     if let Err(e) = r {
         eprint!("Error while listening: {e}")
@@ -35,10 +43,6 @@ fn handle_request(req: &Request) -> Result<Response, ServerError> {
             builder.add_header("Connection", "close");
             Ok(builder.build())
         }
-        (_, _) => {
-            let mut builder = ResponseBuilder::new();
-            builder.set_status_code(StatusCode::NotFound);
-            Ok(builder.build())
-        }
+        (_, _) => Ok(Response::not_found()),
     }
 }
@@ -0,0 +1,38 @@
+use rust_http::message::{Method, Request, Response, StatusCode};
+use rust_http::server::{BoxFuture, Server, ServerError, async_handler};
+use tokio::net::TcpStream;
+
+// A forward proxy that only supports CONNECT tunnels
+// Try it with: curl -p -x localhost:42069 https://example.com
+#[tokio::main]
+async fn main() {
+    let server = Server::new("localhost:42069", async_handler(tunnel)).await;
+    let r = server.listen_and_serve().await;
+    if let Err(e) = r {
+        eprint!("Error while listening: {e}")
+    }
+}
+
+fn tunnel(req: &Request) -> BoxFuture<'_, Result<Response, ServerError>> {
+    Box::pin(async move {
+        if *req.get_method() != Method::Connect {
+            return Err(ServerError::with_status(StatusCode::MethodNotAllowed));
+        }
+        let Some(authority) = req.line.authority() else {
+            return Err(ServerError::with_status(StatusCode::BadRequest));
+        };
+
+        // Connects before answering, so the client gets an error if the host is unreachable
+        let mut upstream = TcpStream::connect(authority).await?;
+
+        let mut response = Response::new(StatusCode::Ok);
+        response.on_upgrade(move |mut client| {
+            Box::pin(async move {
+                if let Err(e) = tokio::io::copy_bidirectional(&mut client, &mut upstream).await {
+                    eprintln!("Tunnel closed: {e}");
+                }
+            })
+        });
+        Ok(response)
+    })
+}
@@ -1,14 +1,16 @@
 use std::net::SocketAddr;
 
-use tokio::net::TcpSocket;
+use tokio::net::{TcpSocket, TcpStream};
 
 use crate::{
     client::error::ClientError,
     message::{Connection, Request, Response},
 };
+#[cfg(feature = "blocking")]
+pub mod blocking;
 pub mod error;
 
-pub async fn send_request(url: &str, req: &mut Request) -> Result<Response, ClientError> {
+pub async fn send_request(url: &str, req: &Request) -> Result<Response, ClientError> {
     let addr = tokio::net::lookup_host(format!("{url}:80"))
         .await?
         .next()
@@ -19,19 +21,174 @@ pub async fn send_request(url: &str, req: &mut Request) -> Result<Response, Clie
         SocketAddr::V6(_) => TcpSocket::new_v6()?,
     };
 
-    println!("Addr: {addr:?}");
-
-    println!("Req: {req:?}");
-
     let mut stream = socket.connect(addr).await?;
     let (r, w) = stream.split();
     let mut connection = Connection::<_, _, Response>::new(r, w);
 
     connection.send(req).await?;
+    let resp = connection.read().await?;
 
-    println!("Wrote request to stream");
+    Ok(resp)
+}
 
-    let resp = connection.read().await?;
+/// Forwards a request to the upstream server at addr, such as `backend:8080`,
+/// and returns the response to relay back to the client
+///
+/// The request is rewritten with [`Request::to_upstream`],
+/// and hop-by-hop headers are removed from the response, RFC 9110 Section 7.6.1
+///
+/// ```ignore
+/// async fn proxy(req: &Request) -> Result<Response, ServerError> {
+///     client::forward("localhost:8080", req)
+///         .await
+///         .map_err(|_| ServerError::with_status(StatusCode::BadGateway))
+/// }
+/// ```
+pub async fn forward(addr: &str, req: &Request) -> Result<Response, ClientError> {
+    let upstream = req.to_upstream(addr);
+    let stream = TcpStream::connect(addr).await?;
+    let mut connection = Connection::<_, _, Response>::from_stream(stream);
+    connection.send(&upstream).await?;
 
+    let mut resp = connection.read().await?;
+    resp.headers.remove_hop_by_hop();
     Ok(resp)
 }
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::*;
+    use crate::message::{Method, RequestBuilder, StatusCode};
+    use crate::server::{ServerConfig, ServerError, serve_connection};
+
+    fn upstream(req: &Request) -> Result<Response, ServerError> {
+        let mut response = Response::new(StatusCode::Ok);
+        response.headers.set("Keep-Alive", "timeout=5");
+        let host = req.headers.get("Host").cloned().unwrap_or_default();
+        response.body = format!("{host} {}", req.get_url()).into();
+        Ok(response)
+    }
+
+    #[tokio::test]
+    async fn test_forward() -> Result<(), ClientError> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?.to_string();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            serve_connection(stream, &upstream, &ServerConfig::default()).await;
+        });
+
+        let req = RequestBuilder::new(Method::Get, "http://proxy.example/a?b")
+            .header("Host", "proxy.example")
+            .build();
+        let resp = forward(&addr, &req).await?;
+        assert_eq!(resp.status_line.status_code, StatusCode::Ok);
+        assert_eq!(resp.body, format!("{addr} /a?b").into_bytes());
+        assert_eq!(resp.headers.get("Keep-Alive"), None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_forward_keeps_reason_phrase() -> Result<(), ClientError> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?.to_string();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            // The request is read first, so closing doesn't reset the connection while it is sent
+            assert!(stream.read(&mut [0u8; 1024]).await.unwrap() > 0);
+            stream
+                .write_all(b"HTTP/1.1 404 Nothing To See Here\r\nContent-Length: 0\r\n\r\n")
+                .await
+                .unwrap();
+        });
+
+        let req = RequestBuilder::new(Method::Get, "/").build();
+        let resp = forward(&addr, &req).await?;
+        assert_eq!(resp.status_line.status_code, StatusCode::NotFound);
+        assert_eq!(
+            resp.status_line.to_string(),
+            "HTTP/1.1 404 Nothing To See Here"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_upload_body_from_file() -> Result<(), ClientError> {
+        fn upload(req: &Request) -> Result<Response, ServerError> {
+            let mut response = Response::new(StatusCode::Ok);
+            response.body = req.get_body().len().to_string().into();
+            Ok(response)
+        }
+
+        let content: Vec<u8> = (0..4 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+        let path =
+            std::env::temp_dir().join(format!("rust-http-{}-upload.bin", std::process::id()));
+        std::fs::write(&path, &content)?;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            serve_connection(stream, &upload, &ServerConfig::default()).await;
+        });
+
+        let req = RequestBuilder::new(Method::Put, "/upload")
+            .header("Host", addr.to_string())
+            .body_from_file(&path)?
+            .build();
+        let stream = TcpStream::connect(addr).await?;
+        let mut connection = Connection::<_, _, Response>::from_stream(stream);
+        connection.send(&req).await?;
+        let resp = connection.read().await?;
+        assert_eq!(resp.status_line.status_code, StatusCode::Ok);
+        assert_eq!(resp.body, content.len().to_string().into_bytes());
+
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_upload_body_from_reader() -> Result<(), ClientError> {
+        fn upload(req: &Request) -> Result<Response, ServerError> {
+            let mut response = Response::new(StatusCode::Ok);
+            response.body = req.body_bytes();
+            Ok(response)
+        }
+
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            serve_connection(stream, &upload, &ServerConfig::default()).await;
+        });
+
+        // A pipe whose length is only known when the writer is closed, like stdin
+        let (mut pipe, reader) = tokio::io::duplex(64);
+        let content: Vec<u8> = (0..200_000).map(|i| (i % 251) as u8).collect();
+        let written = content.clone();
+        tokio::spawn(async move {
+            for chunk in written.chunks(1000) {
+                pipe.write_all(chunk).await.unwrap();
+            }
+        });
+
+        let req = RequestBuilder::new(Method::Post, "/upload")
+            .header("Host", addr.to_string())
+            .body_from_reader(reader)
+            .build();
+        let stream = TcpStream::connect(addr).await?;
+        let mut connection = Connection::<_, _, Response>::from_stream(stream);
+        connection.send(&req).await?;
+        let resp = connection.read().await?;
+        assert_eq!(resp.status_line.status_code, StatusCode::Ok);
+        assert_eq!(resp.body, content);
+        Ok(())
+    }
+}
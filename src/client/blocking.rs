@@ -0,0 +1,156 @@
+//! A blocking client, for scripts and tests that do not run in an async runtime
+//!
+//! Requests are sent on a current-thread runtime, created the first time a thread sends a request
+//! and reused for the later requests from that thread.
+//!
+//! ```ignore
+//! let resp = client::blocking::get("http://localhost:42069/hello")?;
+//! println!("{}", String::from_utf8_lossy(&resp.body));
+//! ```
+
+use std::{cell::RefCell, future::Future};
+
+use tokio::{
+    net::TcpStream,
+    runtime::{Builder, Handle, Runtime},
+};
+
+use crate::{
+    client::error::ClientError,
+    message::{Connection, Method, Request, RequestBuilder, Response, Uri},
+};
+
+thread_local! {
+    static RUNTIME: RefCell<Option<Runtime>> = const { RefCell::new(None) };
+}
+
+/// Runs future to completion on the runtime of this thread
+fn block_on<T>(future: impl Future<Output = Result<T, ClientError>>) -> Result<T, ClientError> {
+    // Blocking inside a runtime would stall every task on its thread
+    if Handle::try_current().is_ok() {
+        return Err(ClientError::InsideRuntime);
+    }
+    RUNTIME.with(|runtime| {
+        let mut runtime = runtime.borrow_mut();
+        if runtime.is_none() {
+            *runtime = Some(Builder::new_current_thread().enable_all().build()?);
+        }
+        runtime.as_ref().unwrap().block_on(future)
+    })
+}
+
+/// Sends a GET request for an `http://` url and returns the response
+///
+/// # Errors
+///
+/// Returns [`ClientError::InvalidUrl`] if url is not an absolute `http://` url with a host,
+/// and [`ClientError::InsideRuntime`] if called from within an async runtime
+pub fn get(url: &str) -> Result<Response, ClientError> {
+    let uri = Uri::parse(url).ok_or(ClientError::InvalidUrl)?;
+    if uri.scheme() != Some("http") {
+        return Err(ClientError::InvalidUrl);
+    }
+    let host = uri.host().ok_or(ClientError::InvalidUrl)?;
+    let host = match uri.port() {
+        Some(port) => format!("{host}:{port}"),
+        None => host.to_string(),
+    };
+    let path = if uri.path().is_empty() {
+        "/"
+    } else {
+        uri.path()
+    };
+    let target = match uri.query() {
+        Some(query) => format!("{path}?{query}"),
+        None => path.to_string(),
+    };
+
+    let req = RequestBuilder::new(Method::Get, target)
+        .header("Host", host)
+        .header("Connection", "close")
+        .build();
+    send(&req)
+}
+
+/// Sends req and returns the response
+///
+/// The server is the authority of an absolute-form target, or the `Host` header,
+/// on port 80 unless another port is given
+///
+/// # Errors
+///
+/// Returns [`ClientError::UrlNotFound`] if req has no valid host,
+/// and [`ClientError::InsideRuntime`] if called from within an async runtime
+pub fn send(req: &Request) -> Result<Response, ClientError> {
+    let (host, port) = req.host().ok_or(ClientError::UrlNotFound)?;
+    let addr = format!("{host}:{}", port.unwrap_or(80));
+    block_on(async {
+        let stream = TcpStream::connect(addr).await?;
+        let mut connection = Connection::<_, _, Response>::from_stream(stream);
+        connection.send(req).await?;
+        Ok(connection.read().await?)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::message::StatusCode;
+    use crate::server::{ServerConfig, ServerError, serve_connection};
+
+    fn echo_target(req: &Request) -> Result<Response, ServerError> {
+        let mut response = Response::ok();
+        response.body = req.get_url().to_string().into();
+        Ok(response)
+    }
+
+    /// Serves one connection on another thread, returning the address it listens on
+    fn serve_once() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        std::thread::spawn(move || {
+            let runtime = Builder::new_current_thread().enable_all().build().unwrap();
+            runtime.block_on(async move {
+                listener.set_nonblocking(true).unwrap();
+                let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+                let (stream, _) = listener.accept().await.unwrap();
+                serve_connection(stream, &echo_target, &ServerConfig::default()).await;
+            });
+        });
+        addr
+    }
+
+    #[test]
+    fn test_get() -> Result<(), ClientError> {
+        let addr = serve_once();
+        let resp = get(&format!("http://{addr}/a?b=c"))?;
+        assert_eq!(resp.status_line.status_code, StatusCode::Ok);
+        assert_eq!(resp.body, &b"/a?b=c"[..]);
+
+        let addr = serve_once();
+        let req = RequestBuilder::new(Method::Get, "/send")
+            .header("Host", addr)
+            .build();
+        let resp = send(&req)?;
+        assert_eq!(resp.body, &b"/send"[..]);
+
+        assert!(matches!(
+            get("https://localhost/"),
+            Err(ClientError::InvalidUrl)
+        ));
+        assert!(matches!(get("/path"), Err(ClientError::InvalidUrl)));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_inside_runtime() {
+        assert!(matches!(
+            get("http://localhost/"),
+            Err(ClientError::InsideRuntime)
+        ));
+    }
+}
@@ -12,6 +12,14 @@ pub enum ClientError {
     #[error("Url not found")]
     UrlNotFound,
 
+    #[error("Invalid url, only http:// urls with a host are supported")]
+    InvalidUrl,
+
+    /// A blocking function was called from a thread running an async runtime,
+    /// where it would stall the other tasks, use the async functions instead
+    #[error("The blocking client can't be used inside an async runtime")]
+    InsideRuntime,
+
     #[error("Response error: {0}")]
     ResponseError(#[from] ResponseError),
 
@@ -1,3 +1,5 @@
 pub mod client;
 pub mod message;
 pub mod server;
+#[cfg(feature = "test-util")]
+pub mod test_util;
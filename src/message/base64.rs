@@ -0,0 +1,95 @@
+//! Minimal base64 (RFC 4648) with the standard alphabet and padding
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encodes input as padded base64
+pub(crate) fn encode(input: &[u8]) -> String {
+    let mut out = String::with_capacity(input.len().div_ceil(3) * 4);
+    for chunk in input.chunks(3) {
+        let mut n = 0u32;
+        for (i, &b) in chunk.iter().enumerate() {
+            n |= (b as u32) << (16 - 8 * i);
+        }
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i)) as usize & 0x3f] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn decode_byte(b: u8) -> Option<u8> {
+    match b {
+        b'A'..=b'Z' => Some(b - b'A'),
+        b'a'..=b'z' => Some(b - b'a' + 26),
+        b'0'..=b'9' => Some(b - b'0' + 52),
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => None,
+    }
+}
+
+/// Decodes padded base64
+///
+/// Returns None if the input is not valid base64
+pub(crate) fn decode(input: &[u8]) -> Option<Vec<u8>> {
+    if !input.len().is_multiple_of(4) {
+        return None;
+    }
+
+    let mut out = Vec::with_capacity(input.len() / 4 * 3);
+    for (i, quad) in input.chunks(4).enumerate() {
+        let last = i == input.len() / 4 - 1;
+        let padding = quad.iter().rev().take_while(|&&b| b == b'=').count();
+        if padding > 2 || (padding > 0 && !last) {
+            return None;
+        }
+
+        let mut n = 0u32;
+        for &b in &quad[..4 - padding] {
+            n = (n << 6) | decode_byte(b)? as u32;
+        }
+        n <<= 6 * padding;
+
+        let bytes = [(n >> 16) as u8, (n >> 8) as u8, n as u8];
+        out.extend_from_slice(&bytes[..3 - padding]);
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode(b""), Some(Vec::new()));
+        assert_eq!(decode(b"Zg=="), Some(b"f".to_vec()));
+        assert_eq!(decode(b"Zm8="), Some(b"fo".to_vec()));
+        assert_eq!(decode(b"Zm9v"), Some(b"foo".to_vec()));
+        assert_eq!(decode(b"Zm9vYmFy"), Some(b"foobar".to_vec()));
+        assert_eq!(
+            decode(b"QWxhZGRpbjpvcGVuIHNlc2FtZQ=="),
+            Some(b"Aladdin:open sesame".to_vec())
+        );
+
+        assert_eq!(decode(b"Zm9"), None);
+        assert_eq!(decode(b"Zg==Zg=="), None);
+        assert_eq!(decode(b"Z==="), None);
+        assert_eq!(decode(b"Zm9*"), None);
+    }
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(encode(b""), "");
+        assert_eq!(encode(b"f"), "Zg==");
+        assert_eq!(encode(b"fo"), "Zm8=");
+        assert_eq!(encode(b"foo"), "Zm9v");
+        assert_eq!(encode(b"foobar"), "Zm9vYmFy");
+        assert_eq!(encode(&[0xff, 0xfe, 0x00]), "//4A");
+    }
+}
@@ -1,7 +1,9 @@
+use std::io;
+
 use tokio::io::AsyncReadExt;
 
 use crate::message::{
-    Headers,
+    Codecs, Headers, TransferEncoding,
     error::{BodyError, HeadersError},
     stream_reader::StreamReader,
 };
@@ -9,26 +11,41 @@ use crate::message::{
 /// Different encoding types supported
 #[derive(Debug, PartialEq, Eq)]
 enum Encoding {
-    Nothing(usize), // Stores the size of the body. No body is size 0
-    Chunked,
-}
-
-/// Used to store state for parsing chunked body
-#[derive(Debug, PartialEq, Eq)]
-enum ChunkedState {
-    Size,        // Going to parse the size
-    Data(usize), // Going to parse the body
+    Nothing(usize),            // Stores the size of the body. No body is size 0
+    Chunked(TransferEncoding), // Stores the whole chain, ending with chunked
 }
 
 const CRLF: &[u8; 2] = b"\r\n";
 
+/// How the length of a message body was determined when it was read, RFC 9112 Section 6.3
+///
+/// Proxies and caches need it to tell a complete body from one cut short by a closed connection,
+/// and to frame the body again when forwarding it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BodyFraming {
+    /// `Content-Length`, with the length it gave
+    Length(u64),
+    /// `Transfer-Encoding: chunked`, which is decoded when the body is read
+    Chunked,
+    /// The body ended when the connection was closed
+    UntilClose,
+    /// The message has no body, or was not read from a connection
+    #[default]
+    None,
+}
+
 /// Returns the encoding type of the parser
 ///
 /// Follows https://datatracker.ietf.org/doc/html/rfc9112#name-message-body-length
 ///
+/// Any framing that could be read differently by another server is rejected,
+/// so requests can't be smuggled past a proxy in front of this server
+///
 /// # Errors
 ///
-/// This function will return an error if .
+/// This function will return an error if both `Transfer-Encoding` and `Content-Length` are present,
+/// if `Transfer-Encoding` does not end with a single `chunked`,
+/// or if `Content-Length` is not one decimal number, possibly repeated
 fn get_encoding(headers: &mut Headers) -> Result<Encoding, BodyError> {
     let transmission = headers.get("Transfer-Encoding");
     let content = headers.get("Content-Length");
@@ -38,89 +55,211 @@ fn get_encoding(headers: &mut Headers) -> Result<Encoding, BodyError> {
     }
 
     if let Some(transmission) = transmission {
-        if transmission == "chunked" {
-            return Ok(Encoding::Chunked);
-        } else {
-            return Err(BodyError::Header(HeadersError::InvalidHeaderFields));
-        }
-    } else if let Some(length) = content {
-        let cl = length.parse::<usize>();
-        if let Ok(cl) = cl {
-            return Ok(Encoding::Nothing(cl));
-        }
-
-        // if all values seperated by ',' is equal, and a number, then this value will be used
-        let mut values = length.split(',').map(|v| v.trim());
-        let Some(first) = values.next() else {
-            return Err(BodyError::Header(HeadersError::InvalidHeaderFields));
+        // Only chunked is decoded, so it has to be the final coding and applied only once.
+        // The codings before it are left in the header for the caller
+        return match TransferEncoding::parse(transmission) {
+            Some(chain) if chain.is_chunked() => Ok(Encoding::Chunked(chain)),
+            _ => Err(BodyError::Header(HeadersError::InvalidTransferEncoding)),
         };
-        if !values.all(|v| v == first) {
-            return Err(BodyError::Header(HeadersError::InvalidHeaderFields));
-        }
-        let len = first
-            .parse::<usize>()
-            .map_err(|_| BodyError::Header(HeadersError::InvalidContentLength))?;
-        return Ok(Encoding::Nothing(len));
+    }
+
+    if let Some(length) = headers.content_length() {
+        // Lengths that don't fit in usize can't be read into memory
+        return length
+            .ok()
+            .and_then(|len| usize::try_from(len).ok())
+            .map(Encoding::Nothing)
+            .ok_or(BodyError::Header(HeadersError::InvalidContentLength));
     }
 
     Ok(Encoding::Nothing(0))
 }
 
+/// Parses chunk-size = 1*HEXDIG
+fn parse_chunk_size(line: &[u8]) -> Option<usize> {
+    if line.is_empty() || !line.iter().all(u8::is_ascii_hexdigit) {
+        return None;
+    }
+    let line = std::str::from_utf8(line).ok()?;
+    usize::from_str_radix(line, 16).ok()
+}
+
+/// Reads the body framed by the headers
+///
+/// A body larger than max_size is rejected with [`BodyError::TooLarge`],
+/// before it is read when the length is known up front.
+/// The trailer section of a chunked body is limited by max_trailer_size,
+/// see [`ChunkedDecoder::new`]
+///
+/// Only the bytes of this body are consumed. Anything the client sent after it,
+/// such as bytes past `Content-Length`, is left in the reader as the start of the next message.
+/// Returns the body with the framing that determined its length
 pub async fn parse_body<R>(
     headers: &mut Headers,
     reader: &mut StreamReader<R>,
-) -> Result<Vec<u8>, BodyError>
+    max_size: Option<usize>,
+    max_trailer_size: Option<usize>,
+) -> Result<(Vec<u8>, BodyFraming), BodyError>
 where
     R: AsyncReadExt + Unpin,
 {
     let encoding = get_encoding(headers)?;
+    let max_size = max_size.unwrap_or(usize::MAX);
     match encoding {
         // No body
-        Encoding::Nothing(0) => Ok(Vec::new()),
+        Encoding::Nothing(0) if headers.get("Content-Length").is_none() => {
+            Ok((Vec::new(), BodyFraming::None))
+        }
+        Encoding::Nothing(0) => Ok((Vec::new(), BodyFraming::Length(0))),
+        Encoding::Nothing(len) if len > max_size => Err(BodyError::TooLarge),
         Encoding::Nothing(len) => {
-            // Simply read len bytes from the stream
-            Ok(reader.read_n(len).await?)
+            // Reads exactly len bytes, any excess belongs to the next pipelined message
+            let body = reader.read_n(len).await?;
+            Ok((body, BodyFraming::Length(len as u64)))
         }
-        Encoding::Chunked => {
-            let mut state = ChunkedState::Size;
+        Encoding::Chunked(chain) => {
+            let mut decoder = ChunkedDecoder::new(Some(max_size), max_trailer_size);
             let mut body = Vec::new();
-            loop {
-                match state {
-                    ChunkedState::Size => {
-                        let line = reader.read_line().await?;
-                        match usize::from_str_radix(&String::from_utf8_lossy(&line), 16) {
-                            Ok(size) => {
-                                state = ChunkedState::Data(size);
-                                if size == 0 {
-                                    let len = { body.len() };
-                                    headers.set("Content-Length", len.to_string());
-
-                                    // TODO: Will need to change if server supports more encodings
-                                    // Is supposed to removed chunked from the header, but for now only
-                                    // chunked is supported
-                                    headers.remove("Transfer-Encoding");
-                                    break;
-                                }
-                            }
-                            Err(e) => {
-                                eprintln!("Error parsing chunked-size: {e}");
-                                return Err(BodyError::MalformedChunkedSize);
-                            }
-                        }
-                    }
-                    ChunkedState::Data(len) => {
-                        let chunk = reader.read_n(len + CRLF.len()).await?;
-                        if chunk[len] != b'\r' && chunk[len + 1] != b'\n' {
-                            return Err(BodyError::MalformedChunkedBody);
-                        }
-                        body.extend_from_slice(&chunk[..len]);
-
-                        state = ChunkedState::Size;
-                    }
-                }
+            while decoder.next_chunk_into(reader, &mut body).await? {}
+
+            headers.set_content_length(body.len() as u64);
+            // Chunked is decoded, so only the codings applied before it remain
+            match chain.without_chunked() {
+                Some(rest) => headers.set("Transfer-Encoding", rest.to_string()),
+                None => headers.remove("Transfer-Encoding"),
             }
+            Ok((body, BodyFraming::Chunked))
+        }
+    }
+}
 
-            Ok(body)
+/// Decodes the transfer codings left after chunked, when every one of them is in codecs
+///
+/// `Transfer-Encoding` is then removed, and `Content-Length` set to the decoded length.
+/// A body with a coding that is not registered is returned as it is,
+/// with the codings left in the header for the caller
+///
+/// # Errors
+///
+/// Returns [`BodyError::Decode`] if decoding fails, or gives more than max_size bytes
+pub(crate) fn decode_transfer_codings(
+    headers: &mut Headers,
+    body: Vec<u8>,
+    codecs: &Codecs,
+    max_size: Option<usize>,
+) -> Result<Vec<u8>, BodyError> {
+    let Some(chain) = headers
+        .get("Transfer-Encoding")
+        .map(String::as_str)
+        .and_then(TransferEncoding::parse)
+    else {
+        return Ok(body);
+    };
+    let codings = chain.codings().iter().map(|coding| coding.as_str());
+    let Some(decoded) = codecs.decode(codings, &body, max_size.unwrap_or(usize::MAX)) else {
+        return Ok(body);
+    };
+    let decoded = decoded.map_err(BodyError::Decode)?;
+    headers.remove("Transfer-Encoding");
+    headers.set_content_length(decoded.len() as u64);
+    Ok(decoded)
+}
+
+/// Checks if the body framed by the headers is chunked
+///
+/// # Errors
+///
+/// Returns an error if the framing is invalid, see [`parse_body`]
+pub(crate) fn is_chunked(headers: &mut Headers) -> Result<bool, BodyError> {
+    Ok(matches!(get_encoding(headers)?, Encoding::Chunked(_)))
+}
+
+/// Decodes a chunked body one chunk at a time, as the chunks arrive
+///
+/// Follows RFC 9112 Section 7.1
+#[derive(Debug)]
+pub(crate) struct ChunkedDecoder {
+    read: usize,
+    max_size: usize,
+    max_trailer_size: usize,
+}
+
+impl ChunkedDecoder {
+    /// A body larger than max_size fails with [`BodyError::TooLarge`],
+    /// and a trailer section larger than max_trailer_size, counting the bytes of the field lines
+    /// without CRLF as for the header section, fails with [`HeadersError::TooLarge`].
+    /// None allows any size
+    pub(crate) fn new(max_size: Option<usize>, max_trailer_size: Option<usize>) -> ChunkedDecoder {
+        ChunkedDecoder {
+            read: 0,
+            max_size: max_size.unwrap_or(usize::MAX),
+            max_trailer_size: max_trailer_size.unwrap_or(usize::MAX),
+        }
+    }
+
+    /// Reads the next chunk, appending its data to out
+    ///
+    /// Returns false, without appending anything, when the last chunk is reached.
+    /// The trailer section after it is read and discarded
+    pub(crate) async fn next_chunk_into<R>(
+        &mut self,
+        reader: &mut StreamReader<R>,
+        out: &mut Vec<u8>,
+    ) -> Result<bool, BodyError>
+    where
+        R: AsyncReadExt + Unpin,
+    {
+        let line = reader.read_line().await?;
+        let len = match parse_chunk_size(line) {
+            Some(0) => {
+                self.skip_trailers(reader).await?;
+                return Ok(false);
+            }
+            Some(size) if size > self.max_size - self.read => return Err(BodyError::TooLarge),
+            // Leaves room for the CRLF, so a huge size can't overflow
+            Some(size) if size <= usize::MAX - CRLF.len() => size,
+            _ => {
+                eprintln!(
+                    "Error parsing chunked-size: {}",
+                    String::from_utf8_lossy(line)
+                );
+                return Err(BodyError::MalformedChunkedSize);
+            }
+        };
+
+        // Reads the chunk and the CRLF after it directly into out
+        reader.read_n_into(len + CRLF.len(), out).await?;
+        if !out.ends_with(CRLF) {
+            return Err(BodyError::MalformedChunkedBody);
+        }
+        out.truncate(out.len() - CRLF.len());
+        self.read += len;
+        Ok(true)
+    }
+
+    /// Reads and discards the trailer section after the last chunk
+    ///
+    /// trailer-section = *( field-line CRLF ), ended by an empty line
+    async fn skip_trailers<R>(&self, reader: &mut StreamReader<R>) -> Result<(), BodyError>
+    where
+        R: AsyncReadExt + Unpin,
+    {
+        let lenient = reader.is_lenient();
+        let mut remaining = self.max_trailer_size;
+        let mut first = true;
+        loop {
+            match reader.read_line_limited(remaining).await {
+                Ok(Some([])) => return Ok(()),
+                Ok(Some(line)) => remaining -= line.len(),
+                Ok(None) => return Err(BodyError::Header(HeadersError::TooLarge)),
+                // Some clients close the connection right after the last chunk,
+                // leaving out the empty line, which is accepted when lenient
+                Err(e) if first && lenient && e.kind() == io::ErrorKind::UnexpectedEof => {
+                    return Ok(());
+                }
+                Err(e) => return Err(e.into()),
+            }
+            first = false;
         }
     }
 }
@@ -155,7 +294,7 @@ mod tests {
         headers = Headers::new();
         headers.parse_one_from_line(b"Transfer-Encoding: chunked")?;
         let encoding = get_encoding(&mut headers)?;
-        assert_eq!(encoding, Encoding::Chunked);
+        assert!(matches!(encoding, Encoding::Chunked(_)));
 
         headers = Headers::new();
         headers.parse_one_from_line(b"Content-Length: 2")?;
@@ -166,16 +305,243 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_invalid_content_length() -> Result<(), RequestError> {
+        for value in [
+            "99999999999999999999999999",
+            "18446744073709551616",
+            "5,6",
+            "5,,5",
+            "+5",
+            "",
+            "abc",
+        ] {
+            let mut headers = Headers::new();
+            headers.set("Content-Length", value);
+            assert!(
+                matches!(
+                    get_encoding(&mut headers),
+                    Err(BodyError::Header(HeadersError::InvalidContentLength))
+                ),
+                "{value}"
+            );
+        }
+
+        let mut headers = Headers::new();
+        headers.set("Content-Length", usize::MAX.to_string());
+        assert_eq!(get_encoding(&mut headers)?, Encoding::Nothing(usize::MAX));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reject_smuggling() -> Result<(), RequestError> {
+        let rejected: [&[&[u8]]; 14] = [
+            &[b"Content-Length: 5", b"Content-Length: 6"],
+            &[b"Content-Length: +5"],
+            &[b"Content-Length: -5"],
+            &[b"Content-Length: 0x5"],
+            &[b"Content-Length: 5,,5"],
+            &[b"Content-Length: 99999999999999999999999"],
+            &[b"Transfer-Encoding: chunked, gzip"],
+            &[b"Transfer-Encoding: gzip"],
+            &[b"Transfer-Encoding: chunked", b"Transfer-Encoding: chunked"],
+            &[
+                b"Transfer-Encoding: chunked",
+                b"Transfer-Encoding: identity",
+            ],
+            &[b"Transfer-Encoding: xchunked"],
+            &[b"Transfer-Encoding: ,"],
+            &[b"Transfer-Encoding: chunked;foo=bar"],
+            &[b"Transfer-Encoding: chunked", b"Content-Length: 5"],
+        ];
+        for lines in rejected {
+            let mut headers = Headers::new();
+            for line in lines {
+                headers.parse_one_from_line(line)?;
+            }
+            assert!(get_encoding(&mut headers).is_err(), "{headers:?}");
+        }
+
+        let mut headers = Headers::new();
+        headers.parse_one_from_line(b"Content-Length: 5")?;
+        headers.parse_one_from_line(b"Content-Length: 5")?;
+        assert_eq!(get_encoding(&mut headers)?, Encoding::Nothing(5));
+
+        let mut headers = Headers::new();
+        headers.parse_one_from_line(b"Transfer-Encoding: Chunked")?;
+        assert!(matches!(get_encoding(&mut headers)?, Encoding::Chunked(_)));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_parse_body_chunked_chain() -> Result<(), RequestError> {
+        let mut c = Cursor::new(b"3\r\nabc\r\n0\r\n\r\n");
+        let mut reader = StreamReader::new(&mut c);
+        let mut headers = Headers::new();
+        headers.parse_one_from_line(b"Transfer-Encoding: gzip, chunked")?;
+        let (body, framing) = parse_body(&mut headers, &mut reader, None, None).await?;
+        assert_eq!(body, b"abc");
+        assert_eq!(framing, BodyFraming::Chunked);
+        // Chunked is removed, while gzip is left for the caller to decode
+        assert_eq!(headers.get("Transfer-Encoding"), Some(&"gzip".to_string()));
+        assert_eq!(headers.get("Content-Length"), Some(&"3".to_string()));
+
+        let mut headers = Headers::new();
+        headers.parse_one_from_line(b"Transfer-Encoding: chunked, gzip")?;
+        assert!(matches!(
+            get_encoding(&mut headers),
+            Err(BodyError::Header(HeadersError::InvalidTransferEncoding))
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_parse_body_chunked_missing_last_crlf() -> Result<(), RequestError> {
+        for lenient in [false, true] {
+            let mut c = Cursor::new(b"3\r\nabc\r\n0\r\n");
+            let mut reader = StreamReader::new(&mut c);
+            reader.set_lenient_lf(lenient);
+            let mut headers = Headers::new();
+            headers.parse_one_from_line(b"Transfer-Encoding: chunked")?;
+            let res = parse_body(&mut headers, &mut reader, None, None).await;
+            if lenient {
+                assert_eq!(res?.0, b"abc");
+            } else {
+                assert!(matches!(res, Err(BodyError::IO(_))), "{res:?}");
+            }
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reject_chunk_size() -> Result<(), RequestError> {
+        for input in [
+            &b"+5\r\nhello\r\n0\r\n\r\n"[..],
+            b"\r\nhello\r\n0\r\n\r\n",
+            b"ffffffffffffffff\r\nhello\r\n0\r\n\r\n",
+        ] {
+            let mut c = Cursor::new(input);
+            let mut reader = StreamReader::new(&mut c);
+            let mut headers = Headers::new();
+            headers.parse_one_from_line(b"Transfer-Encoding: chunked")?;
+            let res = parse_body(&mut headers, &mut reader, None, None).await;
+            assert!(
+                matches!(res, Err(BodyError::MalformedChunkedSize)),
+                "{}",
+                String::from_utf8_lossy(input)
+            );
+        }
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_parse_body_chunked_() -> Result<(), RequestError> {
-        let mut c = Cursor::new(b"1\r\nA\r\n4\r\n1\r\n1\r\n0\r\n");
+        let mut c = Cursor::new(b"1\r\nA\r\n4\r\n1\r\n1\r\n0\r\n\r\n");
         let mut reader = StreamReader::new(&mut c);
         let mut headers = Headers::new();
         headers.parse_one_from_line(b"Transfer-Encoding: chunked")?;
-        let body = parse_body(&mut headers, &mut reader).await?;
+        let (body, _) = parse_body(&mut headers, &mut reader, None, None).await?;
 
         assert_eq!(String::from_utf8_lossy(&body), "A1\r\n1".to_string());
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_parse_body_chunked_trailers() -> Result<(), RequestError> {
+        let mut c = Cursor::new(b"3\r\nabc\r\n0\r\nX-Checksum: 123\r\n\r\nGET / HTTP/1.1\r\n");
+        let mut reader = StreamReader::new(&mut c);
+        let mut headers = Headers::new();
+        headers.parse_one_from_line(b"Transfer-Encoding: chunked")?;
+        let (body, _) = parse_body(&mut headers, &mut reader, None, None).await?;
+        assert_eq!(body, b"abc");
+        // The trailer section is consumed, so the next message starts right after it
+        assert_eq!(reader.read_line().await?, b"GET / HTTP/1.1");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_parse_body_trailers_too_large() -> Result<(), RequestError> {
+        let input = b"0\r\nX-A: 1\r\nX-B: 2\r\n\r\n";
+        let mut headers = Headers::new();
+        headers.parse_one_from_line(b"Transfer-Encoding: chunked")?;
+
+        // Exactly the size of the field lines
+        let mut c = Cursor::new(input);
+        let mut reader = StreamReader::new(&mut c);
+        let (body, _) = parse_body(&mut headers.clone(), &mut reader, None, Some(12)).await?;
+        assert!(body.is_empty());
+
+        let mut c = Cursor::new(input);
+        let mut reader = StreamReader::new(&mut c);
+        let res = parse_body(&mut headers, &mut reader, None, Some(11)).await;
+        assert!(matches!(
+            res,
+            Err(BodyError::Header(HeadersError::TooLarge))
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_parse_body_leaves_excess() -> Result<(), RequestError> {
+        let mut c = Cursor::new(b"helloGET / HTTP/1.1\r\n");
+        let mut reader = StreamReader::new(&mut c);
+        let mut headers = Headers::new();
+        headers.parse_one_from_line(b"Content-Length: 5")?;
+        let (body, framing) = parse_body(&mut headers, &mut reader, None, None).await?;
+        assert_eq!(body, b"hello");
+        assert_eq!(framing, BodyFraming::Length(5));
+        assert_eq!(reader.read_line().await?, b"GET / HTTP/1.1");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_parse_body_no_body() -> Result<(), RequestError> {
+        let mut c = Cursor::new(b"GET / HTTP/1.1\r\n");
+        let mut reader = StreamReader::new(&mut c);
+        let (body, framing) = parse_body(&mut Headers::new(), &mut reader, None, None).await?;
+        assert!(body.is_empty());
+        assert_eq!(framing, BodyFraming::None);
+
+        let mut headers = Headers::new();
+        headers.parse_one_from_line(b"Content-Length: 0")?;
+        let (body, framing) = parse_body(&mut headers, &mut reader, None, None).await?;
+        assert!(body.is_empty());
+        assert_eq!(framing, BodyFraming::Length(0));
+        assert_eq!(reader.read_line().await?, b"GET / HTTP/1.1");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_parse_body_too_large() -> Result<(), RequestError> {
+        let mut c = Cursor::new(b"hello");
+        let mut reader = StreamReader::new(&mut c);
+        let mut headers = Headers::new();
+        headers.parse_one_from_line(b"Content-Length: 5")?;
+        let res = parse_body(&mut headers, &mut reader, Some(4), None).await;
+        assert!(matches!(res, Err(BodyError::TooLarge)));
+
+        let mut c = Cursor::new(b"hello");
+        let mut reader = StreamReader::new(&mut c);
+        let (body, _) = parse_body(&mut headers, &mut reader, Some(5), None).await?;
+        assert_eq!(body, b"hello");
+
+        let mut c = Cursor::new(b"3\r\nabc\r\n3\r\ndef\r\n0\r\n");
+        let mut reader = StreamReader::new(&mut c);
+        let mut headers = Headers::new();
+        headers.parse_one_from_line(b"Transfer-Encoding: chunked")?;
+        let res = parse_body(&mut headers, &mut reader, Some(5), None).await;
+        assert!(matches!(res, Err(BodyError::TooLarge)));
+
+        Ok(())
+    }
 }
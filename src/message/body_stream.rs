@@ -0,0 +1,138 @@
+use std::{
+    io,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+
+use bytes::{Buf, Bytes};
+use tokio::io::{AsyncRead, ReadBuf};
+use tokio::sync::mpsc;
+
+use crate::message::BodyError;
+
+/// Sends the decoded chunks of a request body to its [`BodyStream`]
+pub(crate) type BodySender = mpsc::Sender<Result<Bytes, BodyError>>;
+
+/// Holds the body stream of a request until it is taken, see [`Request::body_stream`](crate::message::Request::body_stream)
+pub(crate) type BodyStreamSlot = Arc<Mutex<Option<BodyStream>>>;
+
+/// A request body that is read while it arrives, instead of being buffered before the handler is called
+///
+/// Chunks are delivered as they are decoded, either with [`BodyStream::next_chunk`]
+/// or by reading it as an [`AsyncRead`].
+/// At most one chunk is read from the connection ahead of the handler,
+/// so a handler that stops reading stops the client from sending more
+#[derive(Debug)]
+pub struct BodyStream {
+    receiver: mpsc::Receiver<Result<Bytes, BodyError>>,
+    chunk: Bytes,
+}
+
+impl BodyStream {
+    /// Creates a stream, and the sender that the connection sends the chunks with
+    ///
+    /// The sender is dropped after the last chunk, which ends the stream
+    pub(crate) fn channel() -> (BodySender, BodyStream) {
+        let (sender, receiver) = mpsc::channel(1);
+        let stream = BodyStream {
+            receiver,
+            chunk: Bytes::new(),
+        };
+        (sender, stream)
+    }
+
+    /// Returns the next chunk of the body, or None after the last one
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the body is malformed, larger than the maximum size,
+    /// or the connection fails while reading it
+    pub async fn next_chunk(&mut self) -> Option<Result<Bytes, BodyError>> {
+        if !self.chunk.is_empty() {
+            return Some(Ok(std::mem::take(&mut self.chunk)));
+        }
+        self.receiver.recv().await
+    }
+
+    /// Reads the rest of the body into memory
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error from [`BodyStream::next_chunk`]
+    pub async fn collect(mut self) -> Result<Vec<u8>, BodyError> {
+        let mut body = Vec::new();
+        while let Some(chunk) = self.next_chunk().await {
+            body.extend_from_slice(&chunk?);
+        }
+        Ok(body)
+    }
+}
+
+impl AsyncRead for BodyStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        while self.chunk.is_empty() {
+            match self.receiver.poll_recv(cx) {
+                Poll::Ready(Some(Ok(chunk))) => self.chunk = chunk,
+                Poll::Ready(Some(Err(BodyError::IO(e)))) => return Poll::Ready(Err(e)),
+                Poll::Ready(Some(Err(e))) => {
+                    return Poll::Ready(Err(io::Error::new(io::ErrorKind::InvalidData, e)));
+                }
+                // The end of the body
+                Poll::Ready(None) => return Poll::Ready(Ok(())),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        let n = self.chunk.len().min(buf.remaining());
+        buf.put_slice(&self.chunk[..n]);
+        self.chunk.advance(n);
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use tokio::io::AsyncReadExt;
+
+    #[tokio::test]
+    async fn test_next_chunk() {
+        let (sender, mut stream) = BodyStream::channel();
+        tokio::spawn(async move {
+            for chunk in ["abc", "de"] {
+                sender.send(Ok(Bytes::from(chunk))).await.unwrap();
+            }
+        });
+        assert_eq!(stream.next_chunk().await.unwrap().unwrap(), "abc");
+        assert_eq!(stream.next_chunk().await.unwrap().unwrap(), "de");
+        assert!(stream.next_chunk().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_async_read() -> io::Result<()> {
+        let (sender, mut stream) = BodyStream::channel();
+        tokio::spawn(async move {
+            for chunk in ["hello ", "world"] {
+                sender.send(Ok(Bytes::from(chunk))).await.unwrap();
+            }
+        });
+        let mut buf = [0u8; 4];
+        stream.read_exact(&mut buf).await?;
+        assert_eq!(&buf, b"hell");
+        let mut rest = String::new();
+        stream.read_to_string(&mut rest).await?;
+        assert_eq!(rest, "o world");
+
+        let (sender, mut stream) = BodyStream::channel();
+        sender.send(Err(BodyError::TooLarge)).await.unwrap();
+        let err = stream.read_to_end(&mut Vec::new()).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,240 @@
+//! Content and transfer codings, such as gzip, that compress a message body
+//!
+//! A [`Codecs`] registry maps coding names, as they appear in `Content-Encoding`,
+//! `Transfer-Encoding` and `Accept-Encoding`, to a [`Codec`] implementing them.
+//! [`Codecs::default`] has gzip and deflate, and br with the `brotli` feature,
+//! and other codings can be added with [`Codecs::register`].
+
+use std::{
+    fmt::Debug,
+    io::{self, Read, Write},
+    sync::Arc,
+};
+
+use flate2::{
+    Compression,
+    read::{GzDecoder, ZlibDecoder},
+    write::{GzEncoder, ZlibEncoder},
+};
+use indexmap::IndexMap;
+
+/// Encodes and decodes bodies with one coding
+pub trait Codec: Send + Sync {
+    /// Encodes data
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if encoding fails
+    fn encode(&self, data: &[u8]) -> io::Result<Vec<u8>>;
+
+    /// Decodes data, producing at most limit bytes
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if data is not valid for the coding,
+    /// or decodes to more than limit bytes, so a small body can't expand to fill the memory
+    fn decode(&self, data: &[u8], limit: usize) -> io::Result<Vec<u8>>;
+}
+
+/// Reads all of reader, failing if it gives more than limit bytes
+fn read_limited(reader: impl Read, limit: usize) -> io::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    reader
+        .take((limit as u64).saturating_add(1))
+        .read_to_end(&mut out)?;
+    if out.len() > limit {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "decoded body larger than the limit",
+        ));
+    }
+    Ok(out)
+}
+
+/// The gzip coding, RFC 9110 Section 8.4.1.3
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Gzip;
+
+impl Codec for Gzip {
+    fn encode(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data)?;
+        encoder.finish()
+    }
+
+    fn decode(&self, data: &[u8], limit: usize) -> io::Result<Vec<u8>> {
+        read_limited(GzDecoder::new(data), limit)
+    }
+}
+
+/// The deflate coding, which is the zlib format, RFC 9110 Section 8.4.1.2
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Deflate;
+
+impl Codec for Deflate {
+    fn encode(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data)?;
+        encoder.finish()
+    }
+
+    fn decode(&self, data: &[u8], limit: usize) -> io::Result<Vec<u8>> {
+        read_limited(ZlibDecoder::new(data), limit)
+    }
+}
+
+/// The br coding, RFC 7932
+#[cfg(feature = "brotli")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Brotli;
+
+#[cfg(feature = "brotli")]
+impl Codec for Brotli {
+    fn encode(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        // Quality 5 and a 4 MiB window, a common choice for compressing on the fly
+        let mut encoder = brotli::CompressorWriter::new(Vec::new(), 4096, 5, 22);
+        encoder.write_all(data)?;
+        Ok(encoder.into_inner())
+    }
+
+    fn decode(&self, data: &[u8], limit: usize) -> io::Result<Vec<u8>> {
+        read_limited(brotli::Decompressor::new(data, 4096), limit)
+    }
+}
+
+/// The codings a server can decode and encode, by their lowercase names
+///
+/// ```ignore
+/// let codecs = Codecs::default().register("x-custom", MyCodec);
+/// let server = Server::builder().codecs(codecs);
+/// ```
+#[derive(Clone)]
+pub struct Codecs(IndexMap<String, Arc<dyn Codec>>);
+
+impl Codecs {
+    /// Creates an empty registry, without any codings
+    pub fn new() -> Codecs {
+        Codecs(IndexMap::new())
+    }
+
+    /// Adds codec for the coding name, replacing any codec already registered for it
+    ///
+    /// The name is case-insensitive. Codings registered first are preferred
+    /// when the client weighs several of them equally in `Accept-Encoding`
+    pub fn register(mut self, name: impl Into<String>, codec: impl Codec + 'static) -> Self {
+        self.0
+            .insert(name.into().to_ascii_lowercase(), Arc::new(codec));
+        self
+    }
+
+    /// Returns the codec for the coding name, the name is case-insensitive
+    pub fn get(&self, name: &str) -> Option<&dyn Codec> {
+        self.0
+            .get(&name.to_ascii_lowercase())
+            .map(|codec| codec.as_ref())
+    }
+
+    /// The names of the registered codings, in the order they were registered
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.0.keys().map(String::as_str)
+    }
+
+    /// Decodes data that had the codings applied in order, so the last one is undone first
+    ///
+    /// Returns None if any of the codings is not registered
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if decoding fails, or the result is larger than limit
+    pub fn decode<'a>(
+        &self,
+        codings: impl DoubleEndedIterator<Item = &'a str>,
+        data: &[u8],
+        limit: usize,
+    ) -> Option<io::Result<Vec<u8>>> {
+        let codecs = codings
+            .map(|coding| self.get(coding))
+            .collect::<Option<Vec<_>>>()?;
+        let mut data = data.to_vec();
+        for codec in codecs.iter().rev() {
+            data = match codec.decode(&data, limit) {
+                Ok(data) => data,
+                Err(e) => return Some(Err(e)),
+            };
+        }
+        Some(Ok(data))
+    }
+}
+
+/// Has gzip, x-gzip and deflate, and br with the `brotli` feature
+impl Default for Codecs {
+    fn default() -> Self {
+        let codecs = Codecs::new()
+            .register("gzip", Gzip)
+            .register("x-gzip", Gzip)
+            .register("deflate", Deflate);
+        #[cfg(feature = "brotli")]
+        let codecs = codecs.register("br", Brotli);
+        codecs
+    }
+}
+
+impl Debug for Codecs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.names()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_round_trip() -> io::Result<()> {
+        let data = b"hello hello hello hello hello".repeat(10);
+        let codecs = Codecs::default();
+        for name in codecs.names() {
+            let codec = codecs.get(name).unwrap();
+            let encoded = codec.encode(&data)?;
+            assert!(encoded.len() < data.len(), "{name}");
+            assert_eq!(codec.decode(&encoded, data.len())?, data, "{name}");
+            assert!(codec.decode(&encoded, data.len() - 1).is_err(), "{name}");
+            assert!(codec.decode(b"not encoded", data.len()).is_err(), "{name}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_chain() -> io::Result<()> {
+        let codecs = Codecs::default();
+        let encoded = Deflate.encode(&Gzip.encode(b"abc")?)?;
+        let decoded = codecs.decode(["GZIP", "deflate"].into_iter(), &encoded, 100);
+        assert_eq!(decoded.unwrap()?, b"abc");
+
+        assert!(
+            codecs
+                .decode(["gzip", "compress"].into_iter(), &encoded, 100)
+                .is_none()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_register() {
+        struct Identity;
+        impl Codec for Identity {
+            fn encode(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+                Ok(data.to_vec())
+            }
+            fn decode(&self, data: &[u8], _: usize) -> io::Result<Vec<u8>> {
+                Ok(data.to_vec())
+            }
+        }
+
+        let codecs = Codecs::new().register("X-Same", Identity);
+        assert!(codecs.get("x-same").is_some());
+        assert!(codecs.get("gzip").is_none());
+        assert_eq!(codecs.names().collect::<Vec<_>>(), ["x-same"]);
+    }
+}
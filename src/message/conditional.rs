@@ -0,0 +1,210 @@
+//! Conditional requests as described in RFC 9110 Section 13
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::message::{Method, Request, Response, StatusCode, http_date};
+
+/// Checks if a comma separated list of entity-tags contains the etag
+///
+/// Uses the weak comparison, where `W/"a"` matches `"a"`
+fn etag_list_contains(list: &str, etag: &str) -> bool {
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    let etag = opaque(etag);
+    list.split(',')
+        .any(|tag| tag.trim() == "*" || opaque(tag) == etag)
+}
+
+/// Checks if a comma separated list of entity-tags contains the etag
+///
+/// Uses the strong comparison, where weak tags never match, RFC 9110 Section 8.8.3.2
+fn etag_list_contains_strong(list: &str, etag: &str) -> bool {
+    if etag.trim().starts_with("W/") {
+        return false;
+    }
+    list.split(',')
+        .any(|tag| tag.trim() == "*" || tag.trim() == etag.trim())
+}
+
+/// Truncates the time to whole seconds, as HTTP dates has no higher precision
+fn whole_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+impl Request {
+    /// Checks if the client already has the current representation,
+    /// which means a 304 Not Modified can be sent instead
+    ///
+    /// `If-None-Match` is compared against the etag, and only when it is missing
+    /// is `If-Modified-Since` compared against last_modified.
+    /// Only GET and HEAD requests can be not modified
+    pub fn is_not_modified(&self, etag: Option<&str>, last_modified: Option<SystemTime>) -> bool {
+        if !matches!(self.get_method(), Method::Get | Method::Head) {
+            return false;
+        }
+
+        if let Some(if_none_match) = self.headers.get("If-None-Match") {
+            return etag.is_some_and(|etag| etag_list_contains(if_none_match, etag));
+        }
+
+        let Some(last_modified) = last_modified else {
+            return false;
+        };
+        match self
+            .headers
+            .get("If-Modified-Since")
+            .and_then(|v| http_date::parse(v))
+        {
+            Some(since) => whole_seconds(last_modified) <= whole_seconds(since),
+            None => false,
+        }
+    }
+}
+
+impl Request {
+    /// Evaluates the preconditions of a state changing request, such as PUT or DELETE,
+    /// against the current representation, RFC 9110 Section 13.2.2
+    ///
+    /// `If-Match` is compared against the etag, and only when it is missing
+    /// is `If-Unmodified-Since` compared against last_modified.
+    /// A missing etag means there is no current representation, which `If-Match: *` fails.
+    /// Returns a `412 Precondition Failed` response when a precondition fails,
+    /// and None when the request can be performed
+    ///
+    /// ```ignore
+    /// if let Some(response) = req.precondition_failed(Some(&etag), Some(modified)) {
+    ///     return Ok(response);
+    /// }
+    /// ```
+    pub fn precondition_failed(
+        &self,
+        etag: Option<&str>,
+        last_modified: Option<SystemTime>,
+    ) -> Option<Response> {
+        let passes = if let Some(if_match) = self.headers.get("If-Match") {
+            etag.is_some_and(|etag| etag_list_contains_strong(if_match, etag))
+        } else {
+            let since = self
+                .headers
+                .get("If-Unmodified-Since")
+                .and_then(|v| http_date::parse(v));
+            match (since, last_modified) {
+                (Some(since), Some(last_modified)) => {
+                    whole_seconds(last_modified) <= whole_seconds(since)
+                }
+                // An invalid date is ignored, and without a date there is nothing to compare
+                _ => true,
+            }
+        };
+
+        (!passes).then(|| Response::new(StatusCode::PreconditionFailed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::message::RequestBuilder;
+
+    #[test]
+    fn test_etag_list_contains() {
+        assert!(etag_list_contains("\"a\"", "\"a\""));
+        assert!(etag_list_contains("\"b\", \"a\"", "\"a\""));
+        assert!(etag_list_contains("W/\"a\"", "\"a\""));
+        assert!(etag_list_contains("*", "\"a\""));
+        assert!(!etag_list_contains("\"b\"", "\"a\""));
+    }
+
+    #[test]
+    fn test_etag_list_contains_strong() {
+        assert!(etag_list_contains_strong("\"a\"", "\"a\""));
+        assert!(etag_list_contains_strong("\"b\", \"a\"", "\"a\""));
+        assert!(etag_list_contains_strong("*", "\"a\""));
+        assert!(!etag_list_contains_strong("W/\"a\"", "\"a\""));
+        assert!(!etag_list_contains_strong("\"a\"", "W/\"a\""));
+    }
+
+    #[test]
+    fn test_precondition_failed() {
+        let modified = UNIX_EPOCH + Duration::from_secs(784111777);
+        let failed = |res: Option<Response>| {
+            res.is_some_and(|r| r.status_line.status_code == StatusCode::PreconditionFailed)
+        };
+
+        let req = RequestBuilder::new(Method::Put, "/").build();
+        assert!(
+            req.precondition_failed(Some("\"a\""), Some(modified))
+                .is_none()
+        );
+
+        let req = RequestBuilder::new(Method::Put, "/")
+            .header("If-Match", "\"a\"")
+            .build();
+        assert!(req.precondition_failed(Some("\"a\""), None).is_none());
+        assert!(failed(req.precondition_failed(Some("\"b\""), None)));
+        assert!(failed(req.precondition_failed(None, None)));
+
+        let req = RequestBuilder::new(Method::Delete, "/")
+            .header("If-Match", "*")
+            .build();
+        assert!(req.precondition_failed(Some("\"a\""), None).is_none());
+        assert!(failed(req.precondition_failed(None, None)));
+
+        let req = RequestBuilder::new(Method::Put, "/")
+            .header("If-Unmodified-Since", "Sun, 06 Nov 1994 08:49:37 GMT")
+            .build();
+        assert!(req.precondition_failed(None, Some(modified)).is_none());
+        assert!(failed(req.precondition_failed(
+            None,
+            Some(modified + Duration::from_secs(1))
+        )));
+        assert!(req.precondition_failed(None, None).is_none());
+
+        // If-Match takes precedence over If-Unmodified-Since
+        let req = RequestBuilder::new(Method::Put, "/")
+            .header("If-Match", "\"a\"")
+            .header("If-Unmodified-Since", "Sun, 06 Nov 1994 08:49:37 GMT")
+            .build();
+        let later = modified + Duration::from_secs(1);
+        assert!(
+            req.precondition_failed(Some("\"a\""), Some(later))
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_is_not_modified() {
+        let modified = UNIX_EPOCH + Duration::from_secs(784111777);
+
+        let req = RequestBuilder::new(Method::Get, "/").build();
+        assert!(!req.is_not_modified(Some("\"a\""), Some(modified)));
+
+        let req = RequestBuilder::new(Method::Get, "/")
+            .header("If-None-Match", "\"a\"")
+            .build();
+        assert!(req.is_not_modified(Some("\"a\""), Some(modified)));
+        assert!(!req.is_not_modified(Some("\"b\""), Some(modified)));
+
+        let req = RequestBuilder::new(Method::Post, "/")
+            .header("If-None-Match", "\"a\"")
+            .build();
+        assert!(!req.is_not_modified(Some("\"a\""), Some(modified)));
+
+        let req = RequestBuilder::new(Method::Get, "/")
+            .header("If-Modified-Since", "Sun, 06 Nov 1994 08:49:37 GMT")
+            .build();
+        assert!(req.is_not_modified(None, Some(modified)));
+        assert!(!req.is_not_modified(None, Some(modified + Duration::from_secs(1))));
+        assert!(req.is_not_modified(None, Some(modified + Duration::from_millis(10))));
+
+        // If-None-Match takes precedence over If-Modified-Since
+        let req = RequestBuilder::new(Method::Get, "/")
+            .header("If-None-Match", "\"b\"")
+            .header("If-Modified-Since", "Sun, 06 Nov 1994 08:49:37 GMT")
+            .build();
+        assert!(!req.is_not_modified(Some("\"a\""), Some(modified)));
+    }
+}
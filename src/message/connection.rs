@@ -1,12 +1,20 @@
-use std::io;
+use std::{io, sync::Arc};
 
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadHalf, WriteHalf};
 
 use crate::message::{
-    Headers, Request, RequestError, RequestLine, Response, ResponseError, StatusLine,
-    body::parse_body, stream_reader::StreamReader,
+    BodyError, BodyFraming, Bytes, Codecs, DEFAULT_BUFFER_SIZE, Headers, HeadersError, Method,
+    Request, RequestError, RequestLine, RequestLineError, Response, ResponseError, StatusCode,
+    StatusLine,
+    body::{ChunkedDecoder, decode_transfer_codings, is_chunked, parse_body},
+    headers::is_tchar,
+    stream_reader::StreamReader,
 };
 
+/// Number of header fields that are allocated room for up front when reading a message,
+/// enough for most requests to be parsed without growing the map
+const HEADERS_CAPACITY: usize = 16;
+
 pub struct Connection<R, W, T>
 where
     R: AsyncReadExt + Unpin,
@@ -16,6 +24,17 @@ where
 {
     reader: StreamReader<R>,
     writer: W,
+    max_body_size: Option<usize>,
+    max_request_line_size: Option<usize>,
+    max_header_size: Option<usize>,
+    lenient_obs_fold: bool,
+    stream_chunked: bool,
+    // The chunked body of the last request, while it is being streamed
+    chunked: Option<ChunkedDecoder>,
+    codecs: Option<Arc<Codecs>>,
+    body_to_eof: bool,
+    // The body of the last request was read until the client closed the connection
+    at_eof: bool,
     t: std::marker::PhantomData<T>,
 }
 
@@ -25,12 +44,191 @@ where
     W: AsyncWriteExt + Unpin,
 {
     pub fn new(reader: R, writer: W) -> Self {
+        Self::with_capacity(reader, writer, DEFAULT_BUFFER_SIZE)
+    }
+
+    /// Creates a [`Connection`] with a read buffer of capacity bytes
+    ///
+    /// # Panics
+    ///
+    /// Panics if capacity is 0
+    pub fn with_capacity(reader: R, writer: W, capacity: usize) -> Self {
         Self {
-            reader: StreamReader::new(reader),
+            reader: StreamReader::with_capacity(reader, capacity),
             writer,
+            max_body_size: None,
+            max_request_line_size: None,
+            max_header_size: None,
+            lenient_obs_fold: false,
+            stream_chunked: false,
+            chunked: None,
+            codecs: None,
+            body_to_eof: false,
+            at_eof: false,
             t: std::marker::PhantomData,
         }
     }
+
+    /// Returns the reader, the bytes buffered from it but not yet parsed, and the writer
+    ///
+    /// Used to take over the stream, e.g. after switching protocols
+    pub fn into_parts(self) -> (R, Vec<u8>, W) {
+        let (reader, buffered) = self.reader.into_parts();
+        (reader, buffered, self.writer)
+    }
+
+    /// Returns the next n bytes without consuming them, see [`StreamReader::peek`]
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading fails, or n is larger than the buffer capacity
+    pub async fn peek(&mut self, n: usize) -> io::Result<&[u8]> {
+        self.reader.peek(n).await
+    }
+
+    /// Sets whether a bare LF is accepted as a line ending, see [`StreamReader::set_lenient_lf`]
+    pub fn set_lenient_lf(&mut self, lenient: bool) {
+        self.reader.set_lenient_lf(lenient);
+    }
+
+    /// Sets whether obsolete line folding in the headers is unfolded instead of rejected
+    ///
+    /// Off by default, so a field line starting with whitespace fails with
+    /// [`HeadersError::ObsoleteLineFolding`](crate::message::HeadersError::ObsoleteLineFolding).
+    /// When on, the fold is replaced by a space as RFC 9112 Section 5.2 allows
+    pub fn set_lenient_obs_fold(&mut self, lenient: bool) {
+        self.lenient_obs_fold = lenient;
+    }
+
+    /// Sets the codecs used to decode transfer codings other than chunked, such as gzip
+    ///
+    /// None, the default, leaves those codings in `Transfer-Encoding` with the body undecoded.
+    /// Bodies with a coding that is not in codecs are also left undecoded,
+    /// as are streamed bodies, see [`Connection::set_stream_chunked`]
+    pub fn set_codecs(&mut self, codecs: Option<Arc<Codecs>>) {
+        self.codecs = codecs;
+    }
+
+    /// Decodes the transfer codings of a body that is read, see [`Connection::set_codecs`]
+    fn decode_body(&self, headers: &mut Headers, body: Vec<u8>) -> Result<Vec<u8>, BodyError> {
+        match &self.codecs {
+            Some(codecs) => decode_transfer_codings(headers, body, codecs, self.max_body_size),
+            None => Ok(body),
+        }
+    }
+
+    /// Reads the field lines up to and including the empty line ending the header section
+    async fn read_headers<E>(&mut self) -> Result<Headers, E>
+    where
+        E: From<io::Error> + From<HeadersError>,
+    {
+        let mut headers = Headers::with_capacity(HEADERS_CAPACITY);
+        let mut last_name: Option<String> = None;
+        let mut remaining = self.max_header_size.unwrap_or(usize::MAX);
+        loop {
+            let line = match self.reader.read_line_limited(remaining).await {
+                // The blank line ending the header section never came
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                    return Err(HeadersError::Incomplete.into());
+                }
+                line => line?,
+            };
+            let Some(line) = line else {
+                return Err(HeadersError::TooLarge.into());
+            };
+            if line.is_empty() {
+                break;
+            }
+            remaining -= line.len();
+
+            if line[0] == b' ' || line[0] == b'\t' {
+                match &last_name {
+                    Some(name) if self.lenient_obs_fold => headers.unfold_line(name, line)?,
+                    _ => return Err(HeadersError::ObsoleteLineFolding.into()),
+                }
+                continue;
+            }
+            last_name = Some(headers.parse_field_line(line)?);
+        }
+        Ok(headers)
+    }
+
+    /// Sets the largest body that is read, larger bodies fail with [`BodyError::TooLarge`](crate::message::BodyError::TooLarge),
+    /// or [`RequestError::BodyTooLarge`] when reading requests
+    ///
+    /// None, the default, reads bodies of any size
+    pub fn set_max_body_size(&mut self, max_body_size: Option<usize>) {
+        self.max_body_size = max_body_size;
+    }
+
+    /// Sets the longest request line that is read, counting the bytes without CRLF
+    ///
+    /// Longer lines fail with [`RequestError::UriTooLong`] when the target makes them too long,
+    /// and with [`RequestLineError::TooLong`] otherwise, e.g. for a method that never ends.
+    /// None, the default, reads request lines of any length
+    pub fn set_max_request_line_size(&mut self, max_request_line_size: Option<usize>) {
+        self.max_request_line_size = max_request_line_size;
+    }
+
+    /// Sets the largest header section that is read, counting the bytes of the field lines without CRLF
+    ///
+    /// Larger header sections fail with [`HeadersError::TooLarge`],
+    /// or [`RequestError::HeadersTooLarge`] when reading requests.
+    /// The trailer section of a chunked body has the same limit.
+    /// None, the default, reads header sections of any size
+    pub fn set_max_header_size(&mut self, max_header_size: Option<usize>) {
+        self.max_header_size = max_header_size;
+    }
+}
+
+impl<S, T> Connection<ReadHalf<S>, WriteHalf<S>, T>
+where
+    S: AsyncRead + AsyncWrite,
+{
+    /// Creates a [`Connection`] over one stream that is both read from and written to,
+    /// such as a `TcpStream`, a `UnixStream` or an in-memory `tokio::io::duplex`
+    ///
+    /// Use `Connection<_, _, Request>` to read requests and respond to them,
+    /// and `Connection<_, _, Response>` to send requests and read the responses
+    ///
+    /// ```ignore
+    /// let stream = TcpStream::connect("localhost:42069").await?;
+    /// let mut connection = Connection::<_, _, Response>::from_stream(stream);
+    /// connection.send(&request).await?;
+    /// let response = connection.read().await?;
+    /// ```
+    pub fn from_stream(stream: S) -> Self {
+        let (reader, writer) = tokio::io::split(stream);
+        Self::new(reader, writer)
+    }
+}
+
+/// Checks if a request has a body that ends when the client closes the connection,
+/// which only legacy HTTP/1.0 clients send
+fn is_delimited_by_close(line: &RequestLine, headers: &Headers) -> bool {
+    line.version.is_http_1_0()
+        && matches!(line.method, Method::Post | Method::Put)
+        && headers.get("Content-Length").is_none()
+        && headers.get("Transfer-Encoding").is_none()
+}
+
+/// Checks if a request line that is too long starts with a method and the start of the target,
+/// so it is the target that is too long, RFC 9112 Section 3
+///
+/// The line is only cut short within the target or the version,
+/// and a version is never longer than `HTTP/1.1`
+fn is_target_too_long(start: &[u8]) -> bool {
+    let Some(sp) = start.iter().position(|&b| b == b' ') else {
+        return false;
+    };
+    let (method, rest) = (&start[..sp], &start[sp + 1..]);
+    if method.is_empty() || !method.iter().all(|&b| is_tchar(b)) {
+        return false;
+    }
+    match rest.iter().position(|&b| b == b' ') {
+        Some(sp) => rest.len() - sp - 1 <= "HTTP/1.1".len(),
+        None => true,
+    }
 }
 
 // Reads requests from the stream and sends responses
@@ -39,35 +237,207 @@ where
     R: AsyncReadExt + Unpin,
     W: AsyncWriteExt + Unpin,
 {
+    /// Reads the next request
+    ///
+    /// The fields named in the `Connection` header are removed, as they only apply to this connection,
+    /// RFC 9110 Section 7.6.1. `Connection` itself, `Upgrade` and the framing fields are kept
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RequestError::ConnectionClosed`] if the stream ends before the next request starts,
+    /// [`HeadersError::Incomplete`] if it ends in the header section,
+    /// and [`RequestError::IO`] if it ends in another part of the request
     pub async fn read(&mut self) -> Result<Request, RequestError> {
-        let req_line = {
-            let line = self.reader.read_line().await?;
-            RequestLine::from_line(&line)
-        }?;
+        // The rest of a streamed body comes before the next request
+        while self.read_chunk().await?.is_some() {}
 
-        let mut headers = Headers::new();
-        loop {
-            let line = self.reader.read_line().await?;
-            if line.is_empty() {
-                break;
+        // EOF before the request line is a clean close, while EOF inside a request is an IO error
+        if self.reader.peek(1).await?.is_empty() {
+            return Err(RequestError::ConnectionClosed);
+        }
+
+        let max_line = self.max_request_line_size.unwrap_or(usize::MAX);
+        let req_line = self
+            .reader
+            .read_line_limited(max_line)
+            .await?
+            .map(RequestLine::from_line)
+            .transpose()?;
+        let Some(req_line) = req_line else {
+            if is_target_too_long(self.reader.overlong_line()) {
+                return Err(RequestError::UriTooLong);
             }
+            return Err(RequestLineError::TooLong.into());
+        };
 
-            headers.parse_one_from_line(&line)?;
+        let mut headers = match self.read_headers::<RequestError>().await {
+            Err(RequestError::Header(HeadersError::TooLarge)) => {
+                return Err(RequestError::HeadersTooLarge);
+            }
+            headers => headers?,
+        };
+        headers.remove_connection_options();
+
+        if self.stream_chunked && is_chunked(&mut headers)? {
+            self.chunked = Some(ChunkedDecoder::new(
+                self.max_body_size,
+                self.max_header_size,
+            ));
+            return Ok(Request {
+                line: req_line,
+                headers,
+                body: Bytes::new(),
+                interim: None,
+                body_stream: None,
+                body_file: None,
+                body_reader: None,
+                framing: BodyFraming::Chunked,
+            });
         }
 
-        let body = parse_body(&mut headers, &mut self.reader).await?;
+        let (body, framing) = if self.body_to_eof && is_delimited_by_close(&req_line, &headers) {
+            let max_size = self.max_body_size.unwrap_or(usize::MAX);
+            let body = self.reader.read_to_end_limited(max_size).await?;
+            self.at_eof = true;
+            (
+                body.ok_or(RequestError::BodyTooLarge)?,
+                BodyFraming::UntilClose,
+            )
+        } else {
+            let body = parse_body(
+                &mut headers,
+                &mut self.reader,
+                self.max_body_size,
+                self.max_header_size,
+            );
+            match body.await {
+                Err(BodyError::TooLarge) => return Err(RequestError::BodyTooLarge),
+                Err(BodyError::Header(HeadersError::TooLarge)) => {
+                    return Err(RequestError::HeadersTooLarge);
+                }
+                body => body?,
+            }
+        };
+        let body = self.decode_body(&mut headers, body)?;
 
         Ok(Request {
             line: req_line,
             headers,
-            body,
+            body: body.into(),
+            interim: None,
+            body_stream: None,
+            body_file: None,
+            body_reader: None,
+            framing,
         })
     }
 
-    pub async fn respond(&mut self, response: &mut Response) -> io::Result<()> {
+    /// Sets whether chunked request bodies are left to be read with [`Connection::read_chunk`]
+    ///
+    /// Off by default, so [`Connection::read`] returns requests with the whole body.
+    /// When on, chunked requests are returned with an empty body as soon as the head is read
+    pub fn set_stream_chunked(&mut self, stream_chunked: bool) {
+        self.stream_chunked = stream_chunked;
+    }
+
+    /// Reads the next chunk of the streamed body of the last request,
+    /// see [`Connection::set_stream_chunked`]
+    ///
+    /// Returns None after the last chunk, or if the body is not streamed.
+    /// A streamed body that is not read to the end is skipped by the next [`Connection::read`]
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the body is malformed, larger than the maximum body size, or reading fails.
+    /// The rest of the body can't be read after an error
+    pub async fn read_chunk(&mut self) -> Result<Option<Bytes>, BodyError> {
+        self.split_body().0.read_chunk().await
+    }
+
+    /// Splits the connection into the streamed body of the last request and the writer,
+    /// so the rest of the body can be read while the response is written
+    pub(crate) fn split_body(&mut self) -> (StreamedBody<'_, R>, &mut W) {
+        let body = StreamedBody {
+            reader: &mut self.reader,
+            decoder: &mut self.chunked,
+        };
+        (body, &mut self.writer)
+    }
+
+    /// Sets whether the body of a HTTP/1.0 `POST` or `PUT` without `Content-Length`
+    /// or `Transfer-Encoding` is read until the client closes the connection
+    ///
+    /// Off by default, so such requests have no body, RFC 9112 Section 6.3.
+    /// No request can follow a body read this way, see [`Connection::is_at_eof`].
+    /// The client has to only shut down its sending side to be able to read the response
+    pub fn set_read_body_to_eof(&mut self, body_to_eof: bool) {
+        self.body_to_eof = body_to_eof;
+    }
+
+    /// Checks if the body of the last request was read until the client closed the connection,
+    /// see [`Connection::set_read_body_to_eof`]
+    pub fn is_at_eof(&self) -> bool {
+        self.at_eof
+    }
+
+    /// Checks if the streamed body of the last request has not been read to the end
+    pub fn is_reading_body(&self) -> bool {
+        self.chunked.is_some()
+    }
+
+    /// Sends an interim 1xx response, such as `100 Continue` or `103 Early Hints`,
+    /// before the final response
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if status is not 1xx, or the write fails
+    pub async fn send_interim(&mut self, status: StatusCode, headers: &Headers) -> io::Result<()> {
+        if !status.is_informational() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Interim responses must have a 1xx status",
+            ));
+        }
+        let mut head = Vec::new();
+        StatusLine::new(status).encode(&mut head)?;
+        headers.encode(&mut head)?;
+        self.writer.write_all(&head).await?;
+        self.writer.flush().await
+    }
+
+    pub async fn respond(&mut self, response: &Response) -> io::Result<()> {
         response.write_to(&mut self.writer).await?;
         self.writer.flush().await
     }
+
+    /// Sends the response to a HEAD request, without the body, see [`Response::write_head_to`]
+    pub async fn respond_head(&mut self, response: &Response) -> io::Result<()> {
+        response.write_head_to(&mut self.writer).await?;
+        self.writer.flush().await
+    }
+}
+
+/// The streamed body of a request, borrowed from its [`Connection`], see [`Connection::split_body`]
+pub(crate) struct StreamedBody<'a, R> {
+    reader: &'a mut StreamReader<R>,
+    decoder: &'a mut Option<ChunkedDecoder>,
+}
+
+impl<R: AsyncReadExt + Unpin> StreamedBody<'_, R> {
+    /// Reads the next chunk, see [`Connection::read_chunk`]
+    pub(crate) async fn read_chunk(&mut self) -> Result<Option<Bytes>, BodyError> {
+        let Some(decoder) = self.decoder else {
+            return Ok(None);
+        };
+        let mut chunk = Vec::new();
+        match decoder.next_chunk_into(self.reader, &mut chunk).await {
+            Ok(true) => Ok(Some(chunk.into())),
+            result => {
+                *self.decoder = None;
+                result.map(|_| None)
+            }
+        }
+    }
 }
 
 // Reads reponses from the stream and sends requests
@@ -79,29 +449,31 @@ where
     pub async fn read(&mut self) -> Result<Response, ResponseError> {
         let status_line = {
             let line = self.reader.read_line().await?;
-            StatusLine::from_line(&line)
+            StatusLine::from_line(line)
         }?;
 
-        let mut headers = Headers::new();
-        loop {
-            let line = self.reader.read_line().await?;
-            if line.is_empty() {
-                break;
-            }
-
-            headers.parse_one_from_line(&line)?;
-        }
+        let mut headers = self.read_headers::<ResponseError>().await?;
 
-        let body = parse_body(&mut headers, &mut self.reader).await?;
+        let (body, framing) = parse_body(
+            &mut headers,
+            &mut self.reader,
+            self.max_body_size,
+            self.max_header_size,
+        )
+        .await?;
+        let body = self.decode_body(&mut headers, body)?;
 
         Ok(Response {
             status_line,
             headers,
-            body,
+            body: body.into(),
+            upgrade: None,
+            trailers: None,
+            framing,
         })
     }
 
-    pub async fn send(&mut self, request: &mut Request) -> io::Result<()> {
+    pub async fn send(&mut self, request: &Request) -> io::Result<()> {
         request.write_to(&mut self.writer).await?;
         self.writer.flush().await
     }
@@ -117,6 +489,44 @@ mod tests {
 
     use super::*;
 
+    #[tokio::test]
+    async fn test_peek_before_read() -> Result<(), RequestError> {
+        let input = b"GET / HTTP/1.1\r\n\r\n";
+        let mut connection = Connection::<_, _, Request>::new(Cursor::new(input), Vec::new());
+
+        assert_eq!(connection.peek(4).await?, b"GET ");
+        let rq = connection.read().await?;
+        assert_eq!(rq.line.method, Method::Get);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_obs_fold() -> Result<(), RequestError> {
+        let input = b"GET / HTTP/1.1\r\nX-Long: a\r\n b\r\n\tc\r\nHost: localhost\r\n\r\n";
+
+        let mut connection = Connection::<_, _, Request>::new(Cursor::new(input), Vec::new());
+        let res = connection.read().await;
+        assert!(matches!(
+            res,
+            Err(RequestError::Header(HeadersError::ObsoleteLineFolding))
+        ));
+
+        let mut connection = Connection::<_, _, Request>::new(Cursor::new(input), Vec::new());
+        connection.set_lenient_obs_fold(true);
+        let rq = connection.read().await?;
+        assert_eq!(rq.headers.get("X-Long"), Some(&"a b c".to_string()));
+        assert_eq!(rq.headers.get("Host"), Some(&"localhost".to_string()));
+
+        // A fold before any field has nothing to continue
+        let input = b"GET / HTTP/1.1\r\n b\r\n\r\n";
+        let mut connection = Connection::<_, _, Request>::new(Cursor::new(input), Vec::new());
+        connection.set_lenient_obs_fold(true);
+        assert!(connection.read().await.is_err());
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_request_connection() -> Result<(), RequestError> {
         let input = b"GET / HTTP/1.1\r\nHost: localhost:42069\r\nUser-Agent: curl/7.81.0\r\nAccept: */*\r\n\r\n";
@@ -138,6 +548,26 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_request_connection_bare_lf() -> Result<(), RequestError> {
+        let input = b"POST /post HTTP/1.0\nHost: localhost\nContent-Length: 4\n\nbody".to_vec();
+
+        let mut connection =
+            Connection::<_, _, Request>::new(Cursor::new(input.clone()), Cursor::new(Vec::new()));
+        assert!(connection.read().await.is_err());
+
+        let mut connection =
+            Connection::<_, _, Request>::new(Cursor::new(input), Cursor::new(Vec::new()));
+        connection.set_lenient_lf(true);
+        let rq = connection.read().await?;
+        assert_eq!(rq.line.method, Method::Post);
+        assert_eq!(rq.line.url, "/post");
+        assert_eq!(rq.headers.get("Host"), Some(&"localhost".to_string()));
+        assert_eq!(rq.body, &b"body"[..]);
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_request_connection_no_body() -> Result<(), RequestError> {
         let input = b"GET / HTTP/1.1\r\nHost: localhost:42069\r\nUser-Agent: curl/7.81.0\r\nAccept: */*\r\n\r\n".to_vec();
@@ -210,6 +640,243 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_request_connection_options_removed() -> Result<(), RequestError> {
+        let input = b"POST / HTTP/1.1\r\nHost: localhost\r\nConnection: keep-alive, X-Secret, Content-Length, Upgrade\r\n\
+            X-Secret: a\r\nKeep-Alive: timeout=5\r\nUpgrade: websocket\r\nContent-Length: 2\r\n\r\nhi"
+            .to_vec();
+        let mut connection = Connection::<_, _, Request>::new(Cursor::new(input), Vec::new());
+        let rq = connection.read().await?;
+
+        assert!(rq.headers.get("X-Secret").is_none());
+        assert!(rq.headers.get("Keep-Alive").is_none());
+        assert_eq!(rq.headers.get("Upgrade"), Some(&"websocket".to_string()));
+        assert_eq!(rq.headers.get("Host"), Some(&"localhost".to_string()));
+        assert!(rq.headers.field_contains_value("Connection", "keep-alive"));
+        assert_eq!(rq.body, "hi");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_request_body_to_eof() -> Result<(), RequestError> {
+        let input = b"POST /upload HTTP/1.0\r\nHost: localhost\r\n\r\nsensor=42\r\nok".to_vec();
+        let mut connection = Connection::<_, _, Request>::new(Cursor::new(input), Vec::new());
+        connection.set_read_body_to_eof(true);
+        let rq = connection.read().await?;
+        assert_eq!(rq.body, "sensor=42\r\nok");
+        assert_eq!(rq.body_framing(), BodyFraming::UntilClose);
+        assert!(connection.is_at_eof());
+
+        // Framed bodies, HTTP/1.1 and methods without a body are read as usual
+        for input in [
+            &b"POST / HTTP/1.0\r\nContent-Length: 2\r\n\r\nhiGET / HTTP/1.0\r\n\r\n"[..],
+            b"POST / HTTP/1.1\r\n\r\nGET / HTTP/1.0\r\n\r\n",
+            b"GET / HTTP/1.0\r\n\r\nGET / HTTP/1.0\r\n\r\n",
+        ] {
+            let mut connection =
+                Connection::<_, _, Request>::new(Cursor::new(input.to_vec()), Vec::new());
+            connection.set_read_body_to_eof(true);
+            connection.read().await?;
+            assert!(!connection.is_at_eof());
+            assert_eq!(connection.read().await?.line.method, Method::Get);
+        }
+
+        // Off by default
+        let input = b"POST / HTTP/1.0\r\n\r\nbody".to_vec();
+        let mut connection = Connection::<_, _, Request>::new(Cursor::new(input), Vec::new());
+        assert_eq!(connection.read().await?.body, "");
+
+        let input = b"PUT / HTTP/1.0\r\n\r\n0123456789".to_vec();
+        let mut connection = Connection::<_, _, Request>::new(Cursor::new(input), Vec::new());
+        connection.set_read_body_to_eof(true);
+        connection.set_max_body_size(Some(9));
+        assert!(matches!(
+            connection.read().await,
+            Err(RequestError::BodyTooLarge)
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_body_framing() -> Result<(), RequestError> {
+        let input = b"POST / HTTP/1.1\r\nContent-Length: 2\r\n\r\nhi\
+            POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nhi\r\n0\r\n\r\n\
+            GET / HTTP/1.1\r\n\r\n\
+            POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\n";
+        let mut connection = Connection::<_, _, Request>::new(Cursor::new(input), Vec::new());
+        assert_eq!(
+            connection.read().await?.body_framing(),
+            BodyFraming::Length(2)
+        );
+        assert_eq!(
+            connection.read().await?.body_framing(),
+            BodyFraming::Chunked
+        );
+        assert_eq!(connection.read().await?.body_framing(), BodyFraming::None);
+        connection.set_stream_chunked(true);
+        assert_eq!(
+            connection.read().await?.body_framing(),
+            BodyFraming::Chunked
+        );
+
+        let input = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nhi\r\n0\r\n\r\n";
+        let mut connection = Connection::<_, _, Response>::new(Cursor::new(input), Vec::new());
+        let response = connection.read().await.unwrap();
+        assert_eq!(response.body_framing(), BodyFraming::Chunked);
+        // Reading decodes chunked, so the framing is only known from body_framing
+        assert_eq!(response.headers.get("Transfer-Encoding"), None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_request_connection_eof() -> Result<(), RequestError> {
+        let input = b"GET / HTTP/1.1\r\n\r\n".to_vec();
+        let mut connection = Connection::<_, _, Request>::new(Cursor::new(input), Vec::new());
+        connection.read().await?;
+        let rq = connection.read().await;
+        assert!(matches!(rq, Err(RequestError::ConnectionClosed)), "{rq:?}");
+
+        for partial in [&b"GET / HT"[..], b"\r"] {
+            let mut connection =
+                Connection::<_, _, Request>::new(Cursor::new(partial.to_vec()), Vec::new());
+            let rq = connection.read().await;
+            assert!(matches!(rq, Err(RequestError::IO(_))), "{rq:?}");
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_connection_truncated_headers() -> Result<(), RequestError> {
+        for partial in [
+            &b"GET / HTTP/1.1\r\nHost: a\r\n"[..],
+            b"GET / HTTP/1.1\r\nHost: a",
+            b"GET / HTTP/1.1\r\n",
+        ] {
+            let mut connection =
+                Connection::<_, _, Request>::new(Cursor::new(partial.to_vec()), Vec::new());
+            let rq = connection.read().await;
+            assert!(
+                matches!(rq, Err(RequestError::Header(HeadersError::Incomplete))),
+                "{rq:?}"
+            );
+        }
+
+        let input = b"HTTP/1.1 200 Ok\r\nContent-Length: 0\r\n".to_vec();
+        let mut connection = Connection::<_, _, Response>::new(Cursor::new(input), Vec::new());
+        let resp = connection.read().await;
+        assert!(
+            matches!(resp, Err(ResponseError::Header(HeadersError::Incomplete))),
+            "{resp:?}"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_request_connection_stream_chunked() -> Result<(), RequestError> {
+        let input = b"POST /a HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n2\r\nde\r\n0\r\n\r\n\
+POST /b HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nfgh\r\n0\r\n\r\n\
+GET /c HTTP/1.1\r\n\r\n"
+            .to_vec();
+        let batch_reader = BatchReader::new(input, 4);
+        let mut connection = Connection::<_, _, Request>::new(batch_reader, Vec::new());
+        connection.set_stream_chunked(true);
+
+        let rq = connection.read().await?;
+        assert_eq!(rq.get_url(), "/a");
+        assert!(rq.body.is_empty());
+        assert!(connection.is_reading_body());
+        assert_eq!(connection.read_chunk().await?, Some(Bytes::from("abc")));
+        assert_eq!(connection.read_chunk().await?, Some(Bytes::from("de")));
+        assert_eq!(connection.read_chunk().await?, None);
+        assert!(!connection.is_reading_body());
+
+        // The unread body is skipped
+        let rq = connection.read().await?;
+        assert_eq!(rq.get_url(), "/b");
+        let rq = connection.read().await?;
+        assert_eq!(rq.get_url(), "/c");
+        assert!(!connection.is_reading_body());
+        assert_eq!(connection.read_chunk().await?, None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_request_connection_excess_body_is_next_request() -> Result<(), RequestError> {
+        let input =
+            b"POST / HTTP/1.1\r\nContent-Length: 1\r\n\r\nABGET /next HTTP/1.1\r\nContent-Length: 0\r\n\r\n"
+                .to_vec();
+        for batch_size in [1, 3, input.len()] {
+            let batch_reader = BatchReader::new(input.clone(), batch_size);
+            let mut connection = Connection::<_, _, Request>::new(batch_reader, Vec::new());
+
+            let rq = connection.read().await?;
+            assert_eq!(rq.body, vec![b'A']);
+
+            // The extra byte is the start of the next request, and is parsed as part of it
+            let rq = connection.read().await;
+            assert!(
+                matches!(rq, Err(RequestError::RequestLine(_))),
+                "{batch_size}: {rq:?}"
+            );
+        }
+
+        let input =
+            b"POST / HTTP/1.1\r\nContent-Length: 1\r\n\r\nAGET /next HTTP/1.1\r\nContent-Length: 0\r\n\r\n"
+                .to_vec();
+        let batch_reader = BatchReader::new(input, 3);
+        let mut connection = Connection::<_, _, Request>::new(batch_reader, Vec::new());
+        assert_eq!(connection.read().await?.body, vec![b'A']);
+        let rq = connection.read().await?;
+        assert_eq!(rq.line.url, "/next".to_string());
+        assert!(rq.body.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_request_connection_limits() -> Result<(), RequestError> {
+        let input = b"POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 3\r\n\r\nabc".to_vec();
+
+        let mut connection =
+            Connection::<_, _, Request>::new(Cursor::new(input.clone()), Vec::new());
+        // Exactly the size of the field lines
+        connection.set_max_header_size(Some(24));
+        connection.set_max_body_size(Some(3));
+        assert_eq!(connection.read().await?.body, b"abc".to_vec());
+
+        let mut connection =
+            Connection::<_, _, Request>::new(Cursor::new(input.clone()), Vec::new());
+        connection.set_max_header_size(Some(23));
+        assert!(matches!(
+            connection.read().await,
+            Err(RequestError::HeadersTooLarge)
+        ));
+
+        let mut connection = Connection::<_, _, Request>::new(Cursor::new(input), Vec::new());
+        connection.set_max_body_size(Some(2));
+        assert!(matches!(
+            connection.read().await,
+            Err(RequestError::BodyTooLarge)
+        ));
+
+        // The trailer section counts against the same limit as the header section
+        let input = b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n0\r\nX-Trailer: a much longer value\r\n\r\n";
+        let mut connection = Connection::<_, _, Request>::new(Cursor::new(input), Vec::new());
+        connection.set_max_header_size(Some(26));
+        assert!(matches!(
+            connection.read().await,
+            Err(RequestError::HeadersTooLarge)
+        ));
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_request_connection_chunked_encoding() -> Result<(), RequestError> {
         let input =
@@ -226,6 +893,103 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_request_connection_chunked_then_next_request() -> Result<(), RequestError> {
+        let input = b"POST /a HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nAB\r\n0\r\n\r\n\
+            POST /b HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n1\r\nC\r\n0\r\nChecksum: 1\r\n\r\n\
+            GET /c HTTP/1.1\r\n\r\n"
+            .to_vec();
+        let mut connection =
+            Connection::<_, _, Request>::new(BatchReader::new(input, 3), Vec::new());
+
+        let rq = connection.read().await?;
+        assert_eq!(rq.get_url(), "/a");
+        assert_eq!(rq.body, "AB");
+        let rq = connection.read().await?;
+        assert_eq!(rq.get_url(), "/b");
+        assert_eq!(rq.body, "C");
+        let rq = connection.read().await?;
+        assert_eq!(rq.get_url(), "/c");
+        assert!(matches!(
+            connection.read().await,
+            Err(RequestError::ConnectionClosed)
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_request_connection_decode_codings() -> Result<(), RequestError> {
+        use crate::message::{Codec, Gzip};
+
+        let gzipped = Gzip.encode(b"hello").unwrap();
+        let mut input = format!(
+            "POST / HTTP/1.1\r\nTransfer-Encoding: gzip, chunked\r\n\r\n{:x}\r\n",
+            gzipped.len()
+        )
+        .into_bytes();
+        input.extend_from_slice(&gzipped);
+        input.extend_from_slice(b"\r\n0\r\n\r\n");
+        input.extend_from_slice(
+            b"POST / HTTP/1.1\r\nTransfer-Encoding: x-unknown, chunked\r\n\r\n2\r\nAB\r\n0\r\n\r\n\
+            POST / HTTP/1.1\r\nTransfer-Encoding: gzip, chunked\r\n\r\n2\r\nAB\r\n0\r\n\r\n",
+        );
+        let mut connection =
+            Connection::<_, _, Request>::new(BatchReader::new(input, 3), Vec::new());
+        connection.set_codecs(Some(Arc::new(Codecs::default())));
+
+        let rq = connection.read().await?;
+        assert_eq!(rq.body, "hello");
+        assert!(rq.transfer_encoding().is_none());
+        assert_eq!(rq.headers.get("Content-Length"), Some(&"5".to_string()));
+
+        // Unknown codings are left for the handler
+        let rq = connection.read().await?;
+        assert_eq!(rq.body, "AB");
+        assert_eq!(
+            rq.headers.get("Transfer-Encoding"),
+            Some(&"x-unknown".to_string())
+        );
+
+        let rq = connection.read().await;
+        assert!(
+            matches!(rq, Err(RequestError::Body(BodyError::Decode(_)))),
+            "{rq:?}"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_request_connection_gzip_chunked() -> Result<(), RequestError> {
+        use crate::message::TransferCoding;
+
+        let input =
+            b"POST / HTTP/1.1\r\nTransfer-Encoding: gzip, chunked\r\n\r\n2\r\nAB\r\n0\r\n\r\n"
+                .to_vec();
+        let batch_reader = BatchReader::new(input, 3);
+        let mut connection = Connection::<_, _, Request>::new(batch_reader, Vec::new());
+
+        let rq = connection.read().await?;
+        assert_eq!(rq.body, b"AB".to_vec());
+        let te = rq.transfer_encoding().unwrap();
+        assert_eq!(te.codings(), &[TransferCoding::Gzip]);
+
+        let input =
+            b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked, gzip\r\n\r\n2\r\nAB\r\n0\r\n\r\n"
+                .to_vec();
+        let batch_reader = BatchReader::new(input, 3);
+        let mut connection = Connection::<_, _, Request>::new(batch_reader, Vec::new());
+        assert!(matches!(
+            connection.read().await,
+            Err(RequestError::Body(BodyError::Header(
+                HeadersError::InvalidTransferEncoding
+            )))
+        ));
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_request_connection_chunked_encoding_with_crlf_in_body() -> Result<(), RequestError>
     {
@@ -258,6 +1022,53 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_send_interim() -> io::Result<()> {
+        let mut out = Vec::new();
+        let mut connection = Connection::<_, _, Request>::new(Cursor::new(Vec::new()), &mut out);
+
+        let mut headers = Headers::new();
+        headers.set("Link", "</style.css>; rel=preload");
+        connection
+            .send_interim(StatusCode::EarlyHints, &headers)
+            .await?;
+        assert!(
+            connection
+                .send_interim(StatusCode::Ok, &Headers::new())
+                .await
+                .is_err()
+        );
+        connection.respond(&Response::new(StatusCode::Ok)).await?;
+
+        assert_eq!(
+            String::from_utf8_lossy(&out),
+            "HTTP/1.1 103 Early Hints\r\nLink: </style.css>; rel=preload\r\n\r\nHTTP/1.1 200 Ok\r\n\r\n"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_respond_single_write() -> io::Result<()> {
+        use crate::message::test_utils::counting_writer::CountingWriter;
+
+        let mut writer = CountingWriter::default();
+        let mut connection = Connection::<_, _, Request>::new(Cursor::new(b""), &mut writer);
+
+        let mut response = Response::new(StatusCode::Ok);
+        response.headers.add("Content-Type", "text/plain");
+        response.body = Bytes::from_static(b"Hello");
+        connection.respond(&response).await?;
+
+        assert_eq!(writer.writes, 1);
+        assert_eq!(writer.flushes, 1);
+        assert_eq!(
+            String::from_utf8_lossy(&writer.buf),
+            "HTTP/1.1 200 Ok\r\nContent-Type: text/plain\r\nContent-Length: 5\r\n\r\nHello"
+        );
+
+        Ok(())
+    }
+
     //
     //  Response tests
     //
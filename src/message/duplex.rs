@@ -0,0 +1,141 @@
+use std::io;
+
+use tokio::io::AsyncWriteExt;
+
+use crate::message::{Request, Response, StatusCode, UpgradeFuture, Upgraded, write::encode_chunk};
+
+/// Writes the body of a response created with [`Response::duplex`]
+///
+/// Each write is sent right away, as a chunk for HTTP/1.1 clients,
+/// and the body is ended when the callback returns
+#[derive(Debug)]
+pub struct DuplexWriter<'a> {
+    stream: Upgraded<'a>,
+    chunked: bool,
+}
+
+impl DuplexWriter<'_> {
+    /// Sends data as the next part of the body, flushing it so the client gets it right away
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the write fails, usually because the client disconnected
+    pub async fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        // An empty chunk would end the body
+        if data.is_empty() {
+            return Ok(());
+        }
+        if self.chunked {
+            let mut chunk = Vec::with_capacity(data.len() + 16);
+            encode_chunk(&mut chunk, data);
+            self.stream.write_all(&chunk).await?;
+        } else {
+            self.stream.write_all(data).await?;
+        }
+        self.stream.flush().await
+    }
+
+    /// Ends the body with the last chunk, when it is chunked
+    async fn finish(&mut self) -> io::Result<()> {
+        if self.chunked {
+            self.stream.write_all(b"0\r\n\r\n").await?;
+        }
+        self.stream.flush().await
+    }
+}
+
+impl Response {
+    /// Creates a `200 OK` response whose body is written by f while the request body is still arriving,
+    /// for full-duplex exchanges such as RPC streams and proxied uploads
+    ///
+    /// f is called after the head is sent, and the handler reads the request body
+    /// with a [`BodyStream`](crate::message::BodyStream) taken from [`Request::body_stream`] before returning.
+    /// The server keeps reading the body into the stream while f runs,
+    /// which needs [`ServerConfig::stream_chunked_bodies`](crate::server::ServerConfig::stream_chunked_bodies)
+    /// and a chunked request body, other bodies are read before the handler is called.
+    /// The body is chunked for HTTP/1.1 clients and ends with the connection for HTTP/1.0,
+    /// and the connection is closed after it. f is not called for a HEAD request
+    ///
+    /// ```ignore
+    /// fn echo(req: &Request) -> Result<Response, ServerError> {
+    ///     let mut body = req.body_stream().ok_or(ServerError::with_status(StatusCode::BadRequest))?;
+    ///     Ok(Response::duplex(req, move |writer| {
+    ///         Box::pin(async move {
+    ///             while let Some(Ok(chunk)) = body.next_chunk().await {
+    ///                 if writer.write(&chunk).await.is_err() {
+    ///                     break;
+    ///                 }
+    ///             }
+    ///         })
+    ///     }))
+    /// }
+    /// ```
+    pub fn duplex<F>(req: &Request, f: F) -> Response
+    where
+        F: for<'a, 'b> FnOnce(&'a mut DuplexWriter<'b>) -> UpgradeFuture<'a> + Send + 'static,
+    {
+        let chunked = !req.line.version.is_http_1_0();
+        let mut response = Response::new(StatusCode::Ok);
+        response.headers.set("Connection", "close");
+        if chunked {
+            response.headers.set("Transfer-Encoding", "chunked");
+        }
+        response.on_upgrade(move |stream| Box::pin(write_duplex(stream, f, chunked)));
+        response
+    }
+}
+
+async fn write_duplex<F>(stream: Upgraded<'_>, f: F, chunked: bool)
+where
+    F: for<'a, 'b> FnOnce(&'a mut DuplexWriter<'b>) -> UpgradeFuture<'a>,
+{
+    let mut writer = DuplexWriter { stream, chunked };
+    f(&mut writer).await;
+    if let Err(e) = writer.finish().await {
+        eprintln!("Failed to end duplex body: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::{HttpVersion, Method, RequestBuilder};
+    use pretty_assertions::assert_eq;
+
+    async fn run_duplex(mut response: Response) -> String {
+        let mut written = Vec::new();
+        let upgrade = response.take_upgrade().unwrap();
+        upgrade
+            .call(Upgraded::new(tokio::io::empty(), Vec::new(), &mut written))
+            .await;
+        String::from_utf8(written).unwrap()
+    }
+
+    fn write_parts<'a>(writer: &'a mut DuplexWriter<'_>) -> UpgradeFuture<'a> {
+        Box::pin(async move {
+            for part in ["hello", "", " world"] {
+                writer.write(part.as_bytes()).await.unwrap();
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn test_duplex() {
+        let req = RequestBuilder::new(Method::Post, "/").build();
+        let response = Response::duplex(&req, write_parts);
+        assert_eq!(
+            response.headers.get("Transfer-Encoding"),
+            Some(&"chunked".to_string())
+        );
+        assert_eq!(
+            run_duplex(response).await,
+            "5\r\nhello\r\n6\r\n world\r\n0\r\n\r\n"
+        );
+
+        let mut req = RequestBuilder::new(Method::Post, "/").build();
+        req.line.version = HttpVersion::new(1, 0);
+        let response = Response::duplex(&req, write_parts);
+        assert_eq!(response.headers.get("Transfer-Encoding"), None);
+        assert_eq!(run_duplex(response).await, "hello world");
+    }
+}
@@ -10,38 +10,119 @@ pub enum VersionError {
 
 #[derive(Debug, Error)]
 pub enum RequestLineError {
-    #[error("Malformed request line")]
-    MalformedRequestLine,
+    #[error("Malformed request line {line:?} at byte {offset}: {reason}")]
+    MalformedRequestLine {
+        /// The start of the line
+        line: String,
+        /// Where in the line the problem was found
+        offset: usize,
+        reason: &'static str,
+    },
 
     #[error("Invalid method")]
     InvalidMehtod,
 
+    #[error("Invalid request target")]
+    InvalidTarget,
+
+    #[error("Request line longer than the maximum size")]
+    TooLong,
+
     #[error("Invalid http version")]
     InvalidHTTPVersion(#[from] VersionError),
 }
 
 #[derive(Debug, Error)]
 pub enum StatusLineError {
-    #[error("Malformed status line")]
-    MalformedStatusLine,
-
-    #[error("Invalid method")]
+    #[error("Malformed status line {line:?} at byte {offset}: {reason}")]
+    MalformedStatusLine {
+        /// The start of the line
+        line: String,
+        /// Where in the line the problem was found
+        offset: usize,
+        reason: &'static str,
+    },
+
+    #[error("Invalid status code")]
     InvalidStatusCode,
 
+    #[error("Invalid reason phrase")]
+    InvalidReasonPhrase,
+
     #[error("Invalid http version")]
     InvalidHTTPVersion(#[from] VersionError),
 }
 
 #[derive(Debug, Error)]
 pub enum HeadersError {
-    #[error("Malformed header")]
-    MalformedFieldLine,
+    #[error("Malformed field line {line:?} at byte {offset}: {reason}")]
+    MalformedFieldLine {
+        /// The start of the line
+        line: String,
+        /// Where in the line the problem was found
+        offset: usize,
+        reason: &'static str,
+    },
 
     #[error("Contained both Transfer-Encoding and Content-Length")]
     InvalidHeaderFields,
 
     #[error("Invalid Content-Length value")]
     InvalidContentLength,
+
+    #[error("Invalid or unsupported Transfer-Encoding")]
+    InvalidTransferEncoding,
+
+    #[error("Obsolete line folding, a field line starting with whitespace")]
+    ObsoleteLineFolding,
+
+    #[error("Header section larger than the maximum size")]
+    TooLarge,
+
+    #[error("Connection closed before the end of the header section")]
+    Incomplete,
+}
+
+/// Longest part of a line that is kept in a parse error
+const SNIPPET_LEN: usize = 64;
+
+/// Returns the start of line, for showing what a parse error was about
+fn snippet(line: &[u8]) -> String {
+    let mut snippet = String::from_utf8_lossy(&line[..line.len().min(SNIPPET_LEN)]).into_owned();
+    if line.len() > SNIPPET_LEN {
+        snippet.push_str("...");
+    }
+    snippet
+}
+
+impl RequestLineError {
+    pub(crate) fn malformed(line: &[u8], offset: usize, reason: &'static str) -> RequestLineError {
+        RequestLineError::MalformedRequestLine {
+            line: snippet(line),
+            offset,
+            reason,
+        }
+    }
+}
+
+impl StatusLineError {
+    pub(crate) fn malformed(line: &[u8], offset: usize, reason: &'static str) -> StatusLineError {
+        StatusLineError::MalformedStatusLine {
+            line: snippet(line),
+            offset,
+            reason,
+        }
+    }
+}
+
+impl HeadersError {
+    pub(crate) fn malformed(line: &[u8], offset: usize, reason: &'static str) -> HeadersError {
+        HeadersError::MalformedFieldLine {
+            line: snippet(line),
+            offset,
+            reason,
+        }
+    }
 }
 
 #[derive(Debug, Error)]
@@ -58,8 +139,23 @@ pub enum RequestError {
     #[error("Malformed request")]
     MalformedRequest,
 
-    #[error("Body longer than content-length")]
-    BodyTooLong,
+    #[error("Timed out reading request")]
+    Timeout,
+
+    /// The connection was closed cleanly before any byte of a new request,
+    /// the normal end of a keep-alive connection
+    #[error("Connection closed")]
+    ConnectionClosed,
+
+    #[error("Body larger than the maximum size")]
+    BodyTooLarge,
+
+    #[error("Header section larger than the maximum size")]
+    HeadersTooLarge,
+
+    /// The request line was longer than the maximum size because of its target
+    #[error("Request target longer than the maximum request line size")]
+    UriTooLong,
 
     #[error("Malformed chunked size")]
     MalformedChunkedSize,
@@ -71,6 +167,18 @@ pub enum RequestError {
     IO(#[from] Error),
 }
 
+/// An error extracting the query or form of a request into a type,
+/// see [`Request::query_as`](crate::message::Request::query_as)
+#[cfg(feature = "serde")]
+#[derive(Debug, Error)]
+pub enum FormError {
+    #[error("Expected Content-Type application/x-www-form-urlencoded")]
+    ContentType,
+
+    #[error("Malformed form: {0}")]
+    Deserialize(#[from] serde_urlencoded::de::Error),
+}
+
 #[derive(Debug, Error)]
 pub enum ResponseError {
     #[error("Malformed status line: {0}")]
@@ -94,8 +202,8 @@ pub enum BodyError {
     #[error("Malformed header: {0}")]
     Header(#[from] HeadersError),
 
-    #[error("Body longer than content-length")]
-    TooLong,
+    #[error("Body larger than the maximum size")]
+    TooLarge,
 
     #[error("Malformed chunked size")]
     MalformedChunkedSize,
@@ -103,6 +211,10 @@ pub enum BodyError {
     #[error("Malformed chunked body")]
     MalformedChunkedBody,
 
+    /// A transfer coding could not be decoded, or decoded to more than the maximum size
+    #[error("Failed to decode the body: {0}")]
+    Decode(Error),
+
     #[error("IO error: {0}")]
     IO(#[from] Error),
 }
@@ -0,0 +1,126 @@
+use serde::de::DeserializeOwned;
+
+use crate::message::{FormError, Request, Uri};
+
+impl Request {
+    /// Deserializes the query of the target into T
+    ///
+    /// A target without a query is deserialized as an empty query,
+    /// so T can use `Option` or `#[serde(default)]` for parameters that can be left out.
+    /// The error converts to a [`ServerError`](crate::server::ServerError) answered with 400 Bad Request
+    ///
+    /// ```ignore
+    /// #[derive(Deserialize)]
+    /// struct Search {
+    ///     q: String,
+    ///     page: Option<u32>,
+    /// }
+    ///
+    /// fn search(req: &Request) -> Result<Response, ServerError> {
+    ///     let search: Search = req.query_as()?;
+    ///     ...
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns [`FormError::Deserialize`] if the query does not match T
+    pub fn query_as<T: DeserializeOwned>(&self) -> Result<T, FormError> {
+        let uri = self.line.uri();
+        let query = uri.as_ref().and_then(Uri::query).unwrap_or("");
+        Ok(serde_urlencoded::from_str(query)?)
+    }
+
+    /// Deserializes an `application/x-www-form-urlencoded` body into T, as sent by HTML forms
+    ///
+    /// Only the buffered body is read, so a streamed body has to be collected first.
+    /// The error converts to a [`ServerError`](crate::server::ServerError) answered with 400 Bad Request
+    ///
+    /// # Errors
+    ///
+    /// Returns [`FormError::ContentType`] if the `Content-Type` is missing or another type,
+    /// and [`FormError::Deserialize`] if the body does not match T
+    pub fn form_as<T: DeserializeOwned>(&self) -> Result<T, FormError> {
+        let is_form = self
+            .content_type()
+            .is_some_and(|t| t.is("application/x-www-form-urlencoded"));
+        if !is_form {
+            return Err(FormError::ContentType);
+        }
+        Ok(serde_urlencoded::from_bytes(self.get_body())?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        message::{Method, RequestBuilder, StatusCode},
+        server::ServerError,
+    };
+    use pretty_assertions::assert_eq;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Search {
+        q: String,
+        page: Option<u32>,
+    }
+
+    #[test]
+    fn test_query_as() -> Result<(), FormError> {
+        let req = RequestBuilder::new(Method::Get, "/search?q=hello+world%21&page=2").build();
+        let search: Search = req.query_as()?;
+        assert_eq!(
+            search,
+            Search {
+                q: "hello world!".to_string(),
+                page: Some(2)
+            }
+        );
+
+        let req = RequestBuilder::new(Method::Get, "/search?q=rust#results").build();
+        let search: Search = req.query_as()?;
+        assert_eq!(search.page, None);
+
+        let req = RequestBuilder::new(Method::Get, "http://host/search?q=rust&page=3").build();
+        let search: Search = req.query_as()?;
+        assert_eq!(search.page, Some(3));
+
+        let req = RequestBuilder::new(Method::Get, "/search?q=rust&page=two").build();
+        let err = req.query_as::<Search>().unwrap_err();
+        assert_eq!(ServerError::from(err).status_code(), StatusCode::BadRequest);
+
+        let req = RequestBuilder::new(Method::Get, "/search").build();
+        assert!(req.query_as::<Search>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_form_as() -> Result<(), FormError> {
+        let req = RequestBuilder::new(Method::Post, "/search")
+            .header(
+                "Content-Type",
+                "application/x-www-form-urlencoded; charset=utf-8",
+            )
+            .body("q=caf%C3%A9&page=1")
+            .build();
+        let search: Search = req.form_as()?;
+        assert_eq!(
+            search,
+            Search {
+                q: "café".to_string(),
+                page: Some(1)
+            }
+        );
+
+        let req = RequestBuilder::new(Method::Post, "/search")
+            .header("Content-Type", "application/json")
+            .body("{\"q\": \"rust\"}")
+            .build();
+        let err = req.form_as::<Search>().unwrap_err();
+        assert!(matches!(err, FormError::ContentType));
+        assert_eq!(ServerError::from(err).status_code(), StatusCode::BadRequest);
+        Ok(())
+    }
+}
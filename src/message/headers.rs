@@ -1,50 +1,206 @@
 use std::{
     collections::HashMap,
+    fmt,
     io::{self, Write},
 };
 
+use indexmap::IndexMap;
+
 use tokio::io::AsyncWriteExt;
 
 use crate::message::error::HeadersError;
 
-#[derive(Debug)]
-pub struct Headers(HashMap<String, String>);
-
-fn is_valid_token(bytes: &[u8]) -> bool {
-    bytes.iter().all(|&b| {
-        matches!(b, b'A'..=b'Z'
-        | b'a'..=b'z'
-        | b'0'..=b'9'
-        | b'!'
-        | b'#'
-        | b'$'
-        | b'%'
-        | b'&'
-        | b'\''
-        | b'*'
-        | b'+'
-        | b'-'
-        | b'.'
-        | b'^'
-        | b'_'
-        | b'`'
-        | b'|'
-        | b'~')
-    })
+/// How the body of a message being written is delimited
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Framing {
+    Length(usize),
+    Chunked,
 }
 
-fn is_valid_field_value(bytes: &[u8]) -> bool {
-    bytes.iter().all(|&b| match b {
+/// Fields that are never forwarded, even when `Connection` does not name them
+const HOP_BY_HOP: [&str; 8] = [
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// Header fields, stored with lowercase names in the order they were first added
+///
+/// Values are available as `&str`, where bytes that are not UTF-8, such as Latin-1 obs-text,
+/// are replaced. The exact bytes of those values are kept as well,
+/// so they can be read with [`Headers::get_bytes`] and are written back unchanged.
+///
+/// The values of credentials, such as `Authorization` and `Cookie`,
+/// are redacted in the `Debug` output so they don't end up in logs
+#[derive(Clone)]
+pub struct Headers {
+    fields: IndexMap<String, String>,
+    // The received bytes of the values that are not valid UTF-8
+    raw: HashMap<String, Vec<u8>>,
+}
+
+/// Fields whose values are replaced in the `Debug` output of [`Headers`]
+const SENSITIVE: [&str; 4] = [
+    "authorization",
+    "cookie",
+    "proxy-authorization",
+    "set-cookie",
+];
+
+impl fmt::Debug for Headers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(self.iter().map(|(name, value)| {
+                let value = if SENSITIVE.contains(&name) {
+                    "<redacted>"
+                } else {
+                    value
+                };
+                (name, value)
+            }))
+            .finish()
+    }
+}
+
+pub(crate) fn is_tchar(b: u8) -> bool {
+    matches!(b, b'A'..=b'Z'
+    | b'a'..=b'z'
+    | b'0'..=b'9'
+    | b'!'
+    | b'#'
+    | b'$'
+    | b'%'
+    | b'&'
+    | b'\''
+    | b'*'
+    | b'+'
+    | b'-'
+    | b'.'
+    | b'^'
+    | b'_'
+    | b'`'
+    | b'|'
+    | b'~')
+}
+
+pub(crate) fn is_valid_token(bytes: &[u8]) -> bool {
+    bytes.iter().all(|&b| is_tchar(b))
+}
+
+fn is_field_value_byte(b: u8) -> bool {
+    match b {
         0x09 | 0x20 => true, // HTAB or SP
         0x21..=0x7E => true, // VCHAR
         0x80..=0xFF => true, // obs-text
         _ => false,
-    })
+    }
+}
+
+fn is_valid_field_value(bytes: &[u8]) -> bool {
+    bytes.iter().all(|&b| is_field_value_byte(b))
+}
+
+/// Header names where the conventional capitalization is not title case
+const CANONICAL_NAMES: [&str; 11] = [
+    "DNT",
+    "ETag",
+    "TE",
+    "WWW-Authenticate",
+    "X-XSS-Protection",
+    "Content-MD5",
+    "Sec-WebSocket-Accept",
+    "Sec-WebSocket-Extensions",
+    "Sec-WebSocket-Key",
+    "Sec-WebSocket-Protocol",
+    "Sec-WebSocket-Version",
+];
+
+/// Returns the conventional capitalization of a header name,
+/// e.g. `content-type` becomes `Content-Type`
+///
+/// Names are title cased, where every part seperated by '-' starts with an upper case letter,
+/// unless the name has another well known capitalization
+fn canonical_name(name: &str) -> String {
+    if let Some(canonical) = CANONICAL_NAMES
+        .iter()
+        .find(|c| c.eq_ignore_ascii_case(name))
+    {
+        return canonical.to_string();
+    }
+
+    let mut out = String::with_capacity(name.len());
+    let mut upper = true;
+    for c in name.chars() {
+        if upper {
+            out.push(c.to_ascii_uppercase());
+        } else {
+            out.push(c.to_ascii_lowercase());
+        }
+        upper = c == '-';
+    }
+    out
+}
+
+/// Parses `Content-Length`, returning None for any invalid value or a list of different values
+fn parse_content_length(value: &str) -> Option<u64> {
+    let mut values = value.split(',').map(|v| v.trim());
+    let first = values.next()?;
+    if !values.all(|v| v == first) {
+        return None;
+    }
+    if first.is_empty() || !first.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    first.parse().ok()
+}
+
+/// Splits a list-based field value into its elements, RFC 9110 Section 5.6.1
+///
+/// Commas inside quoted strings don't separate elements,
+/// and empty elements such as in `a, , b` are skipped
+fn split_list(value: &str) -> Vec<&str> {
+    let mut elements = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    let mut escaped = false;
+    for (i, b) in value.bytes().enumerate() {
+        match b {
+            _ if escaped => escaped = false,
+            b'\\' if quoted => escaped = true,
+            b'"' => quoted = !quoted,
+            b',' if !quoted => {
+                elements.push(value[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    elements.push(value[start..].trim());
+    elements.retain(|e| !e.is_empty());
+    elements
 }
 
 impl Headers {
     pub fn new() -> Headers {
-        Headers(HashMap::new())
+        Headers::with_capacity(0)
+    }
+
+    /// Creates empty headers with room for capacity fields before reallocating
+    pub fn with_capacity(capacity: usize) -> Headers {
+        Headers {
+            fields: IndexMap::with_capacity(capacity),
+            raw: HashMap::new(),
+        }
+    }
+
+    /// The number of fields that fit without reallocating
+    pub fn capacity(&self) -> usize {
+        self.fields.capacity()
     }
 
     pub fn add<K, V>(&mut self, name: K, value: V) -> Option<String>
@@ -54,14 +210,54 @@ impl Headers {
     {
         let name = name.into().to_lowercase();
         let value = value.into().to_string();
-        if let Some(old) = self.0.get(&name) {
+        if let Some(raw) = self.raw.get(&name) {
+            let mut new = raw.clone();
+            new.push(b',');
+            new.extend_from_slice(value.as_bytes());
+            return self.insert_bytes(name, new);
+        }
+        if let Some(old) = self.fields.get(&name) {
             let new = format!("{},{}", old, value);
-            self.0.insert(name, new)
+            self.fields.insert(name, new)
         } else {
-            self.0.insert(name, value)
+            self.fields.insert(name, value)
+        }
+    }
+
+    /// Same as [`Headers::add`] for a lowercase name, but keeps the exact bytes of values that are not UTF-8
+    pub(crate) fn add_bytes(&mut self, name: &str, value: &[u8]) {
+        let mut new = match self.get_bytes(name) {
+            Some(old) => [old, b","].concat(),
+            None => Vec::new(),
+        };
+        new.extend_from_slice(value);
+        self.insert_bytes(name.to_string(), new);
+    }
+
+    /// Inserts value, keeping its bytes when it is not valid UTF-8
+    fn insert_bytes(&mut self, name: String, value: Vec<u8>) -> Option<String> {
+        match String::from_utf8(value) {
+            Ok(value) => {
+                self.raw.remove(&name);
+                self.fields.insert(name, value)
+            }
+            Err(e) => {
+                let value = e.into_bytes();
+                let old = self
+                    .fields
+                    .insert(name.clone(), String::from_utf8_lossy(&value).into_owned());
+                self.raw.insert(name, value);
+                old
+            }
         }
     }
 
+    /// Removes the field with the lowercase name
+    fn remove_lowercase(&mut self, name: &str) -> Option<String> {
+        self.raw.remove(name);
+        self.fields.shift_remove(name)
+    }
+
     pub fn set<K, V>(&mut self, name: K, value: V)
     where
         K: Into<String>,
@@ -69,7 +265,8 @@ impl Headers {
     {
         let name = name.into().to_lowercase();
         let value = value.into().to_string();
-        self.0.insert(name, value);
+        self.raw.remove(&name);
+        self.fields.insert(name, value);
     }
 
     pub fn remove<K>(&mut self, name: K)
@@ -77,15 +274,99 @@ impl Headers {
         K: Into<String>,
     {
         let name = name.into().to_lowercase();
-        self.0.remove(&name);
+        self.remove_lowercase(&name);
+    }
+
+    /// Removes the hop-by-hop fields, which only apply to a single connection,
+    /// along with the fields the `Connection` header names, RFC 9110 Section 7.6.1
+    ///
+    /// Call it on any message that is forwarded to another connection
+    pub fn remove_hop_by_hop(&mut self) {
+        if let Some(connection) = self.remove_lowercase("connection") {
+            for name in connection.split(',').map(str::trim) {
+                self.remove_lowercase(&name.to_lowercase());
+            }
+        }
+        for name in HOP_BY_HOP {
+            self.remove_lowercase(name);
+        }
+    }
+
+    /// Removes the fields the `Connection` header names, the connection options of RFC 9110 Section 7.6.1,
+    /// so they are not seen as part of the message
+    ///
+    /// `Connection` itself is kept for the close and keep-alive semantics,
+    /// as is `Upgrade`, which the server acts on, and the framing fields,
+    /// as naming them must not change how the body is read
+    pub(crate) fn remove_connection_options(&mut self) {
+        let Some(connection) = self.fields.get("connection") else {
+            return;
+        };
+        let options: Vec<String> = connection
+            .split(',')
+            .map(|name| name.trim().to_lowercase())
+            .filter(|name| {
+                !matches!(
+                    name.as_str(),
+                    "connection" | "upgrade" | "content-length" | "transfer-encoding"
+                )
+            })
+            .collect();
+        for name in options {
+            self.remove_lowercase(&name);
+        }
     }
 
     pub fn get(&self, name: &str) -> Option<&String> {
-        self.0.get(&name.to_lowercase())
+        self.fields.get(&name.to_lowercase())
+    }
+
+    /// Returns the value as it was received, including bytes that are not UTF-8
+    ///
+    /// Values that are valid UTF-8 are the same as [`Headers::get`]
+    pub fn get_bytes(&self, name: &str) -> Option<&[u8]> {
+        let name = name.to_lowercase();
+        match self.raw.get(&name) {
+            Some(raw) => Some(raw),
+            None => self.fields.get(&name).map(String::as_bytes),
+        }
+    }
+
+    /// Returns the parsed `Content-Length`, RFC 9110 Section 8.6
+    ///
+    /// Content-Length = 1*DIGIT, so signs like "+5" are not allowed.
+    /// A list of equal values, such as `5, 5, 5` from repeated fields, is one length,
+    /// any other list is invalid as the length is ambiguous.
+    /// Returns None if the header is missing
+    pub fn content_length(&self) -> Option<Result<u64, HeadersError>> {
+        let value = self.get("Content-Length")?;
+        Some(parse_content_length(value).ok_or(HeadersError::InvalidContentLength))
+    }
+
+    /// Sets `Content-Length`, replacing any earlier value
+    pub fn set_content_length(&mut self, len: u64) {
+        self.set("Content-Length", len.to_string());
+    }
+
+    /// Iterates over the elements of a list-based field, such as `Accept-Encoding: gzip, deflate`
+    ///
+    /// Repeated field lines are combined with commas when they are received,
+    /// so the elements are the same whether they came on one line or several.
+    /// Only use it for fields defined as lists, as other values like dates can contain commas
+    pub fn values(&self, name: &str) -> impl Iterator<Item = &str> {
+        self.get(name)
+            .map(|value| split_list(value))
+            .unwrap_or_default()
+            .into_iter()
+    }
+
+    /// Iterates over the fields, with lowercase names, in the order they were first added
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.fields.iter().map(|(k, v)| (k.as_str(), v.as_str()))
     }
 
     pub fn is_empty(&self) -> bool {
-        self.0.is_empty()
+        self.fields.is_empty()
     }
 
     pub fn field_contains_value<K, V>(&self, name: K, value: V) -> bool
@@ -95,7 +376,7 @@ impl Headers {
     {
         let name = name.into().to_lowercase();
         let value: String = value.into();
-        match self.0.get(&name) {
+        match self.fields.get(&name) {
             Some(v) => {
                 if v == &value {
                     return true;
@@ -122,41 +403,139 @@ impl Headers {
     /// This function will return an error if it does not follow the format above,
     /// or the values in field-name or field-value does not follow RFC 9110 Section 5.5
     pub fn parse_one_from_line(&mut self, line: &[u8]) -> Result<(), HeadersError> {
-        let parts = line.splitn(2, |&b| b == b':').collect::<Vec<&[u8]>>();
+        self.parse_field_line(line).map(|_| ())
+    }
 
-        if parts.len() != 2 {
-            return Err(HeadersError::MalformedFieldLine);
+    /// Same as [`Headers::parse_one_from_line`], but returns the lowercase name of the field
+    pub(crate) fn parse_field_line(&mut self, line: &[u8]) -> Result<String, HeadersError> {
+        let Some(colon) = line.iter().position(|&b| b == b':') else {
+            return Err(HeadersError::malformed(line, line.len(), "missing colon"));
+        };
+        let name_bytes = &line[..colon];
+        if name_bytes.is_empty() {
+            return Err(HeadersError::malformed(line, 0, "empty field name"));
+        }
+        if let Some(i) = name_bytes.iter().position(|&b| !is_tchar(b)) {
+            // RFC 9112 Section 5.1 requires rejecting whitespace between the name and colon
+            let reason = if name_bytes[i..].iter().all(|&b| b == b' ' || b == b'\t') {
+                "whitespace before colon"
+            } else {
+                "invalid character in field name"
+            };
+            return Err(HeadersError::malformed(line, i, reason));
         }
 
-        let name_bytes = parts[0];
-        let value_bytes = parts[1].trim_ascii();
-        if !is_valid_token(name_bytes) || !is_valid_field_value(value_bytes) {
-            return Err(HeadersError::MalformedFieldLine);
+        let value_start = colon + 1;
+        if let Some(i) = line[value_start..]
+            .iter()
+            .position(|&b| !is_field_value_byte(b))
+        {
+            return Err(HeadersError::malformed(
+                line,
+                value_start + i,
+                "invalid character in field value",
+            ));
         }
-        let name = String::from_utf8_lossy(name_bytes).into_owned();
-        let value = String::from_utf8_lossy(value_bytes).into_owned();
+        let value_bytes = line[value_start..].trim_ascii();
+        let name = String::from_utf8_lossy(name_bytes).to_lowercase();
 
-        self.add(&name, &value);
+        self.add_bytes(&name, value_bytes);
 
-        Ok(())
+        Ok(name)
     }
 
-    pub async fn write_to<W: AsyncWriteExt + Unpin>(&self, mut w: W) -> Result<(), io::Error> {
-        if self.0.is_empty() {
-            w.write_all(b"\r\n").await?;
-            return Ok(());
+    /// Unfolds an obs-fold continuation line into the field name, RFC 9112 Section 5.2
+    ///
+    /// The fold is replaced by a single space, so `X-Long: a\r\n b` gives `a b`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the line has bytes not allowed in a field value
+    pub(crate) fn unfold_line(&mut self, name: &str, line: &[u8]) -> Result<(), HeadersError> {
+        if let Some(i) = line.iter().position(|&b| !is_field_value_byte(b)) {
+            return Err(HeadersError::malformed(
+                line,
+                i,
+                "invalid character in field value",
+            ));
         }
-        // TODO: Consider switching to BTreeMap
-        let mut keys: Vec<_> = self.0.keys().collect();
-        keys.sort();
+        let line = line.trim_ascii();
+        let mut value = self.get_bytes(name).unwrap_or_default().to_vec();
+        if !line.is_empty() {
+            if !value.is_empty() {
+                value.push(b' ');
+            }
+            value.extend_from_slice(line);
+        }
+        self.insert_bytes(name.to_string(), value);
+        Ok(())
+    }
 
+    /// Writes the field lines and the empty line ending the header section.
+    /// Field names are written with their conventional capitalization
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a field name is not a valid token,
+    /// or a field value contains invalid bytes such as CR or LF,
+    /// which would otherwise allow injecting header fields
+    pub async fn write_to<W: AsyncWriteExt + Unpin>(&self, mut w: W) -> Result<(), io::Error> {
         let mut buf = Vec::new();
-        for key in keys {
-            let value = &self.0[key];
-            write!(buf, "{}: {}\r\n", key, value)?;
-        }
+        self.encode(&mut buf)?;
         w.write_all(&buf).await?;
-        w.write_all(b"\r\n").await?;
+        Ok(())
+    }
+
+    /// Appends the field lines and the empty line ending the header section to buf
+    ///
+    /// # Errors
+    ///
+    /// Same as [`Headers::write_to`]
+    pub(crate) fn encode(&self, buf: &mut Vec<u8>) -> Result<(), io::Error> {
+        self.encode_with_framing(buf, None)
+    }
+
+    /// Same as [`Headers::encode`], but when framing is Some the stored `Content-Length` and `Transfer-Encoding`
+    /// are replaced by the ones for framing, so messages can be written without being changed
+    pub(crate) fn encode_with_framing(
+        &self,
+        buf: &mut Vec<u8>,
+        framing: Option<Framing>,
+    ) -> Result<(), io::Error> {
+        let framing_field = match framing {
+            Some(Framing::Length(len)) => Some(("content-length", len.to_string())),
+            Some(Framing::Chunked) => Some(("transfer-encoding", "chunked".to_string())),
+            None => None,
+        };
+
+        // Fields are written in the order they were added, with the framing field last
+        let fields = self
+            .iter()
+            .filter(|(key, _)| {
+                framing.is_none() || !matches!(*key, "content-length" | "transfer-encoding")
+            })
+            .map(|(key, value)| {
+                let value = self.raw.get(key).map_or(value.as_bytes(), Vec::as_slice);
+                (key, value)
+            })
+            .chain(
+                framing_field
+                    .as_ref()
+                    .map(|(name, value)| (*name, value.as_bytes())),
+            );
+
+        for (key, value) in fields {
+            if !is_valid_token(key.as_bytes()) || !is_valid_field_value(value) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Invalid header field: {key}"),
+                ));
+            }
+            write!(buf, "{}: ", canonical_name(key))?;
+            buf.extend_from_slice(value);
+            buf.extend_from_slice(b"\r\n");
+        }
+        buf.extend_from_slice(b"\r\n");
         Ok(())
     }
 }
@@ -172,6 +551,51 @@ mod tests {
     use super::*;
     use pretty_assertions::assert_eq;
 
+    #[test]
+    fn test_debug_redacts_credentials() {
+        let mut headers = Headers::new();
+        headers.set("Host", "localhost");
+        headers.set("Authorization", "Bearer secret");
+        headers.set("Cookie", "session=secret");
+        headers.set("Proxy-Authorization", "Basic secret");
+        headers.set("Set-Cookie", "id=secret");
+
+        let debug = format!("{headers:?}");
+        assert!(!debug.contains("secret"), "{debug}");
+        assert_eq!(
+            debug,
+            "{\"host\": \"localhost\", \"authorization\": \"<redacted>\", \"cookie\": \"<redacted>\", \
+            \"proxy-authorization\": \"<redacted>\", \"set-cookie\": \"<redacted>\"}"
+        );
+    }
+
+    #[test]
+    fn test_content_length() -> Result<(), HeadersError> {
+        let mut headers = Headers::new();
+        assert!(headers.content_length().is_none());
+
+        headers.set_content_length(42);
+        assert_eq!(headers.get("Content-Length"), Some(&"42".to_string()));
+        assert_eq!(headers.content_length().transpose()?, Some(42));
+
+        headers.set("Content-Length", "5,5,5");
+        assert_eq!(headers.content_length().transpose()?, Some(5));
+        headers.set("Content-Length", "5, 5");
+        assert_eq!(headers.content_length().transpose()?, Some(5));
+
+        for invalid in ["5,6", "5,,5", "+5", "-5", "", "0x5", "18446744073709551616"] {
+            headers.set("Content-Length", invalid);
+            assert!(
+                matches!(
+                    headers.content_length(),
+                    Some(Err(HeadersError::InvalidContentLength))
+                ),
+                "{invalid}"
+            );
+        }
+        Ok(())
+    }
+
     #[test]
     fn test_header_parse() -> Result<(), HeadersError> {
         let input = b"Host: localhost:42069".to_vec();
@@ -194,19 +618,175 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_header_parse_error_position() {
+        let cases: [(&[u8], usize, &str); 5] = [
+            (b"Host : x", 4, "whitespace before colon"),
+            (b"Host x", 6, "missing colon"),
+            (b": x", 0, "empty field name"),
+            (b"Ho(st: x", 2, "invalid character in field name"),
+            (b"Host: a\x01b", 7, "invalid character in field value"),
+        ];
+        for (input, expected_offset, expected_reason) in cases {
+            let res = Headers::new().parse_one_from_line(input);
+            match res {
+                Err(HeadersError::MalformedFieldLine {
+                    line,
+                    offset,
+                    reason,
+                }) => {
+                    assert_eq!(line, String::from_utf8_lossy(input));
+                    assert_eq!(offset, expected_offset);
+                    assert_eq!(reason, expected_reason);
+                }
+                res => panic!("unexpected result for {input:?}: {res:?}"),
+            }
+        }
+
+        let err = Headers::new().parse_one_from_line(b"Host : x").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Malformed field line \"Host : x\" at byte 4: whitespace before colon"
+        );
+
+        // Long lines are cut short in the error
+        let mut input = b"X-Long".repeat(20);
+        input.push(b' ');
+        let Err(HeadersError::MalformedFieldLine { line, .. }) =
+            Headers::new().parse_one_from_line(&input)
+        else {
+            panic!("expected a malformed field line");
+        };
+        assert_eq!(line.len(), 67);
+        assert!(line.ends_with("..."));
+    }
+
     #[tokio::test]
     async fn test_write_to() -> io::Result<()> {
         let mut buf = Vec::new();
         let mut headers = Headers::new();
         headers.add("a", "b");
         headers.write_to(&mut buf).await?;
-        assert_eq!(buf, b"a: b\r\n\r\n");
+        assert_eq!(buf, b"A: b\r\n\r\n");
 
         buf = Vec::new();
         headers.add("c", "d");
         headers.write_to(&mut buf).await?;
-        assert_eq!(buf, b"a: b\r\nc: d\r\n\r\n");
+        assert_eq!(buf, b"A: b\r\nC: d\r\n\r\n");
+
+        Ok(())
+    }
 
+    #[tokio::test]
+    async fn test_write_to_canonical_names() -> io::Result<()> {
+        let mut buf = Vec::new();
+        let mut headers = Headers::new();
+        headers.add("content-length", "5");
+        headers.add("ETAG", "\"a\"");
+        headers.add("x-custom-HEADER", "b");
+        headers.write_to(&mut buf).await?;
+        assert_eq!(
+            String::from_utf8_lossy(&buf),
+            "Content-Length: 5\r\nETag: \"a\"\r\nX-Custom-Header: b\r\n\r\n"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_latin1_value() -> Result<(), Box<dyn std::error::Error>> {
+        // "café" in Latin-1, where é is the obs-text byte 0xE9
+        let mut headers = Headers::new();
+        headers.parse_one_from_line(b"X-Name: caf\xe9")?;
+        assert_eq!(headers.get("X-Name"), Some(&"caf\u{fffd}".to_string()));
+        assert_eq!(headers.get_bytes("x-name"), Some(&b"caf\xe9"[..]));
+
+        headers.parse_one_from_line(b"X-Name: b")?;
+        assert_eq!(headers.get_bytes("X-Name"), Some(&b"caf\xe9,b"[..]));
+        headers.add("X-Name", "c");
+        assert_eq!(headers.get_bytes("X-Name"), Some(&b"caf\xe9,b,c"[..]));
+        headers.parse_one_from_line(b"Host: localhost")?;
+        assert_eq!(headers.get_bytes("Host"), Some(&b"localhost"[..]));
+
+        let mut buf = Vec::new();
+        headers.write_to(&mut buf).await?;
+        assert_eq!(buf, b"X-Name: caf\xe9,b,c\r\nHost: localhost\r\n\r\n");
+
+        headers.set("X-Name", "plain");
+        assert_eq!(headers.get_bytes("X-Name"), Some(&b"plain"[..]));
+        headers.parse_one_from_line(b"X-Other: \xff")?;
+        headers.remove("X-Other");
+        assert_eq!(headers.get_bytes("X-Other"), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_with_capacity() {
+        let mut headers = Headers::with_capacity(4);
+        assert!(headers.capacity() >= 4);
+        assert!(headers.is_empty());
+        headers.add("Host", "localhost");
+        assert!(headers.capacity() >= 4);
+        assert_eq!(Headers::new().capacity(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_write_to_insertion_order() -> io::Result<()> {
+        let mut headers = Headers::new();
+        headers.set("Zeta", "1");
+        headers.set("Alpha", "2");
+        headers.set("Mid", "3");
+        headers.set("zeta", "4");
+        headers.add("Alpha", "5");
+        let mut buf = Vec::new();
+        headers.write_to(&mut buf).await?;
+        assert_eq!(
+            String::from_utf8_lossy(&buf),
+            "Zeta: 4\r\nAlpha: 2,5\r\nMid: 3\r\n\r\n"
+        );
+
+        headers.remove("Alpha");
+        headers.set("Alpha", "6");
+        let names: Vec<&str> = headers.iter().map(|(name, _)| name).collect();
+        assert_eq!(names, ["zeta", "mid", "alpha"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_write_to_invalid() {
+        let mut headers = Headers::new();
+        headers.add("a", "b\r\nc: d");
+        let res = headers.write_to(Vec::new()).await;
+        assert!(res.is_err());
+
+        let mut headers = Headers::new();
+        headers.add("a b", "c");
+        let res = headers.write_to(Vec::new()).await;
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_values() -> Result<(), HeadersError> {
+        let mut folded = Headers::new();
+        folded.parse_one_from_line(b"Accept-Encoding: gzip, deflate;q=0.5 ,br")?;
+        let mut lines = Headers::new();
+        lines.parse_one_from_line(b"Accept-Encoding: gzip")?;
+        lines.parse_one_from_line(b"accept-encoding: deflate;q=0.5")?;
+        lines.parse_one_from_line(b"Accept-Encoding: br")?;
+        for headers in [folded, lines] {
+            assert_eq!(
+                headers.values("Accept-Encoding").collect::<Vec<_>>(),
+                ["gzip", "deflate;q=0.5", "br"]
+            );
+        }
+
+        let mut headers = Headers::new();
+        headers.set("If-None-Match", r#""a,b", , W/"c\"," "#);
+        assert_eq!(
+            headers.values("If-None-Match").collect::<Vec<_>>(),
+            [r#""a,b""#, r#"W/"c\",""#]
+        );
+        assert_eq!(headers.values("Accept").count(), 0);
         Ok(())
     }
 
@@ -224,6 +804,23 @@ mod tests {
         assert!(!res, "Headers should not contain c");
     }
 
+    #[test]
+    fn test_remove_hop_by_hop() {
+        let mut headers = Headers::new();
+        for name in HOP_BY_HOP {
+            headers.set(name, "1");
+        }
+        headers.set("Connection", "keep-alive, X-Custom");
+        headers.set("X-Custom", "1");
+        headers.set("Host", "localhost");
+        headers.set("Content-Length", "5");
+
+        headers.remove_hop_by_hop();
+        let mut names = headers.iter().map(|(name, _)| name).collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["content-length", "host"]);
+    }
+
     #[tokio::test]
     async fn test_empty_header() -> io::Result<()> {
         let mut buf = Vec::new();
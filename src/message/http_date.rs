@@ -0,0 +1,305 @@
+//! HTTP dates as described in RFC 9110 Section 5.6.7
+//!
+//! IMF-fixdate = day-name "," SP date1 SP time-of-day SP GMT
+//! e.g. `Sun, 06 Nov 1994 08:49:37 GMT`
+//!
+//! Dates are always sent as IMF-fixdate, the obsolete RFC 850 and asctime formats
+//! are only parsed
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const DAY_NAMES: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+const LONG_DAY_NAMES: [&str; 7] = [
+    "Sunday",
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+];
+const MONTH_NAMES: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Converts days since 1970-01-01 into (year, month, day)
+///
+/// Uses the algorithm from http://howardhinnant.github.io/date_algorithms.html
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Converts (year, month, day) into days since 1970-01-01
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let month = month as i64;
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// Formats the time as an IMF-fixdate
+///
+/// Times before the unix epoch are formatted as the epoch
+pub fn format(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+        .as_secs();
+    let days = (secs / 86400) as i64;
+    let secs_of_day = secs % 86400;
+    let (year, month, day) = civil_from_days(days);
+    let weekday = ((days + 4) % 7) as usize;
+
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+        DAY_NAMES[weekday],
+        day,
+        MONTH_NAMES[month as usize - 1],
+        year,
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60
+    )
+}
+
+/// Parses an HTTP date in any of the formats of RFC 9110 Section 5.6.7
+///
+/// Recipients have to accept the obsolete RFC 850 and asctime formats besides IMF-fixdate.
+/// A two digit RFC 850 year more than 50 years in the future is taken to be in the past century.
+/// Returns None if the date is malformed or before the unix epoch
+pub fn parse(value: &str) -> Option<SystemTime> {
+    let value = value.trim();
+    let (year, month, day, time) = match value.split_once(", ") {
+        Some((day_name, rest)) if DAY_NAMES.contains(&day_name) => parse_imf_fixdate(rest)?,
+        Some((day_name, rest)) if LONG_DAY_NAMES.contains(&day_name) => parse_rfc850(rest)?,
+        Some(_) => return None,
+        None => parse_asctime(value)?,
+    };
+
+    if !(1..=31).contains(&day) {
+        return None;
+    }
+    let (hour, minute, second) = parse_time_of_day(time)?;
+    let days = days_from_civil(year, month, day);
+    if days < 0 {
+        return None;
+    }
+    let secs = days as u64 * 86400 + hour * 3600 + minute * 60 + second;
+    Some(UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+/// The (year, month, day, time-of-day) of a date
+type DateParts<'a> = (i64, u32, u32, &'a str);
+
+/// date1 SP time-of-day SP GMT, e.g. `06 Nov 1994 08:49:37 GMT`
+fn parse_imf_fixdate(value: &str) -> Option<DateParts<'_>> {
+    let parts = value.split(' ').collect::<Vec<_>>();
+    let [day, month, year, time, "GMT"] = parts[..] else {
+        return None;
+    };
+    if day.len() != 2 || year.len() != 4 {
+        return None;
+    }
+    Some((
+        parse_number(year)?,
+        parse_month(month)?,
+        parse_number(day)?,
+        time,
+    ))
+}
+
+/// rfc850-date = day-name-l "," SP date2 SP time-of-day SP GMT,
+/// where date2 = day "-" month "-" 2DIGIT, e.g. `06-Nov-94 08:49:37 GMT`
+fn parse_rfc850(value: &str) -> Option<DateParts<'_>> {
+    let parts = value.split(' ').collect::<Vec<_>>();
+    let [date, time, "GMT"] = parts[..] else {
+        return None;
+    };
+    let date = date.split('-').collect::<Vec<_>>();
+    let [day, month, year] = date[..] else {
+        return None;
+    };
+    if day.len() != 2 || year.len() != 2 {
+        return None;
+    }
+    let year = two_digit_year(parse_number(year)?, current_year());
+    Some((year, parse_month(month)?, parse_number(day)?, time))
+}
+
+/// asctime-date = day-name SP date3 SP time-of-day SP year,
+/// where date3 = month SP ( 2DIGIT / ( SP DIGIT ) ), e.g. `Sun Nov  6 08:49:37 1994`
+fn parse_asctime(value: &str) -> Option<DateParts<'_>> {
+    let (day_name, rest) = value.split_once(' ')?;
+    if !DAY_NAMES.contains(&day_name) {
+        return None;
+    }
+    let (month, rest) = rest.split_once(' ')?;
+    let (day, rest) = match rest.strip_prefix(' ') {
+        Some(rest) => rest.split_at_checked(1)?,
+        None => rest.split_at_checked(2)?,
+    };
+    let parts = rest.split(' ').collect::<Vec<_>>();
+    let ["", time, year] = parts[..] else {
+        return None;
+    };
+    if year.len() != 4 {
+        return None;
+    }
+    Some((
+        parse_number(year)?,
+        parse_month(month)?,
+        parse_number(day)?,
+        time,
+    ))
+}
+
+/// time-of-day = hour ":" minute ":" second
+fn parse_time_of_day(value: &str) -> Option<(u64, u64, u64)> {
+    let time = value.split(':').collect::<Vec<_>>();
+    let [hour, minute, second] = time[..] else {
+        return None;
+    };
+    let two_digits = |s: &str, max: u64| -> Option<u64> {
+        if s.len() != 2 {
+            return None;
+        }
+        parse_number(s).filter(|&n| n <= max)
+    };
+    let hour = two_digits(hour, 23)?;
+    let minute = two_digits(minute, 59)?;
+    // Allows leap seconds
+    let second = two_digits(second, 60)?;
+    Some((hour, minute, second))
+}
+
+fn parse_month(value: &str) -> Option<u32> {
+    MONTH_NAMES
+        .iter()
+        .position(|&m| m == value)
+        .map(|m| m as u32 + 1)
+}
+
+/// Parses digits only, as `str::parse` also accepts a sign
+fn parse_number<T: std::str::FromStr>(value: &str) -> Option<T> {
+    if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    value.parse().ok()
+}
+
+/// Picks the most recent year ending in the two digits
+/// that is at most 50 years after the current year, RFC 9110 Section 5.6.7
+fn two_digit_year(year: i64, current_year: i64) -> i64 {
+    let year = current_year - current_year.rem_euclid(100) + year;
+    if year > current_year + 50 {
+        year - 100
+    } else if year + 100 <= current_year + 50 {
+        year + 100
+    } else {
+        year
+    }
+}
+
+fn current_year() -> i64 {
+    let days = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+        .as_secs()
+        / 86400;
+    civil_from_days(days as i64).0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_format() {
+        assert_eq!(format(UNIX_EPOCH), "Thu, 01 Jan 1970 00:00:00 GMT");
+        let time = UNIX_EPOCH + Duration::from_secs(784111777);
+        assert_eq!(format(time), "Sun, 06 Nov 1994 08:49:37 GMT");
+        let time = UNIX_EPOCH + Duration::from_secs(951782400);
+        assert_eq!(format(time), "Tue, 29 Feb 2000 00:00:00 GMT");
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            parse("Sun, 06 Nov 1994 08:49:37 GMT"),
+            Some(UNIX_EPOCH + Duration::from_secs(784111777))
+        );
+        assert_eq!(
+            parse("Tue, 29 Feb 2000 00:00:00 GMT"),
+            Some(UNIX_EPOCH + Duration::from_secs(951782400))
+        );
+
+        assert_eq!(parse("Sun, 06 Nov 1994 08:49:37 UTC"), None);
+        assert_eq!(parse("Sun, 6 Nov 1994 08:49:37 GMT"), None);
+        assert_eq!(parse("Sun, 06 Foo 1994 08:49:37 GMT"), None);
+        assert_eq!(parse("Sun, 06 Nov 1994 24:49:37 GMT"), None);
+        assert_eq!(parse("Sun 06 Nov 1994 08:49:37 GMT"), None);
+        assert_eq!(parse("Sun, +6 Nov 1994 08:49:37 GMT"), None);
+    }
+
+    #[test]
+    fn test_parse_rfc850() {
+        assert_eq!(
+            parse("Sunday, 06-Nov-94 08:49:37 GMT"),
+            Some(UNIX_EPOCH + Duration::from_secs(784111777))
+        );
+
+        assert_eq!(parse("Sun, 06-Nov-94 08:49:37 GMT"), None);
+        assert_eq!(parse("Sunday, 06-Nov-1994 08:49:37 GMT"), None);
+        assert_eq!(parse("Sunday, 06 Nov 94 08:49:37 GMT"), None);
+        assert_eq!(parse("Funday, 06-Nov-94 08:49:37 GMT"), None);
+    }
+
+    #[test]
+    fn test_two_digit_year() {
+        assert_eq!(two_digit_year(94, 2026), 1994);
+        assert_eq!(two_digit_year(26, 2026), 2026);
+        assert_eq!(two_digit_year(76, 2026), 2076);
+        assert_eq!(two_digit_year(77, 2026), 1977);
+        assert_eq!(two_digit_year(10, 2080), 2110);
+    }
+
+    #[test]
+    fn test_parse_asctime() {
+        assert_eq!(
+            parse("Sun Nov  6 08:49:37 1994"),
+            Some(UNIX_EPOCH + Duration::from_secs(784111777))
+        );
+        assert_eq!(
+            parse("Tue Feb 29 00:00:00 2000"),
+            Some(UNIX_EPOCH + Duration::from_secs(951782400))
+        );
+
+        assert_eq!(parse("Sun Nov 6 08:49:37 1994"), None);
+        assert_eq!(
+            parse("Sun Nov 06 08:49:37 1994"),
+            Some(UNIX_EPOCH + Duration::from_secs(784111777))
+        );
+        assert_eq!(parse("Sun Nov  6 08:49:37 94"), None);
+        assert_eq!(parse("Sun Nov  6 08:49:37 1994 GMT"), None);
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let time = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        assert_eq!(parse(&format(time)), Some(time));
+    }
+}
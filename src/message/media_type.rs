@@ -0,0 +1,216 @@
+use std::{collections::HashMap, fmt::Display, path::Path};
+
+/// A media type, like the value of `Content-Type`
+///
+/// Follows RFC 9110 Section 8.3.1
+///
+/// media-type = type "/" subtype parameters
+///
+/// The type, subtype and parameter names are case-insensitive and stored in lowercase.
+/// Comparing with a `&str` only looks at the type and subtype, ignoring parameters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MediaType {
+    main_type: String,
+    subtype: String,
+    params: HashMap<String, String>,
+}
+
+fn is_token(s: &str) -> bool {
+    !s.is_empty()
+        && s.bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+}
+
+impl MediaType {
+    /// Parses a media type
+    ///
+    /// Returns None if the type or subtype is missing or not a valid token
+    pub fn parse(value: &str) -> Option<MediaType> {
+        let mut parts = value.split(';');
+        let (main_type, subtype) = parts.next()?.trim().split_once('/')?;
+        if !is_token(main_type) || !is_token(subtype) {
+            return None;
+        }
+
+        let mut params = HashMap::new();
+        for param in parts {
+            let param = param.trim();
+            if param.is_empty() {
+                continue;
+            }
+            let (name, value) = param.split_once('=')?;
+            let name = name.trim();
+            if !is_token(name) {
+                return None;
+            }
+            let value = value.trim();
+            let value = match value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+                Some(quoted) => quoted.replace("\\\"", "\"").replace("\\\\", "\\"),
+                None => value.to_string(),
+            };
+            params.insert(name.to_lowercase(), value);
+        }
+
+        Some(MediaType {
+            main_type: main_type.to_lowercase(),
+            subtype: subtype.to_lowercase(),
+            params,
+        })
+    }
+
+    /// The top-level type, e.g. `text` in `text/html`
+    pub fn main_type(&self) -> &str {
+        &self.main_type
+    }
+
+    /// The subtype, e.g. `html` in `text/html`
+    pub fn subtype(&self) -> &str {
+        &self.subtype
+    }
+
+    /// The type and subtype without parameters, e.g. `text/html`
+    pub fn essence(&self) -> String {
+        format!("{}/{}", self.main_type, self.subtype)
+    }
+
+    /// Returns the value of a parameter, the name is case-insensitive
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params.get(&name.to_lowercase()).map(|v| v.as_str())
+    }
+
+    pub fn params(&self) -> &HashMap<String, String> {
+        &self.params
+    }
+
+    /// Checks if the type and subtype equals other, ignoring case and parameters
+    pub fn is(&self, other: &str) -> bool {
+        let other = other.split(';').next().unwrap_or(other).trim();
+        match other.split_once('/') {
+            Some((main_type, subtype)) => {
+                self.main_type.eq_ignore_ascii_case(main_type)
+                    && self.subtype.eq_ignore_ascii_case(subtype)
+            }
+            None => false,
+        }
+    }
+}
+
+/// Guesses the `Content-Type` of a file from its extension
+///
+/// Text types are given a utf-8 charset,
+/// and unknown extensions fall back to `application/octet-stream`
+pub(crate) fn from_path(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase)
+        .unwrap_or_default();
+    match extension.as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "txt" => "text/plain; charset=utf-8",
+        "csv" => "text/csv; charset=utf-8",
+        "xml" => "application/xml",
+        "json" => "application/json",
+        "wasm" => "application/wasm",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "ico" => "image/x-icon",
+        "webp" => "image/webp",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "mp4" => "video/mp4",
+        "mp3" => "audio/mpeg",
+        _ => "application/octet-stream",
+    }
+}
+
+impl PartialEq<str> for MediaType {
+    fn eq(&self, other: &str) -> bool {
+        self.is(other)
+    }
+}
+
+impl PartialEq<&str> for MediaType {
+    fn eq(&self, other: &&str) -> bool {
+        self.is(other)
+    }
+}
+
+impl Display for MediaType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.main_type, self.subtype)?;
+        let mut params = self.params.iter().collect::<Vec<_>>();
+        params.sort();
+        for (name, value) in params {
+            if is_token(value) {
+                write!(f, "; {name}={value}")?;
+            } else {
+                let escaped = value.replace('\\', "\\\\").replace('"', "\\\"");
+                write!(f, "; {name}=\"{escaped}\"")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_parse() {
+        let mt = MediaType::parse("text/html").unwrap();
+        assert_eq!(mt.main_type(), "text");
+        assert_eq!(mt.subtype(), "html");
+        assert!(mt.params().is_empty());
+
+        let mt = MediaType::parse("Text/HTML; Charset=utf-8").unwrap();
+        assert_eq!(mt.essence(), "text/html");
+        assert_eq!(mt.param("charset"), Some("utf-8"));
+        assert_eq!(mt.param("CHARSET"), Some("utf-8"));
+
+        let mt = MediaType::parse("multipart/form-data; boundary=\"a b\"").unwrap();
+        assert_eq!(mt.param("boundary"), Some("a b"));
+
+        assert_eq!(MediaType::parse("text"), None);
+        assert_eq!(MediaType::parse("text/"), None);
+        assert_eq!(MediaType::parse("te xt/html"), None);
+        assert_eq!(MediaType::parse("text/html; charset"), None);
+    }
+
+    #[test]
+    fn test_from_path() {
+        assert_eq!(
+            from_path(Path::new("static/index.HTML")),
+            "text/html; charset=utf-8"
+        );
+        assert_eq!(from_path(Path::new("logo.png")), "image/png");
+        assert_eq!(
+            from_path(Path::new("archive.tar.unknown")),
+            "application/octet-stream"
+        );
+        assert_eq!(from_path(Path::new("Makefile")), "application/octet-stream");
+    }
+
+    #[test]
+    fn test_compare() {
+        let mt = MediaType::parse("application/JSON; charset=utf-8").unwrap();
+        assert_eq!(mt, "application/json");
+        assert!(mt.is("Application/Json; charset=latin1"));
+        assert!(!mt.is("text/json"));
+        assert!(!mt.is("application"));
+    }
+
+    #[test]
+    fn test_display() {
+        let mt = MediaType::parse("text/plain;charset=utf-8; name=\"a b\"").unwrap();
+        assert_eq!(mt.to_string(), "text/plain; charset=utf-8; name=\"a b\"");
+    }
+}
@@ -1,6 +1,6 @@
 use crate::message::error::RequestLineError;
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Method {
     Get,
     Head,
@@ -40,6 +40,21 @@ impl Method {
         }
     }
 
+    /// Checks if the method is safe, only retrieving data without changing the server,
+    /// RFC 9110 Section 9.2.1
+    pub fn is_safe(&self) -> bool {
+        matches!(
+            self,
+            Method::Get | Method::Head | Method::Options | Method::Trace
+        )
+    }
+
+    /// Checks if sending the request several times has the same effect as sending it once,
+    /// so it can be retried after a failure, RFC 9110 Section 9.2.2
+    pub fn is_idempotent(&self) -> bool {
+        self.is_safe() || matches!(self, Method::Put | Method::Delete)
+    }
+
     pub fn to_bytes(&self) -> Vec<u8> {
         match self {
             Self::Get => b"Get".to_vec(),
@@ -53,3 +68,27 @@ impl Method {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_safe_and_idempotent() {
+        let cases = [
+            (Method::Get, true, true),
+            (Method::Head, true, true),
+            (Method::Options, true, true),
+            (Method::Trace, true, true),
+            (Method::Put, false, true),
+            (Method::Delete, false, true),
+            (Method::Post, false, false),
+            (Method::Connect, false, false),
+        ];
+        for (method, safe, idempotent) in cases {
+            assert_eq!(method.is_safe(), safe, "{method:?}");
+            assert_eq!(method.is_idempotent(), idempotent, "{method:?}");
+        }
+    }
+}
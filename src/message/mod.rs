@@ -1,27 +1,65 @@
+mod base64;
 mod body;
+mod body_stream;
+mod codec;
+mod conditional;
 mod connection;
+mod duplex;
 mod error;
+#[cfg(feature = "serde")]
+mod form;
 mod headers;
+pub mod http_date;
+mod media_type;
 mod method;
+mod negotiation;
+mod range;
 mod request;
 mod request_builder;
 mod request_line;
 mod response;
 mod response_builder;
+mod sha1;
+mod sse;
 mod status_line;
 mod stream_reader;
+mod transfer_encoding;
+mod upgrade;
+mod uri;
 mod version;
+mod websocket;
+mod write;
 
-mod test_utils;
+pub(crate) mod test_utils;
 
+pub use body::BodyFraming;
+pub use body_stream::BodyStream;
+pub use bytes::Bytes;
+#[cfg(feature = "brotli")]
+pub use codec::Brotli;
+pub use codec::{Codec, Codecs, Deflate, Gzip};
 pub use connection::Connection;
-pub use error::{RequestError, ResponseError};
+pub(crate) use connection::StreamedBody;
+pub use duplex::DuplexWriter;
+#[cfg(feature = "serde")]
+pub use error::FormError;
+pub use error::{
+    BodyError, HeadersError, RequestError, RequestLineError, ResponseError, StatusLineError,
+    VersionError,
+};
 pub use headers::Headers;
+pub use media_type::MediaType;
 pub use method::Method;
+pub use range::ByteRange;
 pub use request::Request;
 pub use request_builder::RequestBuilder;
-pub use request_line::RequestLine;
+pub use request_line::{RequestLine, TargetForm};
 pub use response::Response;
 pub use response_builder::ResponseBuilder;
+pub use sse::{Event, EventSender};
 pub use status_line::{StatusCode, StatusLine};
-pub use stream_reader::StreamReader;
+pub use stream_reader::{DEFAULT_BUFFER_SIZE, StreamReader};
+pub use transfer_encoding::{TransferCoding, TransferEncoding};
+pub use upgrade::{UpgradeFuture, Upgraded};
+pub use uri::{Uri, normalize_path};
+pub use version::HttpVersion;
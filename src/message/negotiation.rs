@@ -0,0 +1,287 @@
+//! Proactive content negotiation with `Accept` and `Accept-Encoding`, as described in RFC 9110 Section 12
+
+use crate::message::{MediaType, Request};
+
+/// A media-range from `Accept`, with its weight in thousandths
+struct MediaRange {
+    media_type: MediaType,
+    quality: u16,
+}
+
+/// Parses qvalue = ( "0" [ "." 0*3DIGIT ] ) / ( "1" [ "." 0*3("0") ] ),
+/// returning the weight in thousandths
+fn parse_quality(value: &str) -> Option<u16> {
+    let (int, frac) = value.split_once('.').unwrap_or((value, ""));
+    if frac.len() > 3 || !frac.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let frac = format!("{frac:0<3}").parse::<u16>().ok()?;
+    match int {
+        "0" => Some(frac),
+        "1" if frac == 0 => Some(1000),
+        _ => None,
+    }
+}
+
+/// Parses the media-ranges of an `Accept` header, skipping any that are invalid
+///
+/// Parameters after `q` are accept extensions, and are not part of the media-range
+fn parse_accept(value: &str) -> Vec<MediaRange> {
+    value
+        .split(',')
+        .filter_map(|range| {
+            let mut media = Vec::new();
+            let mut quality = 1000;
+            for (i, part) in range.split(';').enumerate() {
+                match part.trim().split_once('=') {
+                    Some((name, q)) if i > 0 && name.trim().eq_ignore_ascii_case("q") => {
+                        quality = parse_quality(q.trim())?;
+                        break;
+                    }
+                    _ => media.push(part),
+                }
+            }
+            let media_type = MediaType::parse(&media.join(";"))?;
+            Some(MediaRange {
+                media_type,
+                quality,
+            })
+        })
+        .collect()
+}
+
+/// Parses the codings of an `Accept-Encoding` header into lowercase names and weights,
+/// skipping any with an invalid weight
+fn parse_accept_encoding(value: &str) -> Vec<(String, u16)> {
+    value
+        .split(',')
+        .filter_map(|coding| {
+            let mut parts = coding.split(';');
+            let name = parts.next()?.trim().to_ascii_lowercase();
+            if name.is_empty() {
+                return None;
+            }
+            let mut quality = 1000;
+            for part in parts {
+                if let Some((param, q)) = part.trim().split_once('=')
+                    && param.trim().eq_ignore_ascii_case("q")
+                {
+                    quality = parse_quality(q.trim())?;
+                }
+            }
+            Some((name, quality))
+        })
+        .collect()
+}
+
+/// How specific range is when it matches offer, None if it doesn't match
+///
+/// `*/*` is the least specific, then `type/*`, `type/subtype`,
+/// and `type/subtype` with parameters is the most specific
+fn specificity(range: &MediaType, offer: &MediaType) -> Option<u8> {
+    match (range.main_type(), range.subtype()) {
+        ("*", "*") => Some(0),
+        (main, "*") if main == offer.main_type() => Some(1),
+        (main, sub) if main == offer.main_type() && sub == offer.subtype() => {
+            if range.params().is_empty() {
+                return Some(2);
+            }
+            range
+                .params()
+                .iter()
+                .all(|(name, value)| offer.param(name) == Some(value))
+                .then_some(3)
+        }
+        _ => None,
+    }
+}
+
+impl Request {
+    /// Returns the weight the client gave the media type, from 0 to 1000
+    ///
+    /// The most specific matching media-range decides the weight.
+    /// Without an `Accept` header, or one where no range can be parsed, everything has weight 1000
+    fn accept_quality(&self, media_type: &MediaType) -> u16 {
+        let ranges = match self.headers.get("Accept") {
+            Some(accept) => parse_accept(accept),
+            None => return 1000,
+        };
+        if ranges.is_empty() {
+            return 1000;
+        }
+
+        ranges
+            .iter()
+            .filter_map(|r| Some((specificity(&r.media_type, media_type)?, r.quality)))
+            .max_by_key(|(specificity, _)| *specificity)
+            .map_or(0, |(_, quality)| quality)
+    }
+
+    /// Checks if the client accepts the media type, according to the `Accept` header
+    ///
+    /// A media type given `q=0` is not accepted, neither is one matched by no media-range.
+    /// Returns false if media_type is not a valid media type
+    pub fn accepts(&self, media_type: &str) -> bool {
+        MediaType::parse(media_type).is_some_and(|mt| self.accept_quality(&mt) > 0)
+    }
+
+    /// Returns the offered content coding the client prefers, according to the `Accept-Encoding` header,
+    /// RFC 9110 Section 12.5.3
+    ///
+    /// A coding that is not listed gets the weight of `*`, and is not accepted without it.
+    /// The offer with the highest weight wins, earlier offers win ties.
+    /// Returns None without an `Accept-Encoding` header,
+    /// as a client that does not send it may not be able to decode any coding
+    pub fn preferred_encoding<'a>(
+        &self,
+        offered: impl IntoIterator<Item = &'a str>,
+    ) -> Option<&'a str> {
+        let codings = parse_accept_encoding(self.headers.get("Accept-Encoding")?);
+        let quality_of = |name: &str| {
+            codings
+                .iter()
+                .find(|(coding, _)| coding.eq_ignore_ascii_case(name))
+                .or_else(|| codings.iter().find(|(coding, _)| coding == "*"))
+                .map_or(0, |(_, quality)| *quality)
+        };
+
+        let mut best: Option<(&str, u16)> = None;
+        for offer in offered {
+            let quality = quality_of(offer);
+            if quality > 0 && best.is_none_or(|(_, q)| quality > q) {
+                best = Some((offer, quality));
+            }
+        }
+        best.map(|(offer, _)| offer)
+    }
+
+    /// Returns the offered media type the client prefers, according to the `Accept` header
+    ///
+    /// The offer with the highest weight wins, earlier offers win ties.
+    /// Returns None if the client accepts none of them
+    ///
+    /// ```ignore
+    /// match req.preferred(&["application/json", "text/html"]) {
+    ///     Some("application/json") => json_response(),
+    ///     Some(_) => html_response(),
+    ///     None => Ok(Response::new(StatusCode::NotAcceptable)),
+    /// }
+    /// ```
+    pub fn preferred<'a>(&self, offered: &[&'a str]) -> Option<&'a str> {
+        let mut best: Option<(&str, u16)> = None;
+        for offer in offered {
+            let Some(media_type) = MediaType::parse(offer) else {
+                continue;
+            };
+            let quality = self.accept_quality(&media_type);
+            if quality > 0 && best.is_none_or(|(_, q)| quality > q) {
+                best = Some((offer, quality));
+            }
+        }
+        best.map(|(offer, _)| offer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::{Method, RequestBuilder};
+    use pretty_assertions::assert_eq;
+
+    fn request(accept: &str) -> Request {
+        RequestBuilder::new(Method::Get, "/")
+            .header("Accept", accept)
+            .build()
+    }
+
+    #[test]
+    fn test_parse_quality() {
+        assert_eq!(parse_quality("1"), Some(1000));
+        assert_eq!(parse_quality("1.000"), Some(1000));
+        assert_eq!(parse_quality("0.5"), Some(500));
+        assert_eq!(parse_quality("0.05"), Some(50));
+        assert_eq!(parse_quality("0"), Some(0));
+        assert_eq!(parse_quality("1.5"), None);
+        assert_eq!(parse_quality("0.0001"), None);
+        assert_eq!(parse_quality("-1"), None);
+    }
+
+    #[test]
+    fn test_accepts() {
+        let req = request("text/html, application/*;q=0.5, image/png;q=0");
+        assert!(req.accepts("text/html"));
+        assert!(req.accepts("Text/HTML; charset=utf-8"));
+        assert!(req.accepts("application/json"));
+        assert!(!req.accepts("image/png"));
+        assert!(!req.accepts("text/plain"));
+        assert!(!req.accepts("invalid"));
+
+        let req = request("*/*;q=0.1, text/plain;q=0");
+        assert!(req.accepts("image/png"));
+        assert!(!req.accepts("text/plain"));
+
+        let req = RequestBuilder::new(Method::Get, "/").build();
+        assert!(req.accepts("anything/at-all"));
+    }
+
+    #[test]
+    fn test_preferred() {
+        let offered = ["application/json", "text/html"];
+
+        let req = request("text/html, application/json;q=0.9");
+        assert_eq!(req.preferred(&offered), Some("text/html"));
+
+        let req = request("application/json;q=0.9, text/*;q=0.8");
+        assert_eq!(req.preferred(&offered), Some("application/json"));
+
+        // Equal weights go to the first offer
+        let req = request("*/*");
+        assert_eq!(req.preferred(&offered), Some("application/json"));
+
+        // The more specific range overrides the wildcard
+        let req = request("*/*, application/json;q=0");
+        assert_eq!(req.preferred(&offered), Some("text/html"));
+
+        let req = request("text/html;level=1, text/html;q=0.1, application/json;q=0.5");
+        assert_eq!(req.preferred(&offered), Some("application/json"));
+        assert_eq!(
+            req.preferred(&["text/html;level=1", "application/json"]),
+            Some("text/html;level=1")
+        );
+
+        let req = request("image/png");
+        assert_eq!(req.preferred(&offered), None);
+
+        let req = RequestBuilder::new(Method::Get, "/").build();
+        assert_eq!(req.preferred(&offered), Some("application/json"));
+        assert_eq!(req.preferred(&[]), None);
+    }
+
+    #[test]
+    fn test_preferred_encoding() {
+        let encoding = |value: &str| {
+            RequestBuilder::new(Method::Get, "/")
+                .header("Accept-Encoding", value)
+                .build()
+        };
+        let offered = ["br", "gzip", "deflate"];
+
+        let req = encoding("gzip, deflate, br");
+        assert_eq!(req.preferred_encoding(offered), Some("br"));
+
+        let req = encoding("gzip;q=1.0, br;q=0.5");
+        assert_eq!(req.preferred_encoding(offered), Some("gzip"));
+
+        let req = encoding("*;q=0.1, GZIP;q=0.2");
+        assert_eq!(req.preferred_encoding(offered), Some("gzip"));
+
+        let req = encoding("*, br;q=0");
+        assert_eq!(req.preferred_encoding(offered), Some("gzip"));
+
+        let req = encoding("identity");
+        assert_eq!(req.preferred_encoding(offered), None);
+
+        let req = RequestBuilder::new(Method::Get, "/").build();
+        assert_eq!(req.preferred_encoding(offered), None);
+    }
+}
@@ -0,0 +1,94 @@
+/// A single byte range from the `Range` header
+///
+/// Follows RFC 9110 Section 14.1.2
+///
+/// ranges-specifier = range-unit "=" range-set
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteRange {
+    /// `bytes=start-end`, both inclusive, an end before start is never satisfiable
+    FromTo(u64, u64),
+    /// `bytes=start-`, from start to the end
+    From(u64),
+    /// `bytes=-len`, the last len bytes
+    Suffix(u64),
+}
+
+impl ByteRange {
+    /// Parses the value of a `Range` header
+    ///
+    /// Returns None if the value is malformed, uses another unit than bytes,
+    /// or contains more than one range, in which case the header should be ignored
+    pub fn parse(value: &str) -> Option<ByteRange> {
+        let (unit, range) = value.trim().split_once('=')?;
+        if !unit.eq_ignore_ascii_case("bytes") || range.contains(',') {
+            return None;
+        }
+
+        let (start, end) = range.trim().split_once('-')?;
+        let parse = |s: &str| -> Option<u64> {
+            if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+            s.parse().ok()
+        };
+
+        match (start.is_empty(), end.is_empty()) {
+            (true, true) => None,
+            (true, false) => Some(ByteRange::Suffix(parse(end)?)),
+            (false, true) => Some(ByteRange::From(parse(start)?)),
+            (false, false) => Some(ByteRange::FromTo(parse(start)?, parse(end)?)),
+        }
+    }
+
+    /// Resolves the range against a representation of len bytes
+    ///
+    /// Returns the inclusive first and last byte position,
+    /// or None if the range is not satisfiable, which includes a last position before the first
+    pub fn resolve(&self, len: u64) -> Option<(u64, u64)> {
+        match *self {
+            ByteRange::FromTo(start, end) if start < len && start <= end => {
+                Some((start, end.min(len - 1)))
+            }
+            ByteRange::From(start) if start < len => Some((start, len - 1)),
+            ByteRange::Suffix(n) if n > 0 && len > 0 => Some((len.saturating_sub(n), len - 1)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            ByteRange::parse("bytes=0-499"),
+            Some(ByteRange::FromTo(0, 499))
+        );
+        assert_eq!(ByteRange::parse("bytes=500-"), Some(ByteRange::From(500)));
+        assert_eq!(ByteRange::parse("bytes=-500"), Some(ByteRange::Suffix(500)));
+
+        assert_eq!(ByteRange::parse("bytes=5-1"), Some(ByteRange::FromTo(5, 1)));
+        assert_eq!(ByteRange::parse("bytes=-"), None);
+        assert_eq!(ByteRange::parse("bytes=a-1"), None);
+        assert_eq!(ByteRange::parse("bytes=+1-2"), None);
+        assert_eq!(ByteRange::parse("bytes=0-1,4-5"), None);
+        assert_eq!(ByteRange::parse("items=0-1"), None);
+    }
+
+    #[test]
+    fn test_resolve() {
+        assert_eq!(ByteRange::FromTo(0, 4).resolve(10), Some((0, 4)));
+        assert_eq!(ByteRange::FromTo(5, 100).resolve(10), Some((5, 9)));
+        assert_eq!(ByteRange::FromTo(10, 12).resolve(10), None);
+        assert_eq!(ByteRange::FromTo(4, 2).resolve(10), None);
+        assert_eq!(ByteRange::From(3).resolve(10), Some((3, 9)));
+        assert_eq!(ByteRange::From(10).resolve(10), None);
+        assert_eq!(ByteRange::Suffix(3).resolve(10), Some((7, 9)));
+        assert_eq!(ByteRange::Suffix(30).resolve(10), Some((0, 9)));
+        assert_eq!(ByteRange::Suffix(0).resolve(10), None);
+        assert_eq!(ByteRange::Suffix(3).resolve(0), None);
+    }
+}
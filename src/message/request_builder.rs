@@ -1,9 +1,19 @@
-use crate::message::{Headers, Method, Request, RequestLine, version::HttpVersion};
+use std::{fmt::Display, fs, io, path::Path};
+
+use tokio::io::AsyncRead;
+
+use crate::message::{
+    BodyFraming, Headers, Method, Request, RequestLine,
+    request::{FileBody, ReaderBody},
+    version::HttpVersion,
+};
 
 pub struct RequestBuilder {
     request_line: RequestLine,
     headers: Headers,
     body: Vec<u8>,
+    body_file: Option<FileBody>,
+    body_reader: Option<ReaderBody>,
 }
 
 impl RequestBuilder {
@@ -12,6 +22,8 @@ impl RequestBuilder {
             request_line: RequestLine::from_parts(method, url.into(), HttpVersion::default()),
             headers: Headers::new(),
             body: Vec::new(),
+            body_file: None,
+            body_reader: None,
         }
     }
 
@@ -24,8 +36,80 @@ impl RequestBuilder {
         self
     }
 
+    /// Sets `Authorization` to a bearer token, RFC 6750 Section 2.1
+    pub fn bearer_auth(mut self, token: impl Display) -> Self {
+        self.headers.set("Authorization", format!("Bearer {token}"));
+        self
+    }
+
+    /// Sets `Content-Type`, from a `&str` or a [`MediaType`](crate::message::MediaType)
+    pub fn content_type(mut self, media_type: impl Display) -> Self {
+        self.headers.set("Content-Type", media_type.to_string());
+        self
+    }
+
+    /// Sets `Accept`, the media types the client wants in the response
+    pub fn accept(mut self, media_type: impl Display) -> Self {
+        self.headers.set("Accept", media_type.to_string());
+        self
+    }
+
+    /// Sets `User-Agent`
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.headers.set("User-Agent", user_agent);
+        self
+    }
+
     pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
         self.body = body.into();
+        self.body_file = None;
+        self.body_reader = None;
+        self
+    }
+
+    /// Sends the content of the file at path as the body, without loading it into memory
+    ///
+    /// `Content-Length` is the size of the file now, and the file is streamed
+    /// to the connection when the request is sent, see [`Request::write_to`].
+    /// [`Request::get_body`] of the built request is empty
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if path is not a readable file
+    pub fn body_from_file(mut self, path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let metadata = fs::metadata(path)?;
+        if !metadata.is_file() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Not a file"));
+        }
+        self.body = Vec::new();
+        self.body_file = Some(FileBody {
+            path: path.to_path_buf(),
+            len: metadata.len(),
+        });
+        self.body_reader = None;
+        Ok(self)
+    }
+
+    /// Sends what is read from reader as the body, such as stdin or a pipe,
+    /// for uploads whose length is not known up front
+    ///
+    /// The body is sent with `Transfer-Encoding: chunked`, each read becoming a chunk,
+    /// when the request is sent, see [`Request::write_to`].
+    /// Servers that only accept HTTP/1.0 don't understand chunked bodies.
+    /// The reader is consumed by the first send, so the request can't be sent again,
+    /// and [`Request::get_body`] of the built request is empty
+    ///
+    /// ```ignore
+    /// let req = RequestBuilder::new(Method::Post, "/upload")
+    ///     .header("Host", "localhost")
+    ///     .body_from_reader(tokio::io::stdin())
+    ///     .build();
+    /// ```
+    pub fn body_from_reader(mut self, reader: impl AsyncRead + Send + 'static) -> Self {
+        self.body = Vec::new();
+        self.body_file = None;
+        self.body_reader = Some(ReaderBody::new(reader));
         self
     }
 
@@ -33,7 +117,12 @@ impl RequestBuilder {
         Request {
             line: self.request_line,
             headers: self.headers,
-            body: self.body,
+            body: self.body.into(),
+            interim: None,
+            body_stream: None,
+            body_file: self.body_file,
+            body_reader: self.body_reader,
+            framing: BodyFraming::None,
         }
     }
 }
@@ -55,4 +144,25 @@ mod test {
         assert_eq!(request.line.version, (1, 1));
         assert_eq!(request.headers.get("AA"), Some(&"BB".to_string()));
     }
+
+    #[test]
+    fn test_typed_headers() {
+        use crate::message::MediaType;
+
+        let request = RequestBuilder::new(Method::Post, "/")
+            .bearer_auth("abc.def")
+            .content_type(MediaType::parse("application/json; charset=utf-8").unwrap())
+            .accept("text/html")
+            .user_agent("rust-http")
+            .build();
+
+        let header = |name| request.headers.get(name).map(String::as_str);
+        assert_eq!(header("Authorization"), Some("Bearer abc.def"));
+        assert_eq!(
+            header("Content-Type"),
+            Some("application/json; charset=utf-8")
+        );
+        assert_eq!(header("Accept"), Some("text/html"));
+        assert_eq!(header("User-Agent"), Some("rust-http"));
+    }
 }
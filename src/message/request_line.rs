@@ -4,13 +4,58 @@ use std::{
 };
 use tokio::io::AsyncWriteExt;
 
-use crate::message::{Method, error::RequestLineError, version::HttpVersion};
+use crate::message::{Method, Uri, error::RequestLineError, uri::scheme_len, version::HttpVersion};
 
-#[derive(Debug, PartialEq, Eq)]
+/// The form of a request-target, RFC 9112 Section 3.2
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetForm {
+    /// `/path?query`, used for most requests to origin servers
+    Origin,
+    /// `http://host/path?query`, used for requests to proxies
+    Absolute,
+    /// `host:port`, only used with `CONNECT`
+    Authority,
+    /// `*`, only used with server-wide `OPTIONS`
+    Asterisk,
+}
+
+impl TargetForm {
+    /// Finds the form of target, or None if it has no valid form for the method
+    fn detect(method: Method, target: &str) -> Option<TargetForm> {
+        if method == Method::Connect {
+            return is_authority(target).then_some(TargetForm::Authority);
+        }
+        if target == "*" {
+            return (method == Method::Options).then_some(TargetForm::Asterisk);
+        }
+        if target.starts_with('/') {
+            return Some(TargetForm::Origin);
+        }
+        scheme_len(target).map(|_| TargetForm::Absolute)
+    }
+}
+
+/// authority-form = uri-host ":" port
+fn is_authority(target: &str) -> bool {
+    match target.rsplit_once(':') {
+        Some((host, port)) => {
+            !host.is_empty()
+                && !host.contains(['/', '?', '#', '@'])
+                && !port.is_empty()
+                && port.bytes().all(|b| b.is_ascii_digit())
+        }
+        None => false,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct RequestLine {
     pub method: Method,
     pub url: String,
     pub version: HttpVersion,
+    /// The target as received, only kept when it is not valid UTF-8,
+    /// as url is then a lossy conversion of it
+    raw_target: Option<Vec<u8>>,
 }
 
 impl RequestLine {
@@ -21,32 +66,90 @@ impl RequestLine {
     ///
     /// # Errors
     ///
-    /// Returns Error if write fails
+    /// Returns Error if write fails, or the target is not valid
     pub async fn write_to<W: AsyncWriteExt + Unpin>(&self, mut w: W) -> io::Result<()> {
         let mut buf = Vec::new();
+        self.encode(&mut buf)?;
+        w.write_all(&buf).await?;
+        Ok(())
+    }
 
+    /// Appends the request line, including CRLF, to buf
+    ///
+    /// A target with spaces or control characters, which would change the request line
+    /// or inject another request, fails with [`io::ErrorKind::InvalidInput`]
+    pub(crate) fn encode(&self, buf: &mut Vec<u8>) -> io::Result<()> {
+        if self.url.bytes().any(|b| b == b' ' || b.is_ascii_control()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Invalid request target: {:?}", self.url),
+            ));
+        }
         write!(
             buf,
             "{} {} HTTP/{}\r\n",
             self.method.to_str(),
             self.url,
             self.version,
-        )?;
-
-        w.write_all(&buf).await?;
-        Ok(())
+        )
     }
+
     pub fn from_line(line: &[u8]) -> Result<RequestLine, RequestLineError> {
+        // The parts are separated by exactly one SP, RFC 9112 Section 3.
+        // Parsers that also split on tabs or trim spaces see a different request, which can be used to smuggle one
+        if let Some(offset) = line.iter().position(u8::is_ascii_control) {
+            return Err(RequestLineError::malformed(
+                line,
+                offset,
+                "control character such as a tab",
+            ));
+        }
+        if line.first() == Some(&b' ') {
+            return Err(RequestLineError::malformed(line, 0, "leading space"));
+        }
+        if line.last() == Some(&b' ') {
+            return Err(RequestLineError::malformed(
+                line,
+                line.len() - 1,
+                "trailing space",
+            ));
+        }
+
         let parts = line.split(|&b| b == b' ').collect::<Vec<&[u8]>>();
         if parts.len() != 3 {
-            return Err(RequestLineError::MalformedRequestLine);
+            // Points at the end of the line when parts are missing, or at the space starting a fourth part
+            let offset = line
+                .iter()
+                .enumerate()
+                .filter(|&(_, &b)| b == b' ')
+                .nth(2)
+                .map_or(line.len(), |(i, _)| i);
+            return Err(RequestLineError::malformed(
+                line,
+                offset,
+                "expected method, target and version separated by single spaces",
+            ));
         }
 
         let method = Method::parse(parts[0])?;
-        let url = String::from_utf8_lossy(parts[1]).into_owned();
+        let (url, raw_target) = match std::str::from_utf8(parts[1]) {
+            Ok(url) => (url.to_string(), None),
+            Err(_) => (
+                String::from_utf8_lossy(parts[1]).into_owned(),
+                Some(parts[1].to_vec()),
+            ),
+        };
+        if TargetForm::detect(method, &url).is_none() {
+            return Err(RequestLineError::InvalidTarget);
+        }
         let version_parts = parts[2].split(|&b| b == b'/').collect::<Vec<&[u8]>>();
         if version_parts.len() != 2 || version_parts[0] != b"HTTP" {
-            return Err(RequestLineError::MalformedRequestLine);
+            let offset = parts[0].len() + parts[1].len() + 2;
+            return Err(RequestLineError::malformed(
+                line,
+                offset,
+                "expected version starting with HTTP/",
+            ));
         }
 
         let version = HttpVersion::from_bytes(version_parts[1])?;
@@ -55,23 +158,114 @@ impl RequestLine {
             method,
             url,
             version,
+            raw_target,
         })
     }
 
+    /// Returns the request-target exactly as received, before any conversion to UTF-8
+    ///
+    /// Use it when the original bytes matter, e.g. to verify a signed URL.
+    /// For lines not parsed with [`RequestLine::from_line`] this is the bytes of url
+    pub fn raw_target(&self) -> &[u8] {
+        self.raw_target.as_deref().unwrap_or(self.url.as_bytes())
+    }
+
+    /// Returns the form of the request-target
+    ///
+    /// Targets that match no form, which can only come from [`RequestLine::from_parts`],
+    /// are treated as origin-form
+    pub fn target_form(&self) -> TargetForm {
+        TargetForm::detect(self.method, &self.url).unwrap_or(TargetForm::Origin)
+    }
+
+    /// Returns the path of the target, without the query
+    ///
+    /// For absolute-form the authority is removed, so `http://host/a?b` gives `/a`.
+    /// Asterisk-form gives `*` and authority-form an empty path
+    pub fn path(&self) -> &str {
+        let path = match self.target_form() {
+            TargetForm::Origin | TargetForm::Asterisk => &self.url,
+            TargetForm::Authority => return "",
+            TargetForm::Absolute => {
+                let rest = absolute_rest(&self.url);
+                match rest.find(['/', '?', '#']) {
+                    Some(i) if rest[i..].starts_with('/') => &rest[i..],
+                    _ => return "/",
+                }
+            }
+        };
+        let end = path.find(['?', '#']).unwrap_or(path.len());
+        &path[..end]
+    }
+
+    /// Returns the target in origin-form, the path and query without the scheme and authority
+    ///
+    /// Used when forwarding a request received in absolute-form to an origin server.
+    /// The other forms are returned as they are
+    pub fn origin_target(&self) -> String {
+        if self.target_form() != TargetForm::Absolute {
+            return self.url.clone();
+        }
+        let rest = absolute_rest(&self.url);
+        let start = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+        let end = rest.find('#').unwrap_or(rest.len()).max(start);
+        match &rest[start..end] {
+            "" => "/".to_string(),
+            target if target.starts_with('?') => format!("/{target}"),
+            target => target.to_string(),
+        }
+    }
+
+    /// Returns the host and port of the target, for absolute-form and authority-form
+    pub fn authority(&self) -> Option<&str> {
+        match self.target_form() {
+            TargetForm::Authority => Some(&self.url),
+            TargetForm::Absolute => {
+                let rest = absolute_rest(&self.url);
+                let end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+                let authority = &rest[..end];
+                // Drop userinfo, it is not a part of the host
+                let authority = authority.rsplit_once('@').map_or(authority, |(_, a)| a);
+                (!authority.is_empty()).then_some(authority)
+            }
+            TargetForm::Origin | TargetForm::Asterisk => None,
+        }
+    }
+
+    /// Parses the target into its components, for origin-form and absolute-form
+    ///
+    /// Returns None for authority-form and asterisk-form,
+    /// or if the target is not a valid URI
+    pub fn uri(&self) -> Option<Uri> {
+        match self.target_form() {
+            TargetForm::Origin | TargetForm::Absolute => Uri::parse(&self.url),
+            TargetForm::Authority | TargetForm::Asterisk => None,
+        }
+    }
+
     pub fn from_parts(method: Method, url: String, version: HttpVersion) -> RequestLine {
         RequestLine {
             method,
             url,
             version,
+            raw_target: None,
         }
     }
 }
+
+/// Returns what comes after `scheme://` of an absolute-URI, or after `scheme:` when there is no authority
+fn absolute_rest(url: &str) -> &str {
+    let rest = &url[scheme_len(url).map_or(0, |len| len + 1)..];
+    rest.strip_prefix("//").unwrap_or(rest)
+}
+
 impl Default for RequestLine {
     fn default() -> RequestLine {
         RequestLine {
             method: Method::Get,
             url: "".to_string(),
             version: HttpVersion::default(),
+            raw_target: None,
         }
     }
 }
@@ -109,6 +303,12 @@ mod tests {
         assert_eq!(rl.url, "/test".to_string());
         assert_eq!(rl.version, (1, 1));
 
+        let input = b"OPTIONS * HTTP/1.1";
+        let rl = RequestLine::from_line(input)?;
+
+        assert_eq!(rl.method, Method::Options);
+        assert_eq!(rl.url, "*".to_string());
+
         let input = b"POST  /test HTTP/1.1";
         let rl = RequestLine::from_line(input);
 
@@ -121,4 +321,141 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_request_line_parse_error_position() {
+        let cases: [(&[u8], usize); 9] = [
+            (b"POST  /test HTTP/1.1", 11),
+            (b"GET /a b HTTP/1.1", 8),
+            (b"GET /test", 9),
+            (b"POST /test HTP/1.1", 11),
+            (b"GET\t/\tHTTP/1.1", 3),
+            (b"GET /a\tb HTTP/1.1", 6),
+            (b"GET / HTTP/1.1\x0b", 14),
+            (b" GET / HTTP/1.1", 0),
+            (b"GET / HTTP/1.1 ", 14),
+        ];
+        for (input, expected_offset) in cases {
+            match RequestLine::from_line(input) {
+                Err(RequestLineError::MalformedRequestLine { line, offset, .. }) => {
+                    assert_eq!(line, String::from_utf8_lossy(input));
+                    assert_eq!(offset, expected_offset, "{line}");
+                }
+                res => panic!("unexpected result for {input:?}: {res:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_request_line_whitespace_reason() {
+        for (input, expected) in [
+            (&b"GET\t/\tHTTP/1.1"[..], "control character such as a tab"),
+            (b" GET / HTTP/1.1", "leading space"),
+            (b"GET / HTTP/1.1 ", "trailing space"),
+        ] {
+            match RequestLine::from_line(input) {
+                Err(RequestLineError::MalformedRequestLine { reason, .. }) => {
+                    assert_eq!(reason, expected)
+                }
+                res => panic!("unexpected result for {input:?}: {res:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_request_line_target_form() -> Result<(), RequestLineError> {
+        let rl = RequestLine::from_line(b"GET /a/b?c=d HTTP/1.1")?;
+        assert_eq!(rl.target_form(), TargetForm::Origin);
+        assert_eq!(rl.path(), "/a/b");
+        assert_eq!(rl.authority(), None);
+
+        let rl = RequestLine::from_line(b"GET http://example.com:8080/a?b HTTP/1.1")?;
+        assert_eq!(rl.target_form(), TargetForm::Absolute);
+        assert_eq!(rl.url, "http://example.com:8080/a?b");
+        assert_eq!(rl.path(), "/a");
+        assert_eq!(rl.authority(), Some("example.com:8080"));
+
+        let rl = RequestLine::from_line(b"GET http://user@example.com?q HTTP/1.1")?;
+        assert_eq!(rl.path(), "/");
+        assert_eq!(rl.authority(), Some("example.com"));
+
+        let rl = RequestLine::from_line(b"CONNECT example.com:443 HTTP/1.1")?;
+        assert_eq!(rl.target_form(), TargetForm::Authority);
+        assert_eq!(rl.path(), "");
+        assert_eq!(rl.authority(), Some("example.com:443"));
+
+        let rl = RequestLine::from_line(b"OPTIONS * HTTP/1.1")?;
+        assert_eq!(rl.target_form(), TargetForm::Asterisk);
+        assert_eq!(rl.path(), "*");
+
+        for input in [
+            &b"GET * HTTP/1.1"[..],
+            b"CONNECT /path HTTP/1.1",
+            b"CONNECT example.com HTTP/1.1",
+            b"GET path HTTP/1.1",
+            b"GET 1http://host/ HTTP/1.1",
+        ] {
+            assert!(
+                matches!(
+                    RequestLine::from_line(input),
+                    Err(RequestLineError::InvalidTarget)
+                ),
+                "{}",
+                String::from_utf8_lossy(input)
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_origin_target() -> Result<(), RequestLineError> {
+        for (input, target) in [
+            (&b"GET /a?b HTTP/1.1"[..], "/a?b"),
+            (b"GET http://host/a/b?c=d HTTP/1.1", "/a/b?c=d"),
+            (b"GET http://user@host:8080 HTTP/1.1", "/"),
+            (b"GET http://host?q HTTP/1.1", "/?q"),
+            (b"GET http://host/a#frag HTTP/1.1", "/a"),
+            (b"CONNECT host:443 HTTP/1.1", "host:443"),
+        ] {
+            let rl = RequestLine::from_line(input)?;
+            assert_eq!(rl.origin_target(), target);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_uri() -> Result<(), RequestLineError> {
+        let rl = RequestLine::from_line(b"GET http://host:8080/a?b HTTP/1.1")?;
+        let uri = rl.uri().unwrap();
+        assert_eq!(uri.host(), Some("host"));
+        assert_eq!(uri.port(), Some(8080));
+        assert_eq!(uri.path(), "/a");
+        assert_eq!(uri.query(), Some("b"));
+
+        let rl = RequestLine::from_line(b"GET /a?b HTTP/1.1")?;
+        assert_eq!(rl.uri().unwrap().path(), "/a");
+
+        let rl = RequestLine::from_line(b"CONNECT host:443 HTTP/1.1")?;
+        assert_eq!(rl.uri(), None);
+        let rl = RequestLine::from_line(b"OPTIONS * HTTP/1.1")?;
+        assert_eq!(rl.uri(), None);
+        let rl = RequestLine::from_line(b"GET /a|b HTTP/1.1")?;
+        assert_eq!(rl.uri(), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_raw_target() -> Result<(), RequestLineError> {
+        let rl = RequestLine::from_line(b"GET /a%20b?sig=abc HTTP/1.1")?;
+        assert_eq!(rl.raw_target(), b"/a%20b?sig=abc");
+
+        let rl = RequestLine::from_line(b"GET /caf\xe9 HTTP/1.1")?;
+        assert_eq!(rl.url, "/caf\u{FFFD}");
+        assert_eq!(rl.raw_target(), b"/caf\xe9");
+
+        Ok(())
+    }
 }
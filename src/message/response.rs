@@ -1,17 +1,26 @@
 use std::{
-    fs,
-    io::{self},
+    fs::{self, File, Metadata},
+    io::{self, Read, Seek, SeekFrom},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
 };
 
 use tokio::io::AsyncWriteExt;
 
-use crate::message::{Headers, StatusCode, StatusLine};
+use crate::message::{
+    BodyFraming, Bytes, Codecs, Headers, Request, StatusCode, StatusLine, UpgradeFuture, Upgraded,
+    headers::Framing, http_date, media_type, upgrade::OnUpgrade, write::write_message,
+};
 
 #[derive(Debug)]
 pub struct Response {
     pub status_line: StatusLine,
     pub headers: Headers,
-    pub body: Vec<u8>,
+    /// The body, reference counted so cloning or forwarding the response doesn't copy it
+    pub body: Bytes,
+    pub(crate) upgrade: Option<OnUpgrade>,
+    pub(crate) trailers: Option<Headers>,
+    pub(crate) framing: BodyFraming,
 }
 
 impl Response {
@@ -19,73 +28,677 @@ impl Response {
         Response {
             status_line: StatusLine::new(status_code),
             headers: Headers::new(),
-            body: Vec::new(),
+            body: Bytes::new(),
+            upgrade: None,
+            trailers: None,
+            framing: BodyFraming::None,
         }
     }
 
     /// Writes request into a writer.
-    /// Is not a streamed response, so will update 'Content-Length' header to be correct
+    /// Is not a streamed response, so 'Content-Length' is written from the length of the body
     ///
     /// # Errors
     ///
     /// Returns an error if any element fails to write
-    pub async fn write_to<W: AsyncWriteExt + Unpin>(&mut self, mut w: W) -> io::Result<()> {
-        self.status_line.write_to(&mut w).await?;
-        if !self.body.is_empty() {
-            self.headers
-                .set("Content-Length", self.body.len().to_string());
+    pub async fn write_to<W: AsyncWriteExt + Unpin>(&self, w: W) -> io::Result<()> {
+        self.write_message_to(w, true).await
+    }
+
+    /// Writes the response to a HEAD request, which is the head without the body
+    ///
+    /// `Content-Length` is still the length of the body,
+    /// so it is the same as in the response to a GET, RFC 9110 Section 9.3.2
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any element fails to write
+    pub async fn write_head_to<W: AsyncWriteExt + Unpin>(&self, w: W) -> io::Result<()> {
+        self.write_message_to(w, false).await
+    }
+
+    async fn write_message_to<W: AsyncWriteExt + Unpin>(
+        &self,
+        mut w: W,
+        with_body: bool,
+    ) -> io::Result<()> {
+        let (head, tail) = self.encode_around_body(with_body)?;
+        if !with_body {
+            return w.write_all(&head).await;
+        }
+        write_message(&mut w, head, &self.body).await?;
+        if !tail.is_empty() {
+            w.write_all(&tail).await?;
         }
-        self.headers.write_to(&mut w).await?;
+        Ok(())
+    }
+
+    /// Returns the response as it is sent, see [`Response::write_to`]
+    ///
+    /// ```ignore
+    /// let response = Response::new(StatusCode::NoContent);
+    /// assert_eq!(response.to_bytes()?, b"HTTP/1.1 204 No Content\r\n\r\n");
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::ErrorKind::InvalidInput`] error if a header or trailer field is not valid
+    pub fn to_bytes(&self) -> io::Result<Vec<u8>> {
+        let (mut bytes, tail) = self.encode_around_body(true)?;
+        bytes.extend_from_slice(&self.body);
+        bytes.extend_from_slice(&tail);
+        Ok(bytes)
+    }
+
+    /// Serializes what is sent before and after the body,
+    /// shared by [`Response::write_to`] and [`Response::to_bytes`] so they send the same bytes
+    ///
+    /// The tail is the end of the chunked encoding and the trailer section, when there are trailers
+    fn encode_around_body(&self, with_body: bool) -> io::Result<(Vec<u8>, Vec<u8>)> {
+        let framing = if self.trailers.is_some() {
+            Some(Framing::Chunked)
+        } else {
+            (!self.body.is_empty()).then_some(Framing::Length(self.body.len()))
+        };
+
+        // Serializes the head into one buffer, so it is sent with as few writes as possible
+        let mut head = Vec::with_capacity(256);
+        self.status_line.encode(&mut head)?;
+        self.headers.encode_with_framing(&mut head, framing)?;
+        let mut tail = Vec::new();
+        let Some(trailers) = self.trailers.as_ref().filter(|_| with_body) else {
+            return Ok((head, tail));
+        };
+
+        // The body is sent as a single chunk, followed by the last chunk and the trailer section
         if !self.body.is_empty() {
-            w.write_all(&self.body).await?;
+            head.extend_from_slice(format!("{:x}\r\n", self.body.len()).as_bytes());
+            tail.extend_from_slice(b"\r\n");
         }
+        tail.extend_from_slice(b"0\r\n");
+        trailers.encode(&mut tail)?;
+        Ok((head, tail))
+    }
 
-        Ok(())
+    /// Adds a trailer field, sent after the body
+    ///
+    /// A response with trailers is sent with chunked encoding instead of `Content-Length`,
+    /// and the name is announced in the `Trailer` header, RFC 9110 Section 6.6.2.
+    /// Trailers are dropped for HTTP/1.0 clients, which don't support chunked encoding.
+    /// Clients may ignore trailers, so they should only carry optional metadata
+    ///
+    /// ```ignore
+    /// let mut response = Response::new(StatusCode::Ok);
+    /// response.body = data;
+    /// response.set_trailer("Digest", checksum(&response.body));
+    /// ```
+    pub fn set_trailer<K, V>(&mut self, name: K, value: V)
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let name = name.into();
+        announce_trailer(&mut self.headers, &name);
+        self.trailers
+            .get_or_insert_with(Headers::new)
+            .set(name, value);
     }
 
+    /// Removes the trailer fields, and the `Trailer` header announcing them
+    pub(crate) fn remove_trailers(&mut self) {
+        if self.trailers.take().is_some() {
+            self.headers.remove("Trailer");
+        }
+    }
+
+    /// Returns the trailer fields, if any was set
+    pub fn trailers(&self) -> Option<&Headers> {
+        self.trailers.as_ref()
+    }
+
+    /// Returns how the length of the body was determined when the response was read,
+    /// see [`BodyFraming`]
+    ///
+    /// [`BodyFraming::None`] for responses that were built instead of read from a connection
+    pub fn body_framing(&self) -> BodyFraming {
+        self.framing
+    }
+
+    /// Sets a callback that takes over the connection after this response is sent
+    ///
+    /// The server stops reading requests from the connection,
+    /// and passes the raw stream to f to speak another protocol.
+    /// Used after `101 Switching Protocols`, or after a `2xx` to `CONNECT` to tunnel the stream.
+    /// The connection is closed when the future returned by f completes.
+    /// When the request body is still streamed into a [`BodyStream`](crate::message::BodyStream),
+    /// the server keeps reading it, and the stream passed to f has no reader: reads return EOF at once
+    ///
+    /// ```ignore
+    /// response.on_upgrade(|mut stream| {
+    ///     Box::pin(async move {
+    ///         let _ = stream.write_all(b"hello").await;
+    ///     })
+    /// });
+    /// ```
+    pub fn on_upgrade<F>(&mut self, f: F)
+    where
+        F: for<'a> FnOnce(Upgraded<'a>) -> UpgradeFuture<'a> + Send + 'static,
+    {
+        self.upgrade = Some(OnUpgrade::new(f));
+    }
+
+    pub(crate) fn take_upgrade(&mut self) -> Option<OnUpgrade> {
+        self.upgrade.take()
+    }
+
+    /// Returns the headers to adjust them, e.g. in middleware after the handler built the response
+    pub fn headers_mut(&mut self) -> &mut Headers {
+        &mut self.headers
+    }
+
+    /// Sets a header, replacing any earlier value, and returns the response for chaining
+    ///
+    /// ```ignore
+    /// let response = Response::no_content().with_header("Cache-Control", "no-store");
+    /// ```
+    pub fn with_header<K, V>(mut self, name: K, value: V) -> Response
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.headers.set(name, value);
+        self
+    }
+
+    /// Creates an empty `500 Internal Server Error` response
     pub fn internal_error() -> Response {
-        Response {
-            status_line: StatusLine::new(StatusCode::InternalServerError),
-            headers: Headers::new(), // TODO: Add headers??
-            body: Vec::new(),
+        Response::new(StatusCode::InternalServerError)
+    }
+
+    /// Creates an empty `200 Ok` response
+    pub fn ok() -> Response {
+        Response::new(StatusCode::Ok)
+    }
+
+    /// Creates an empty `204 No Content` response
+    pub fn no_content() -> Response {
+        Response::new(StatusCode::NoContent)
+    }
+
+    /// Creates an empty `400 Bad Request` response
+    pub fn bad_request() -> Response {
+        Response::new(StatusCode::BadRequest)
+    }
+
+    /// Creates an empty `401 Unauthorized` response
+    pub fn unauthorized() -> Response {
+        Response::new(StatusCode::Unauthorized)
+    }
+
+    /// Creates an empty `404 Not Found` response
+    pub fn not_found() -> Response {
+        Response::new(StatusCode::NotFound)
+    }
+
+    /// Creates an empty `405 Method Not Allowed` response
+    ///
+    /// RFC 9110 Section 15.5.6 requires an `Allow` header listing the allowed methods
+    pub fn method_not_allowed() -> Response {
+        Response::new(StatusCode::MethodNotAllowed)
+    }
+
+    /// Compresses the body with the coding the client prefers out of codecs,
+    /// according to `Accept-Encoding`, and returns the name of the coding
+    ///
+    /// `Content-Encoding` is set to the coding, and `Accept-Encoding` is added to `Vary`,
+    /// since the body depends on it, RFC 9110 Section 12.5.5.
+    /// Returns None, leaving the body as it is, when the client accepts none of the codings,
+    /// the body is empty, already has a `Content-Encoding`, or is a range of a larger body
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if encoding the body fails
+    pub fn compress(&mut self, req: &Request, codecs: &Codecs) -> io::Result<Option<String>> {
+        if self.body.is_empty()
+            || self.headers.get("Content-Encoding").is_some()
+            || self.headers.get("Content-Range").is_some()
+        {
+            return Ok(None);
         }
+        if !self.headers.field_contains_value("Vary", "Accept-Encoding") {
+            self.headers.add("Vary", "Accept-Encoding");
+        }
+        let Some(coding) = req.preferred_encoding(codecs.names()) else {
+            return Ok(None);
+        };
+        let Some(codec) = codecs.get(coding) else {
+            return Ok(None);
+        };
+
+        self.body = codec.encode(&self.body)?.into();
+        self.headers.set("Content-Encoding", coding);
+        if self.headers.get("Content-Length").is_some() {
+            self.headers.set_content_length(self.body.len() as u64);
+        }
+        Ok(Some(coding.to_string()))
     }
 
     /// Creates response from file
     ///
+    /// `Content-Length` is replaced with the length of the body when the response is written,
+    /// so it is sent once even if it is set again before that
+    ///
     /// # Errors
     ///
     /// This function will return an error if it fails to read from the file
-    pub fn from_file(filename: &str, content_type: &str) -> io::Result<Response> {
+    pub fn from_file(filename: impl AsRef<Path>, content_type: &str) -> io::Result<Response> {
         let filecontent = fs::read(filename)?;
         let mut headers = Headers::new();
-        headers.add("Content-Length", filecontent.len().to_string());
-        headers.add("Content-Type", content_type);
+        headers.set_content_length(filecontent.len() as u64);
+        headers.set("Content-Type", content_type);
         Ok(Response {
             status_line: StatusLine::new(StatusCode::Ok),
             headers,
-            body: filecontent,
+            body: filecontent.into(),
+            upgrade: None,
+            trailers: None,
+            framing: BodyFraming::None,
         })
     }
+
+    /// Creates response from file, answering conditional and range requests
+    ///
+    /// The response has an `ETag` computed from the size and modification time of the file,
+    /// and a `Last-Modified` header.
+    /// If the request has a matching `If-None-Match` or `If-Modified-Since`
+    /// the response is 304 Not Modified without a body.
+    ///
+    /// A satisfiable range gives 206 Partial Content with only the requested bytes,
+    /// while a range outside of the file, or with its last byte before its first,
+    /// gives 416 Range Not Satisfiable.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if it fails to read from the file
+    pub fn serve_file(
+        req: &Request,
+        filename: impl AsRef<Path>,
+        content_type: &str,
+    ) -> io::Result<Response> {
+        let filename = filename.as_ref();
+        let mut file = File::open(filename)?;
+        let metadata = file.metadata()?;
+        let len = metadata.len();
+        let etag = file_etag(&metadata);
+        let last_modified = metadata.modified().ok();
+
+        if req.is_not_modified(Some(&etag), last_modified) {
+            let mut response = Response::new(StatusCode::NotModified);
+            set_validators(&mut response.headers, &etag, last_modified);
+            return Ok(response);
+        }
+
+        let Some(range) = req.range() else {
+            let mut response = Response::from_file(filename, content_type)?;
+            response.headers.set("Accept-Ranges", "bytes");
+            set_validators(&mut response.headers, &etag, last_modified);
+            return Ok(response);
+        };
+
+        let Some((start, end)) = range.resolve(len) else {
+            let mut response = Response::new(StatusCode::RangeNotSatisfiable);
+            response
+                .headers
+                .set("Content-Range", format!("bytes */{len}"));
+            // Without a framing field the empty body would only end when the connection closes
+            response.headers.set_content_length(0);
+            return Ok(response);
+        };
+
+        let mut body = vec![0u8; (end - start + 1) as usize];
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(&mut body)?;
+
+        let mut response = Response::new(StatusCode::PartialContent);
+        response.headers.set("Content-Type", content_type);
+        response.headers.set("Accept-Ranges", "bytes");
+        response
+            .headers
+            .set("Content-Range", format!("bytes {start}-{end}/{len}"));
+        set_validators(&mut response.headers, &etag, last_modified);
+        response.body = body.into();
+        Ok(response)
+    }
+
+    /// Creates response from file, with the `Content-Type` guessed from its extension
+    ///
+    /// Works like [`Response::serve_file`], answering conditional and range requests,
+    /// so serving a directory of static files only needs a route that maps the path to a file.
+    /// Unknown extensions are sent as `application/octet-stream`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if it fails to read from the file
+    pub fn file(req: &Request, path: impl AsRef<Path>) -> io::Result<Response> {
+        let path = path.as_ref();
+        Response::serve_file(req, path, media_type::from_path(path))
+    }
+}
+
+/// Creates a strong etag from the size and modification time of a file
+fn file_etag(metadata: &Metadata) -> String {
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+        .unwrap_or_default();
+    format!("\"{:x}-{:x}\"", modified.as_secs(), metadata.len())
+}
+
+fn set_validators(headers: &mut Headers, etag: &str, last_modified: Option<SystemTime>) {
+    headers.set("ETag", etag);
+    if let Some(last_modified) = last_modified {
+        headers.set("Last-Modified", http_date::format(last_modified));
+    }
+}
+
+/// Adds name to the `Trailer` header, unless it is already listed
+pub(crate) fn announce_trailer(headers: &mut Headers, name: &str) {
+    let announced = headers.get("Trailer").is_some_and(|trailer| {
+        trailer
+            .split(',')
+            .any(|n| n.trim().eq_ignore_ascii_case(name))
+    });
+    if !announced {
+        headers.add("Trailer", name);
+    }
+}
+
+impl From<StatusCode> for Response {
+    fn from(status_code: StatusCode) -> Response {
+        Response::new(status_code)
+    }
+}
+
+/// Clones the message, the status line, headers, body, trailers and framing
+///
+/// The callback set with [`Response::on_upgrade`] can only be called once, so it is not cloned
+impl Clone for Response {
+    fn clone(&self) -> Self {
+        Response {
+            status_line: self.status_line.clone(),
+            headers: self.headers.clone(),
+            body: self.body.clone(),
+            upgrade: None,
+            trailers: self.trailers.clone(),
+            framing: self.framing,
+        }
+    }
 }
 
 // TODO: Is this stupid??
 // Might also just provide body as the writer in the handlers
 impl io::Write for Response {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        io::Write::write(&mut self.body, buf)
+        // Bytes can't grow, its buffer is only reused when the body is not shared
+        let mut body = Vec::from(std::mem::take(&mut self.body));
+        body.extend_from_slice(buf);
+        self.body = body.into();
+        Ok(buf.len())
     }
 
     fn flush(&mut self) -> io::Result<()> {
-        io::Write::flush(&mut self.body)
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::message::{Method, RequestBuilder};
     use pretty_assertions::assert_eq;
 
+    /// Writes a fixture file into the temp dir, returning its path
+    fn fixture(name: &str, content: &[u8]) -> String {
+        let path = std::env::temp_dir().join(format!("rust-http-{}-{name}", std::process::id()));
+        fs::write(&path, content).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn test_clone() {
+        let mut response = Response::new(StatusCode::SwitchingProtocols);
+        response.headers.set("Upgrade", "websocket");
+        response.body = Bytes::from_static(b"body");
+        response.set_trailer("Digest", "abc");
+        response.on_upgrade(|_| Box::pin(async {}));
+
+        let clone = response.clone();
+        assert_eq!(
+            clone.status_line.status_code,
+            StatusCode::SwitchingProtocols
+        );
+        assert_eq!(clone.headers.get("Upgrade"), Some(&"websocket".to_string()));
+        assert_eq!(clone.body, &b"body"[..]);
+        assert!(clone.trailers.is_some());
+        assert!(clone.upgrade.is_none());
+        assert!(response.upgrade.is_some());
+    }
+
+    #[test]
+    fn test_serve_file_range() -> io::Result<()> {
+        let path = fixture("range.txt", b"0123456789");
+
+        let req = RequestBuilder::new(Method::Get, "/").build();
+        let response = Response::serve_file(&req, &path, "text/plain")?;
+        assert_eq!(response.status_line.status_code, StatusCode::Ok);
+        assert_eq!(response.body, &b"0123456789"[..]);
+        assert_eq!(
+            response.headers.get("Accept-Ranges"),
+            Some(&"bytes".to_string())
+        );
+
+        let cases: [(&str, &[u8], &str); 4] = [
+            ("bytes=2-4", b"234", "bytes 2-4/10"),
+            ("bytes=7-", b"789", "bytes 7-9/10"),
+            ("bytes=-2", b"89", "bytes 8-9/10"),
+            ("bytes=8-20", b"89", "bytes 8-9/10"),
+        ];
+        for (range, body, content_range) in cases {
+            let req = RequestBuilder::new(Method::Get, "/")
+                .header("Range", range)
+                .build();
+            let response = Response::serve_file(&req, &path, "text/plain")?;
+            assert_eq!(response.status_line.status_code, StatusCode::PartialContent);
+            assert_eq!(response.body, body);
+            assert_eq!(
+                response.headers.get("Content-Range"),
+                Some(&content_range.to_string())
+            );
+        }
+
+        let req = RequestBuilder::new(Method::Get, "/")
+            .header("Range", "bytes=10-")
+            .build();
+        let response = Response::serve_file(&req, &path, "text/plain")?;
+        assert_eq!(
+            response.status_line.status_code,
+            StatusCode::RangeNotSatisfiable
+        );
+        assert!(response.body.is_empty());
+        assert_eq!(
+            response.headers.get("Content-Range"),
+            Some(&"bytes */10".to_string())
+        );
+
+        let req = RequestBuilder::new(Method::Get, "/")
+            .header("Range", "bytes=4-2")
+            .build();
+        let response = Response::serve_file(&req, &path, "text/plain")?;
+        assert_eq!(
+            response.status_line.status_code,
+            StatusCode::RangeNotSatisfiable
+        );
+        assert!(response.body.is_empty());
+        assert!(
+            String::from_utf8(response.to_bytes()?)
+                .unwrap()
+                .contains("\r\nContent-Length: 0\r\n")
+        );
+
+        fs::remove_file(path)
+    }
+
+    #[test]
+    fn test_serve_file_conditional() -> io::Result<()> {
+        let path = fixture("etag.txt", b"0123456789");
+
+        let req = RequestBuilder::new(Method::Get, "/").build();
+        let response = Response::serve_file(&req, &path, "text/plain")?;
+        assert_eq!(response.status_line.status_code, StatusCode::Ok);
+        let etag = response.headers.get("ETag").unwrap().clone();
+        let last_modified = response.headers.get("Last-Modified").unwrap().clone();
+
+        let req = RequestBuilder::new(Method::Get, "/")
+            .header("If-None-Match", &etag)
+            .build();
+        let response = Response::serve_file(&req, &path, "text/plain")?;
+        assert_eq!(response.status_line.status_code, StatusCode::NotModified);
+        assert!(response.body.is_empty());
+        assert_eq!(response.headers.get("ETag"), Some(&etag));
+
+        let req = RequestBuilder::new(Method::Get, "/")
+            .header("If-None-Match", "\"other\"")
+            .build();
+        let response = Response::serve_file(&req, &path, "text/plain")?;
+        assert_eq!(response.status_line.status_code, StatusCode::Ok);
+        assert_eq!(response.body, &b"0123456789"[..]);
+
+        let req = RequestBuilder::new(Method::Get, "/")
+            .header("If-Modified-Since", &last_modified)
+            .build();
+        let response = Response::serve_file(&req, &path, "text/plain")?;
+        assert_eq!(response.status_line.status_code, StatusCode::NotModified);
+        assert!(response.body.is_empty());
+
+        fs::remove_file(path)
+    }
+
+    #[test]
+    fn test_with_header() {
+        let mut response = Response::no_content()
+            .with_header("Cache-Control", "no-cache")
+            .with_header("Cache-Control", "no-store")
+            .with_header("X-Frame-Options", "DENY");
+        response.headers_mut().remove("X-Frame-Options");
+
+        assert_eq!(
+            response.to_bytes().unwrap(),
+            b"HTTP/1.1 204 No Content\r\nCache-Control: no-store\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn test_from_file_content_length() -> io::Result<()> {
+        let content_lengths = |response: &Response| {
+            String::from_utf8_lossy(&response.to_bytes().unwrap())
+                .lines()
+                .filter(|line| line.starts_with("Content-Length"))
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        };
+
+        let path = fixture("length.txt", b"0123456789");
+        let mut response = Response::from_file(&path, "text/plain")?;
+        assert_eq!(content_lengths(&response), ["Content-Length: 10"]);
+        response.headers.add("Content-Length", "999");
+        assert_eq!(content_lengths(&response), ["Content-Length: 10"]);
+
+        fs::write(&path, b"")?;
+        let response = Response::from_file(&path, "text/plain")?;
+        assert_eq!(content_lengths(&response), ["Content-Length: 0"]);
+
+        fs::remove_file(path)
+    }
+
+    #[test]
+    fn test_file() -> io::Result<()> {
+        let path = fixture("page.html", b"<h1>Hi</h1>");
+
+        let req = RequestBuilder::new(Method::Get, "/").build();
+        let response = Response::file(&req, &path)?;
+        assert_eq!(response.status_line.status_code, StatusCode::Ok);
+        assert_eq!(
+            response.headers.get("Content-Type"),
+            Some(&"text/html; charset=utf-8".to_string())
+        );
+        assert_eq!(
+            response.headers.get("Content-Length"),
+            Some(&"11".to_string())
+        );
+        assert!(response.headers.get("ETag").is_some());
+        assert!(response.headers.get("Last-Modified").is_some());
+        assert_eq!(response.body, &b"<h1>Hi</h1>"[..]);
+
+        let req = RequestBuilder::new(Method::Get, "/")
+            .header("Range", "bytes=0-3")
+            .build();
+        let response = Response::file(&req, &path)?;
+        assert_eq!(response.status_line.status_code, StatusCode::PartialContent);
+        assert_eq!(response.body, &b"<h1>"[..]);
+
+        let missing = RequestBuilder::new(Method::Get, "/").build();
+        assert!(Response::file(&missing, format!("{path}.missing")).is_err());
+
+        fs::remove_file(path)
+    }
+
+    #[test]
+    fn test_compress() -> io::Result<()> {
+        use crate::message::{Codec, Gzip};
+
+        let codecs = Codecs::default();
+        let body = b"compress me, compress me, compress me".to_vec();
+        let req = RequestBuilder::new(Method::Get, "/")
+            .header("Accept-Encoding", "br;q=0.5, gzip")
+            .build();
+
+        let mut response = Response::ok();
+        response
+            .headers
+            .set("Content-Length", body.len().to_string());
+        response.body = body.clone().into();
+        assert_eq!(response.compress(&req, &codecs)?, Some("gzip".to_string()));
+        assert_eq!(
+            response.headers.get("Content-Encoding"),
+            Some(&"gzip".to_string())
+        );
+        assert_eq!(
+            response.headers.get("Vary"),
+            Some(&"Accept-Encoding".to_string())
+        );
+        assert_eq!(
+            response.headers.get("Content-Length"),
+            Some(&response.body.len().to_string())
+        );
+        assert_eq!(Gzip.decode(&response.body, body.len())?, body);
+
+        // Already encoded
+        assert_eq!(response.compress(&req, &codecs)?, None);
+
+        let req = RequestBuilder::new(Method::Get, "/").build();
+        let mut response = Response::ok();
+        response.body = body.clone().into();
+        assert_eq!(response.compress(&req, &codecs)?, None);
+        assert_eq!(response.body, body);
+        assert_eq!(
+            response.headers.get("Vary"),
+            Some(&"Accept-Encoding".to_string())
+        );
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_write_response() -> io::Result<()> {
         let mut response = Response::new(StatusCode::Ok);
@@ -97,14 +710,147 @@ mod tests {
         response.headers.add("Content-Type", "text/plain");
         buf = Vec::new();
         response.write_to(&mut buf).await?;
-        assert_eq!(buf, b"HTTP/1.1 200 Ok\r\ncontent-type: text/plain\r\n\r\n");
+        assert_eq!(buf, b"HTTP/1.1 200 Ok\r\nContent-Type: text/plain\r\n\r\n");
 
         buf = Vec::new();
-        response.body.write_all(b"Hello").await?;
+        io::Write::write_all(&mut response, b"Hello")?;
         response.write_to(&mut buf).await?;
         assert_eq!(
             buf,
-            b"HTTP/1.1 200 Ok\r\ncontent-length: 5\r\ncontent-type: text/plain\r\n\r\nHello"
+            b"HTTP/1.1 200 Ok\r\nContent-Type: text/plain\r\nContent-Length: 5\r\n\r\nHello"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_write_response_trailers() -> io::Result<()> {
+        let mut response = Response::new(StatusCode::Ok);
+        response.headers = Headers::new();
+        response.headers.set("Trailer", "Checksum");
+        response.body = Bytes::from_static(b"Hello");
+        response.set_trailer("Checksum", "abc");
+
+        let mut buf = Vec::new();
+        response.write_to(&mut buf).await?;
+        assert_eq!(
+            String::from_utf8_lossy(&buf),
+            "HTTP/1.1 200 Ok\r\nTrailer: Checksum\r\nTransfer-Encoding: chunked\r\n\r\n\
+            5\r\nHello\r\n0\r\nChecksum: abc\r\n\r\n"
+        );
+
+        response.body.clear();
+        let mut buf = Vec::new();
+        response.write_to(&mut buf).await?;
+        assert_eq!(
+            String::from_utf8_lossy(&buf),
+            "HTTP/1.1 200 Ok\r\nTrailer: Checksum\r\nTransfer-Encoding: chunked\r\n\r\n\
+            0\r\nChecksum: abc\r\n\r\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_shortcuts() {
+        for (response, status_code) in [
+            (Response::ok(), StatusCode::Ok),
+            (Response::no_content(), StatusCode::NoContent),
+            (Response::bad_request(), StatusCode::BadRequest),
+            (Response::unauthorized(), StatusCode::Unauthorized),
+            (Response::not_found(), StatusCode::NotFound),
+            (Response::method_not_allowed(), StatusCode::MethodNotAllowed),
+            (Response::internal_error(), StatusCode::InternalServerError),
+            (
+                Response::from(StatusCode::BadGateway),
+                StatusCode::BadGateway,
+            ),
+        ] {
+            assert_eq!(response.status_line.status_code, status_code);
+            assert!(response.body.is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_to_bytes() -> io::Result<()> {
+        let mut response = Response::new(StatusCode::NoContent);
+        response.headers = Headers::new();
+        assert_eq!(response.to_bytes()?, b"HTTP/1.1 204 No Content\r\n\r\n");
+
+        // The same bytes as write_to, with and without trailers
+        let mut response = Response::new(StatusCode::Ok);
+        response.body = vec![b'a'; 4096].into();
+        for trailer in [false, true] {
+            if trailer {
+                response.set_trailer("Checksum", "abc");
+            }
+            let mut buf = Vec::new();
+            response.write_to(&mut buf).await?;
+            assert_eq!(response.to_bytes()?, buf);
+        }
+
+        // An invalid field is an error instead of being sent
+        let mut response = Response::new(StatusCode::Ok);
+        response.headers.set("X-Test", "a\r\nInjected: 1");
+        let err = response.to_bytes().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_set_trailer_announces() -> io::Result<()> {
+        let mut response = Response::new(StatusCode::Ok);
+        response.headers = Headers::new();
+        response.body = Bytes::from_static(b"Hello");
+        response.set_trailer("Checksum", "abc");
+        response.set_trailer("checksum", "def");
+        response.set_trailer("Expires", "never");
+        assert_eq!(
+            response.headers.get("Trailer"),
+            Some(&"Checksum,Expires".to_string())
+        );
+
+        let bytes = response.to_bytes()?;
+        let (head, body) =
+            bytes.split_at(bytes.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4);
+        assert_eq!(
+            String::from_utf8_lossy(head),
+            "HTTP/1.1 200 Ok\r\nTrailer: Checksum,Expires\r\nTransfer-Encoding: chunked\r\n\r\n"
+        );
+        // The last chunk is followed by the trailer fields and an empty line
+        let body = String::from_utf8_lossy(body);
+        let (chunks, trailer_section) = body.split_once("0\r\n").unwrap();
+        assert_eq!(chunks, "5\r\nHello\r\n");
+        assert!(trailer_section.ends_with("\r\n\r\n"));
+        let mut fields = trailer_section.trim_end().split("\r\n").collect::<Vec<_>>();
+        fields.sort();
+        assert_eq!(fields, ["Checksum: def", "Expires: never"]);
+
+        response.remove_trailers();
+        assert!(response.trailers().is_none());
+        assert_eq!(response.headers.get("Trailer"), None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_write_response_does_not_change_response() -> io::Result<()> {
+        let mut response = Response::new(StatusCode::Ok);
+        response.headers = Headers::new();
+        response.headers.set("Content-Length", "1");
+        response.body = Bytes::from_static(b"Hello");
+
+        let mut first = Vec::new();
+        response.write_to(&mut first).await?;
+        let mut second = Vec::new();
+        response.write_to(&mut second).await?;
+
+        assert_eq!(first, b"HTTP/1.1 200 Ok\r\nContent-Length: 5\r\n\r\nHello");
+        assert_eq!(first, second);
+        assert_eq!(
+            response.headers.get("Content-Length"),
+            Some(&"1".to_string())
         );
 
         Ok(())
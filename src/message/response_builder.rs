@@ -1,7 +1,13 @@
-use std::io::Write;
+use std::{
+    fmt::{self, Display},
+    io,
+};
 
 use crate::{
-    message::{Headers, Response, StatusCode, StatusLine},
+    message::{
+        BodyFraming, Headers, Response, StatusCode, StatusLine, StatusLineError,
+        response::announce_trailer,
+    },
     server::ServerError,
 };
 
@@ -9,6 +15,7 @@ pub struct ResponseBuilder {
     status_line: StatusLine,
     headers: Headers,
     body: Vec<u8>,
+    trailers: Option<Headers>,
 }
 
 impl ResponseBuilder {
@@ -22,14 +29,29 @@ impl ResponseBuilder {
             status_line: StatusLine::new(StatusCode::Ok),
             headers: Headers::new(),
             body: Vec::new(),
+            trailers: None,
         }
     }
 
+    /// Sets the status code, which also resets the reason phrase to its default
     pub fn set_status_code(&mut self, status_code: StatusCode) -> &mut Self {
         self.status_line = StatusLine::new(status_code);
         self
     }
 
+    /// Sets the reason phrase sent after the status code, see [`StatusLine::set_reason`]
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reason is not a valid reason phrase
+    pub fn set_reason_phrase(
+        &mut self,
+        reason: impl Into<String>,
+    ) -> Result<&mut Self, StatusLineError> {
+        self.status_line.set_reason(reason)?;
+        Ok(self)
+    }
+
     /// Write a header.
     /// Will overwride old values
     pub fn add_header<K, V>(&mut self, name: K, value: V) -> &mut Self
@@ -41,8 +63,75 @@ impl ResponseBuilder {
         self
     }
 
+    /// Adds a header only when cond is true, see [`ResponseBuilder::add_header`]
+    ///
+    /// ```ignore
+    /// builder.header_if(cacheable, "Cache-Control", "max-age=3600");
+    /// ```
+    pub fn header_if<K, V>(&mut self, cond: bool, name: K, value: V) -> &mut Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        if cond {
+            self.headers.add(name, value);
+        }
+        self
+    }
+
+    /// Removes every value of a header that was added earlier
+    pub fn remove_header(&mut self, name: &str) -> &mut Self {
+        self.headers.remove(name);
+        self
+    }
+
+    /// Sets `Content-Type`, from a `&str` or a [`MediaType`](crate::message::MediaType)
+    pub fn content_type(&mut self, media_type: impl Display) -> &mut Self {
+        self.headers.set("Content-Type", media_type.to_string());
+        self
+    }
+
+    /// Sets `Cache-Control`, such as `no-store` or `max-age=3600`
+    pub fn cache_control(&mut self, directives: impl Into<String>) -> &mut Self {
+        self.headers.set("Cache-Control", directives);
+        self
+    }
+
+    /// Sets `Location`, where a redirect or a created resource is found
+    ///
+    /// Only sets the header, the status code is set with [`ResponseBuilder::set_status_code`]
+    pub fn location(&mut self, url: impl Into<String>) -> &mut Self {
+        self.headers.set("Location", url);
+        self
+    }
+
+    /// Sets the status to 401 Unauthorized and asks for basic authentication in the realm
+    ///
+    /// The realm is sent as a quoted-string, escaping `"` and `\`, RFC 9110 Section 5.6.4
+    pub fn www_authenticate(&mut self, realm: &str) -> &mut Self {
+        self.set_status_code(StatusCode::Unauthorized);
+        let realm = realm.replace('\\', "\\\\").replace('"', "\\\"");
+        self.headers
+            .set("WWW-Authenticate", format!("Basic realm=\"{realm}\""));
+        self
+    }
+
+    /// Adds a trailer field sent after the body, see [`Response::set_trailer`]
+    pub fn set_trailer<K, V>(&mut self, name: K, value: V) -> &mut Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let name = name.into();
+        announce_trailer(&mut self.headers, &name);
+        self.trailers
+            .get_or_insert_with(Headers::new)
+            .set(name, value);
+        self
+    }
+
     pub fn add_to_body(&mut self, body: &[u8]) -> Result<&mut Self, ServerError> {
-        self.body.write_all(body)?;
+        io::Write::write_all(&mut self.body, body)?;
         Ok(self)
     }
 
@@ -50,7 +139,10 @@ impl ResponseBuilder {
         Response {
             status_line: self.status_line,
             headers: self.headers,
-            body: self.body,
+            body: self.body.into(),
+            upgrade: None,
+            trailers: self.trailers,
+            framing: BodyFraming::None,
         }
     }
 }
@@ -61,6 +153,34 @@ impl Default for ResponseBuilder {
     }
 }
 
+/// Appends to the body, same as [`Response`]
+impl io::Write for ResponseBuilder {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        io::Write::write(&mut self.body, buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::Write::flush(&mut self.body)
+    }
+}
+
+/// Appends formatted text to the body, so `write!` can be used without a temporary `String`
+///
+/// ```ignore
+/// use std::fmt::Write;
+///
+/// let mut builder = ResponseBuilder::new();
+/// for item in items {
+///     write!(builder, "<li>{item}</li>")?;
+/// }
+/// ```
+impl fmt::Write for ResponseBuilder {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.body.extend_from_slice(s.as_bytes());
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -78,4 +198,110 @@ mod test {
         assert_eq!(response.status_line.status_code, StatusCode::Ok);
         assert_eq!(response.headers.get("AA"), Some(&"BB".to_string()));
     }
+
+    #[tokio::test]
+    async fn test_reason_phrase() -> Result<(), Box<dyn std::error::Error>> {
+        let mut builder = ResponseBuilder::new();
+        builder
+            .set_status_code(StatusCode::Other(420))
+            .set_reason_phrase("Enhance Your Calm")?;
+        let mut response = builder.build();
+        response.headers = Headers::new();
+
+        let mut buf = Vec::new();
+        response.write_to(&mut buf).await?;
+        assert_eq!(buf, b"HTTP/1.1 420 Enhance Your Calm\r\n\r\n");
+
+        // A new status code gets its default reason
+        let mut builder = ResponseBuilder::new();
+        builder.set_reason_phrase("Fine")?;
+        builder.set_status_code(StatusCode::NotFound);
+        assert_eq!(builder.build().status_line.reason(), "Not Found");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_trailers() -> Result<(), ServerError> {
+        let mut builder = ResponseBuilder::new();
+        builder
+            .add_to_body(b"Hello")?
+            .set_trailer("Checksum", "abc");
+        let response = builder.build();
+
+        assert_eq!(
+            response.headers.get("Trailer"),
+            Some(&"Checksum".to_string())
+        );
+        assert_eq!(
+            response.trailers().and_then(|t| t.get("Checksum")),
+            Some(&"abc".to_string())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_body() -> Result<(), Box<dyn std::error::Error>> {
+        use std::fmt::Write as _;
+
+        let mut builder = ResponseBuilder::new();
+        builder.add_to_body(b"a")?;
+        write!(builder, "{}-{}", 1, 2)?;
+        io::Write::write_all(&mut builder, b"\n")?;
+        let response = builder.build();
+
+        assert_eq!(response.body, &b"a1-2\n"[..]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_www_authenticate() {
+        let mut builder = ResponseBuilder::new();
+        builder.www_authenticate("admin");
+        let response = builder.build();
+
+        assert_eq!(response.status_line.status_code, StatusCode::Unauthorized);
+        assert_eq!(
+            response.headers.get("WWW-Authenticate"),
+            Some(&"Basic realm=\"admin\"".to_string())
+        );
+
+        // A quote or backslash in the realm can't end the quoted-string early
+        let mut builder = ResponseBuilder::new();
+        builder.www_authenticate(r#"Bob's "admin" \ area"#);
+        assert_eq!(
+            builder.build().headers.get("WWW-Authenticate"),
+            Some(&r#"Basic realm="Bob's \"admin\" \\ area""#.to_string())
+        );
+    }
+
+    #[test]
+    fn test_typed_headers() {
+        let mut builder = ResponseBuilder::new();
+        builder
+            .content_type("text/plain; charset=utf-8")
+            .cache_control("no-store")
+            .location("/login");
+        let response = builder.build();
+
+        let header = |name| response.headers.get(name).map(String::as_str);
+        assert_eq!(header("Content-Type"), Some("text/plain; charset=utf-8"));
+        assert_eq!(header("Cache-Control"), Some("no-store"));
+        assert_eq!(header("Location"), Some("/login"));
+    }
+
+    #[test]
+    fn test_remove_header() {
+        let mut builder = ResponseBuilder::new();
+        builder
+            .add_header("X-Debug", "1")
+            .add_header("X-Keep", "1")
+            .header_if(false, "X-Skipped", "1")
+            .header_if(true, "X-Added", "1")
+            .remove_header("x-debug");
+        let response = builder.build();
+
+        let names = response.headers.iter().map(|(k, _)| k).collect::<Vec<_>>();
+        assert_eq!(names, ["x-keep", "x-added"]);
+    }
 }
@@ -0,0 +1,302 @@
+use std::{io, time::Duration};
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    sync::mpsc,
+};
+
+use crate::message::{Request, Response, StatusCode, Upgraded, write::encode_chunk};
+
+/// How long the stream can be idle before a comment is sent,
+/// so proxies don't close it and a disconnected client is noticed
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Events that can be queued before [`EventSender::send`] waits for the stream to catch up
+const EVENT_BUFFER: usize = 16;
+
+/// An event of a `text/event-stream`, see [`Response::event_stream`]
+///
+/// ```ignore
+/// let event = Event::new("{\"price\": 42}").event("price").id("7");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Event {
+    data: String,
+    event: Option<String>,
+    id: Option<String>,
+    retry: Option<Duration>,
+}
+
+impl Event {
+    /// Creates an event with data, which can span multiple lines
+    pub fn new(data: impl Into<String>) -> Event {
+        Event {
+            data: data.into(),
+            ..Event::default()
+        }
+    }
+
+    /// Sets the event type, which clients listen for with `addEventListener`
+    pub fn event(mut self, event: impl Into<String>) -> Self {
+        self.event = Some(event.into());
+        self
+    }
+
+    /// Sets the id, which a reconnecting client sends back in `Last-Event-ID`
+    pub fn id(mut self, id: impl Into<String>) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    /// Sets how long the client waits before reconnecting
+    pub fn retry(mut self, retry: Duration) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    /// Serializes the event, ending with the empty line that dispatches it
+    ///
+    /// Line breaks are removed from the event type and id, which are single lines,
+    /// and the data is sent as one `data:` field per line
+    pub fn encode(&self) -> String {
+        let single_line = |value: &str| value.replace(['\r', '\n', '\0'], "");
+        let mut frame = String::new();
+        if let Some(event) = &self.event {
+            frame.push_str(&format!("event: {}\n", single_line(event)));
+        }
+        if let Some(id) = &self.id {
+            frame.push_str(&format!("id: {}\n", single_line(id)));
+        }
+        if let Some(retry) = self.retry {
+            frame.push_str(&format!("retry: {}\n", retry.as_millis()));
+        }
+        let data = self.data.replace("\r\n", "\n").replace('\r', "\n");
+        for line in data.split('\n') {
+            frame.push_str(&format!("data: {line}\n"));
+        }
+        frame.push('\n');
+        frame
+    }
+}
+
+/// Sends events to the client of a `text/event-stream` response, see [`Response::event_stream`]
+///
+/// Can be cloned to send from several tasks.
+/// The stream ends when every sender is dropped
+#[derive(Debug, Clone)]
+pub struct EventSender(mpsc::Sender<Event>);
+
+impl EventSender {
+    /// Queues the event to be written, waiting if the client is reading slower than events are sent
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`io::ErrorKind::BrokenPipe`] error if the client disconnected,
+    /// so producing events can stop
+    pub async fn send(&self, event: Event) -> io::Result<()> {
+        self.0
+            .send(event)
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "Client disconnected"))
+    }
+
+    /// Returns true if the client disconnected
+    pub fn is_closed(&self) -> bool {
+        self.0.is_closed()
+    }
+}
+
+impl Response {
+    /// Creates a `text/event-stream` response for server-sent events,
+    /// and the sender the handler pushes events with
+    ///
+    /// The events are written as they are sent, flushing after each of them,
+    /// with `Cache-Control: no-cache` so they are not buffered by caches.
+    /// A comment is sent when no event was sent for 15 seconds, to keep the connection open.
+    /// The stream is chunked for HTTP/1.1 clients and ends with the connection for HTTP/1.0.
+    /// Events are written by a callback set with [`Response::on_upgrade`],
+    /// so the connection is closed after the stream, and it is only served over HTTP/1.x.
+    /// A HEAD request only gets the head, and the sender is closed
+    ///
+    /// ```ignore
+    /// fn events(req: &Request) -> Result<Response, ServerError> {
+    ///     let (response, sender) = Response::event_stream(req);
+    ///     tokio::spawn(async move {
+    ///         for i in 0.. {
+    ///             if sender.send(Event::new(i.to_string())).await.is_err() {
+    ///                 break;
+    ///             }
+    ///             tokio::time::sleep(Duration::from_secs(1)).await;
+    ///         }
+    ///     });
+    ///     Ok(response)
+    /// }
+    /// ```
+    pub fn event_stream(req: &Request) -> (Response, EventSender) {
+        let (sender, receiver) = mpsc::channel(EVENT_BUFFER);
+        let chunked = !req.line.version.is_http_1_0();
+
+        let mut response = Response::new(StatusCode::Ok);
+        response.headers.set("Content-Type", "text/event-stream");
+        response.headers.set("Cache-Control", "no-cache");
+        response.headers.set("Connection", "close");
+        if chunked {
+            response.headers.set("Transfer-Encoding", "chunked");
+        }
+        response.on_upgrade(move |stream| Box::pin(write_events(stream, receiver, chunked)));
+        (response, EventSender(sender))
+    }
+}
+
+/// Writes the events from receiver until every sender is dropped or the client disconnects
+async fn write_events(
+    mut stream: Upgraded<'_>,
+    mut receiver: mpsc::Receiver<Event>,
+    chunked: bool,
+) {
+    let mut buf = [0u8; 64];
+    // The stream has no reader after a streamed request body, see Response::duplex,
+    // and a client can shut down only its sending side, so the end of reading is not a disconnect
+    let mut reading = true;
+    loop {
+        let frame = tokio::select! {
+            event = receiver.recv() => match event {
+                Some(event) => event.encode(),
+                None => break,
+            },
+            // Clients send nothing after the request, a failed write notices a disconnect
+            read = stream.read(&mut buf), if reading => match read {
+                Ok(0) => {
+                    reading = false;
+                    continue;
+                }
+                Ok(_) => continue,
+                Err(_) => return,
+            },
+            _ = tokio::time::sleep(KEEP_ALIVE_INTERVAL) => ":\n\n".to_string(),
+        };
+        if write_frame(&mut stream, frame.as_bytes(), chunked)
+            .await
+            .is_err()
+        {
+            return;
+        }
+    }
+    if chunked {
+        let _ = stream.write_all(b"0\r\n\r\n").await;
+        let _ = stream.flush().await;
+    }
+}
+
+async fn write_frame(stream: &mut Upgraded<'_>, frame: &[u8], chunked: bool) -> io::Result<()> {
+    if chunked {
+        let mut chunk = Vec::with_capacity(frame.len() + 16);
+        encode_chunk(&mut chunk, frame);
+        stream.write_all(&chunk).await?;
+    } else {
+        stream.write_all(frame).await?;
+    }
+    stream.flush().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::{HttpVersion, Method, RequestBuilder};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(Event::new("hello").encode(), "data: hello\n\n");
+        assert_eq!(
+            Event::new("a\r\nb\nc")
+                .event("up\ndate")
+                .id("7")
+                .retry(Duration::from_secs(3))
+                .encode(),
+            "event: update\nid: 7\nretry: 3000\ndata: a\ndata: b\ndata: c\n\n"
+        );
+        assert_eq!(Event::new("").encode(), "data: \n\n");
+    }
+
+    /// Runs the stream of the response, with a client that stays connected until it is done
+    async fn run_stream(mut response: Response) -> String {
+        let (client, server) = tokio::io::duplex(1024);
+        let (reader, _) = tokio::io::split(server);
+        let mut written = Vec::new();
+        let upgrade = response.take_upgrade().unwrap();
+        upgrade
+            .call(Upgraded::new(reader, Vec::new(), &mut written))
+            .await;
+        drop(client);
+        String::from_utf8(written).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_event_stream() -> io::Result<()> {
+        let req = RequestBuilder::new(Method::Get, "/events").build();
+        let (response, sender) = Response::event_stream(&req);
+        let header = |name| response.headers.get(name).map(String::as_str);
+        assert_eq!(header("Content-Type"), Some("text/event-stream"));
+        assert_eq!(header("Cache-Control"), Some("no-cache"));
+        assert_eq!(header("Transfer-Encoding"), Some("chunked"));
+
+        sender.send(Event::new("one")).await?;
+        sender.send(Event::new("two").event("tick")).await?;
+        drop(sender);
+        assert_eq!(
+            run_stream(response).await,
+            "b\r\ndata: one\n\n\r\n17\r\nevent: tick\ndata: two\n\n\r\n0\r\n\r\n"
+        );
+
+        let mut req = RequestBuilder::new(Method::Get, "/events").build();
+        req.line.version = HttpVersion::new(1, 0);
+        let (response, sender) = Response::event_stream(&req);
+        assert_eq!(response.headers.get("Transfer-Encoding"), None);
+        sender.send(Event::new("one")).await?;
+        drop(sender);
+        assert_eq!(run_stream(response).await, "data: one\n\n");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_event_stream_without_reader() -> io::Result<()> {
+        let req = RequestBuilder::new(Method::Post, "/events").build();
+        let (mut response, sender) = Response::event_stream(&req);
+        sender.send(Event::new("one")).await?;
+        drop(sender);
+
+        // As after a streamed request body, where reads end at once
+        let mut written = Vec::new();
+        let upgrade = response.take_upgrade().unwrap();
+        upgrade
+            .call(Upgraded::new(tokio::io::empty(), Vec::new(), &mut written))
+            .await;
+        assert_eq!(
+            String::from_utf8(written).unwrap(),
+            "b\r\ndata: one\n\n\r\n0\r\n\r\n"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_event_stream_client_disconnect() -> io::Result<()> {
+        let req = RequestBuilder::new(Method::Get, "/events").build();
+        let (mut response, sender) = Response::event_stream(&req);
+        sender.send(Event::new("one")).await?;
+
+        // The client closed the connection, so writing the event fails
+        let (client, server) = tokio::io::duplex(1024);
+        drop(client);
+        let (reader, writer) = tokio::io::split(server);
+        let upgrade = response.take_upgrade().unwrap();
+        upgrade
+            .call(Upgraded::new(reader, Vec::new(), writer))
+            .await;
+
+        assert!(sender.is_closed());
+        let err = sender.send(Event::new("late")).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+        Ok(())
+    }
+}
@@ -9,51 +9,187 @@ use crate::message::{error::StatusLineError, version::HttpVersion};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum StatusCode {
-    Ok,                  // 200
-    BadRequest,          // 400
-    NotFound,            // 404
-    MethodNotAllowed,    // 405
-    InternalServerError, // 500
+    Continue,                    // 100
+    SwitchingProtocols,          // 101
+    EarlyHints,                  // 103
+    Ok,                          // 200
+    NoContent,                   // 204
+    PartialContent,              // 206
+    NotModified,                 // 304
+    BadRequest,                  // 400
+    Unauthorized,                // 401
+    NotFound,                    // 404
+    MethodNotAllowed,            // 405
+    NotAcceptable,               // 406
+    RequestTimeout,              // 408
+    PreconditionFailed,          // 412
+    ContentTooLarge,             // 413
+    UriTooLong,                  // 414
+    RangeNotSatisfiable,         // 416
+    RequestHeaderFieldsTooLarge, // 431
+    InternalServerError,         // 500
+    BadGateway,                  // 502
+    ServiceUnavailable,          // 503
+    HttpVersionNotSupported,     // 505
+    /// Any other code from 100 to 999, which has no default reason phrase
+    ///
+    /// [`StatusCode::from_u16`] returns the named variant for codes that have one,
+    /// so the codes received compare equal to the named variants
+    Other(u16),
 }
 
 impl StatusCode {
-    pub fn to_code(&self) -> String {
+    /// Returns true for interim 1xx status codes
+    pub fn is_informational(&self) -> bool {
+        (100..200).contains(&self.as_u16())
+    }
+
+    /// Returns true for 2xx status codes
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.as_u16())
+    }
+
+    /// Returns true for 3xx status codes
+    pub fn is_redirection(&self) -> bool {
+        (300..400).contains(&self.as_u16())
+    }
+
+    /// Returns true for 4xx status codes
+    pub fn is_client_error(&self) -> bool {
+        (400..500).contains(&self.as_u16())
+    }
+
+    /// Returns true for 5xx status codes
+    pub fn is_server_error(&self) -> bool {
+        (500..600).contains(&self.as_u16())
+    }
+
+    /// Returns the numeric status code, e.g. 404 for [`StatusCode::NotFound`]
+    pub fn as_u16(&self) -> u16 {
         match self {
-            Self::Ok => "200",
-            Self::BadRequest => "400",
-            Self::NotFound => "404",
-            Self::MethodNotAllowed => "405",
-            Self::InternalServerError => "500",
+            Self::Continue => 100,
+            Self::SwitchingProtocols => 101,
+            Self::EarlyHints => 103,
+            Self::Ok => 200,
+            Self::NoContent => 204,
+            Self::PartialContent => 206,
+            Self::NotModified => 304,
+            Self::BadRequest => 400,
+            Self::Unauthorized => 401,
+            Self::NotFound => 404,
+            Self::MethodNotAllowed => 405,
+            Self::NotAcceptable => 406,
+            Self::RequestTimeout => 408,
+            Self::PreconditionFailed => 412,
+            Self::ContentTooLarge => 413,
+            Self::UriTooLong => 414,
+            Self::RangeNotSatisfiable => 416,
+            Self::RequestHeaderFieldsTooLarge => 431,
+            Self::InternalServerError => 500,
+            Self::BadGateway => 502,
+            Self::ServiceUnavailable => 503,
+            Self::HttpVersionNotSupported => 505,
+            Self::Other(code) => *code,
         }
-        .to_string()
     }
+
+    pub fn to_code(&self) -> String {
+        self.as_u16().to_string()
+    }
+
     pub fn to_reason(&self) -> String {
         match self {
+            Self::Continue => "Continue",
+            Self::SwitchingProtocols => "Switching Protocols",
+            Self::EarlyHints => "Early Hints",
             Self::Ok => "Ok",
+            Self::NoContent => "No Content",
+            Self::PartialContent => "Partial Content",
+            Self::NotModified => "Not Modified",
             Self::BadRequest => "Bad Request",
+            Self::Unauthorized => "Unauthorized",
             Self::NotFound => "Not Found",
             Self::MethodNotAllowed => "Method Not Allowed",
+            Self::NotAcceptable => "Not Acceptable",
+            Self::RequestTimeout => "Request Timeout",
+            Self::PreconditionFailed => "Precondition Failed",
+            Self::ContentTooLarge => "Content Too Large",
+            Self::UriTooLong => "URI Too Long",
+            Self::RangeNotSatisfiable => "Range Not Satisfiable",
+            Self::RequestHeaderFieldsTooLarge => "Request Header Fields Too Large",
             Self::InternalServerError => "Internal Server Error",
+            Self::BadGateway => "Bad Gateway",
+            Self::ServiceUnavailable => "Service Unavailable",
+            Self::HttpVersionNotSupported => "HTTP Version Not Supported",
+            Self::Other(_) => "",
         }
         .to_string()
     }
 
+    /// Returns the status code for a numeric code, using the named variant when there is one
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if code is not between 100 and 999
+    pub fn from_u16(code: u16) -> Result<StatusCode, StatusLineError> {
+        Ok(match code {
+            100 => Self::Continue,
+            101 => Self::SwitchingProtocols,
+            103 => Self::EarlyHints,
+            200 => Self::Ok,
+            204 => Self::NoContent,
+            206 => Self::PartialContent,
+            304 => Self::NotModified,
+            400 => Self::BadRequest,
+            401 => Self::Unauthorized,
+            404 => Self::NotFound,
+            405 => Self::MethodNotAllowed,
+            406 => Self::NotAcceptable,
+            408 => Self::RequestTimeout,
+            412 => Self::PreconditionFailed,
+            413 => Self::ContentTooLarge,
+            414 => Self::UriTooLong,
+            416 => Self::RangeNotSatisfiable,
+            431 => Self::RequestHeaderFieldsTooLarge,
+            500 => Self::InternalServerError,
+            502 => Self::BadGateway,
+            503 => Self::ServiceUnavailable,
+            505 => Self::HttpVersionNotSupported,
+            _ if (100..=999).contains(&code) => Self::Other(code),
+            _ => return Err(StatusLineError::InvalidStatusCode),
+        })
+    }
+
+    /// Follows RFC 9110 Section 15
+    ///
+    /// status-code = 3DIGIT
     pub fn parse(bytes: &[u8]) -> Result<StatusCode, StatusLineError> {
         match bytes {
-            b"200" => Ok(Self::Ok),
-            b"400" => Ok(Self::BadRequest),
-            b"404" => Ok(Self::NotFound),
-            b"405" => Ok(Self::MethodNotAllowed),
-            b"500" => Ok(Self::InternalServerError),
+            [a, b, c] if bytes.iter().all(u8::is_ascii_digit) => Self::from_u16(
+                u16::from(a - b'0') * 100 + u16::from(b - b'0') * 10 + u16::from(c - b'0'),
+            ),
             _ => Err(StatusLineError::InvalidStatusCode),
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct StatusLine {
     pub version: HttpVersion,
     pub status_code: StatusCode,
+    /// Used instead of the default reason of the status code when set
+    reason: Option<String>,
+    /// The phrase received when parsed, which can be empty,
+    /// with the status code it came with so it is not sent with another one
+    received: Option<(StatusCode, String)>,
+}
+
+/// reason-phrase = 1*( HTAB / SP / VCHAR / obs-text )
+fn is_valid_reason(reason: &[u8]) -> bool {
+    !reason.is_empty()
+        && reason
+            .iter()
+            .all(|&b| b == b'\t' || b == b' ' || b.is_ascii_graphic() || b >= 0x80)
 }
 
 impl StatusLine {
@@ -61,7 +197,41 @@ impl StatusLine {
         StatusLine {
             version: HttpVersion::new(1, 1),
             status_code,
+            reason: None,
+            received: None,
+        }
+    }
+
+    /// Returns the reason phrase, the one set with [`StatusLine::set_reason`] or received,
+    /// otherwise the default for the status code
+    ///
+    /// A received phrase is returned as it was sent, so it is empty if the server sent none.
+    /// It is only used while the status code is the one it was received with,
+    /// so a proxy changing the status code of a forwarded response doesn't send a stale phrase
+    pub fn reason(&self) -> String {
+        if let Some(reason) = &self.reason {
+            return reason.clone();
+        }
+        match &self.received {
+            Some((status_code, reason)) if *status_code == self.status_code => reason.clone(),
+            _ => self.status_code.to_reason(),
+        }
+    }
+
+    /// Sets a reason phrase to send instead of the default for the status code
+    ///
+    /// The reason phrase is only informational, clients act on the status code
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reason is empty, or has CR, LF or other control characters
+    pub fn set_reason(&mut self, reason: impl Into<String>) -> Result<(), StatusLineError> {
+        let reason = reason.into();
+        if !is_valid_reason(reason.as_bytes()) {
+            return Err(StatusLineError::InvalidReasonPhrase);
         }
+        self.reason = Some(reason);
+        Ok(())
     }
 
     /// Follows RFC 9112
@@ -74,17 +244,20 @@ impl StatusLine {
     /// Returns Error if write fails
     pub async fn write_to<W: AsyncWriteExt + Unpin>(&self, mut w: W) -> io::Result<()> {
         let mut buf = Vec::new();
+        self.encode(&mut buf)?;
+        w.write_all(&buf).await?;
+        Ok(())
+    }
 
+    /// Appends the status line, including CRLF, to buf
+    pub(crate) fn encode(&self, buf: &mut Vec<u8>) -> io::Result<()> {
         write!(
             buf,
             "HTTP/{} {} {}\r\n",
             self.version,
             self.status_code.to_code(),
-            self.status_code.to_reason()
-        )?;
-
-        w.write_all(&buf).await?;
-        Ok(())
+            self.reason()
+        )
     }
 
     /// Follows RFC 9112 Section 4
@@ -103,32 +276,47 @@ impl StatusLine {
     pub fn from_line(line: &[u8]) -> Result<StatusLine, StatusLineError> {
         let parts = line.splitn(3, |&b| b == b' ').collect::<Vec<&[u8]>>();
         if parts.len() == 1 {
-            return Err(StatusLineError::MalformedStatusLine);
+            return Err(StatusLineError::malformed(
+                line,
+                line.len(),
+                "missing status code",
+            ));
         }
 
         let version_parts = parts[0].split(|&b| b == b'/').collect::<Vec<&[u8]>>();
         if version_parts.len() != 2 || version_parts[0] != b"HTTP" {
-            return Err(StatusLineError::MalformedStatusLine);
+            return Err(StatusLineError::malformed(
+                line,
+                0,
+                "expected version starting with HTTP/",
+            ));
         }
         let version = HttpVersion::from_bytes(version_parts[1])?;
         let status_code = StatusCode::parse(parts[1])?;
+        // The reason phrase is optional, and kept as received to show what the server sent
+        let reason = match parts.get(2) {
+            Some(reason) if is_valid_reason(reason) => String::from_utf8_lossy(reason).into_owned(),
+            Some([]) | None => String::new(),
+            Some(_) => return Err(StatusLineError::InvalidReasonPhrase),
+        };
 
         Ok(StatusLine {
             version,
             status_code,
+            reason: None,
+            received: Some((status_code, reason)),
         })
     }
 }
 
+/// Shows the status line as received, or as it is sent, without the space before an empty reason phrase
 impl Display for StatusLine {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "HTTP/{} {} {}",
-            self.version,
-            self.status_code.to_code(),
-            self.status_code.to_reason()
-        )
+        write!(f, "HTTP/{} {}", self.version, self.status_code.to_code())?;
+        match self.reason() {
+            reason if reason.is_empty() => Ok(()),
+            reason => write!(f, " {reason}"),
+        }
     }
 }
 
@@ -147,6 +335,45 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_status_line_custom_reason() -> Result<(), StatusLineError> {
+        let mut status_line = StatusLine::new(StatusCode::Ok);
+        status_line.set_reason("Everything Is Fine")?;
+        let mut buf = Vec::new();
+        status_line.write_to(&mut buf).await.unwrap();
+        assert_eq!(buf, b"HTTP/1.1 200 Everything Is Fine\r\n");
+
+        for invalid in ["", "Bad\r\nSet-Cookie: a=b", "Bad\0"] {
+            assert!(status_line.set_reason(invalid).is_err(), "{invalid:?}");
+        }
+        assert_eq!(status_line.reason(), "Everything Is Fine");
+
+        let rl = StatusLine::from_line(b"HTTP/1.1 404 Nothing Here")?;
+        assert_eq!(rl.status_code, StatusCode::NotFound);
+        assert_eq!(rl.reason(), "Nothing Here");
+        assert_eq!(rl.to_string(), "HTTP/1.1 404 Nothing Here");
+
+        // The phrase is kept as sent, even when it only differs from the default in case or is empty
+        let rl = StatusLine::from_line(b"HTTP/1.1 200 OK")?;
+        assert_eq!(rl.to_string(), "HTTP/1.1 200 OK");
+        let rl = StatusLine::from_line(b"HTTP/1.1 404 ")?;
+        assert_eq!(rl.reason(), "");
+        assert_eq!(rl.to_string(), "HTTP/1.1 404");
+        let mut buf = Vec::new();
+        rl.write_to(&mut buf).await.unwrap();
+        assert_eq!(buf, b"HTTP/1.1 404 \r\n");
+        assert!(StatusLine::from_line(b"HTTP/1.1 404 Not\x01Found").is_err());
+
+        // A changed status code gets its default reason, unless one is set
+        let mut rl = StatusLine::from_line(b"HTTP/1.1 200 OK")?;
+        rl.status_code = StatusCode::NotFound;
+        assert_eq!(rl.to_string(), "HTTP/1.1 404 Not Found");
+        rl.set_reason("Gone Fishing")?;
+        assert_eq!(rl.to_string(), "HTTP/1.1 404 Gone Fishing");
+
+        Ok(())
+    }
+
     #[test]
     fn test_status_line_parse() -> Result<(), StatusLineError> {
         let input = b"HTTP/1.1 200 Ok";
@@ -171,4 +398,64 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_status_line_parse_error_position() {
+        let err = StatusLine::from_line(b"HTP/1.1 200 Ok").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Malformed status line \"HTP/1.1 200 Ok\" at byte 0: expected version starting with HTTP/"
+        );
+        assert!(matches!(
+            StatusLine::from_line(b"HTTP/1.1"),
+            Err(StatusLineError::MalformedStatusLine { offset: 8, .. })
+        ));
+    }
+
+    #[test]
+    fn test_status_code_parse() {
+        assert_eq!(StatusCode::parse(b"404").unwrap(), StatusCode::NotFound);
+        for code in [201, 301, 302, 403, 420, 429, 999] {
+            let status = StatusCode::parse(code.to_string().as_bytes()).unwrap();
+            assert_eq!(status, StatusCode::Other(code));
+            assert_eq!(status.as_u16(), code);
+        }
+        for invalid in [&b"099"[..], b"1000", b"20", b"2a0", b"+20", b""] {
+            assert!(StatusCode::parse(invalid).is_err(), "{invalid:?}");
+        }
+        assert_eq!(StatusCode::from_u16(404).unwrap(), StatusCode::NotFound);
+        assert!(StatusCode::from_u16(1000).is_err());
+
+        let rl = StatusLine::from_line(b"HTTP/1.1 301 Moved Permanently").unwrap();
+        assert_eq!(rl.status_code, StatusCode::Other(301));
+        assert!(rl.status_code.is_redirection());
+        assert_eq!(rl.to_string(), "HTTP/1.1 301 Moved Permanently");
+        // Without a default reason an unnamed code is sent with an empty one
+        assert_eq!(
+            StatusLine::new(StatusCode::Other(420)).to_string(),
+            "HTTP/1.1 420"
+        );
+    }
+
+    #[test]
+    fn test_status_code_class() {
+        let cases = [
+            (StatusCode::Continue, 100),
+            (StatusCode::Ok, 200),
+            (StatusCode::NotModified, 304),
+            (StatusCode::NotFound, 404),
+            (StatusCode::InternalServerError, 500),
+            (StatusCode::Other(302), 302),
+            (StatusCode::Other(429), 429),
+        ];
+        for (status, code) in cases {
+            assert_eq!(status.as_u16(), code);
+            assert_eq!(status.to_code(), code.to_string());
+            assert_eq!(status.is_informational(), code / 100 == 1, "{code}");
+            assert_eq!(status.is_success(), code / 100 == 2, "{code}");
+            assert_eq!(status.is_redirection(), code / 100 == 3, "{code}");
+            assert_eq!(status.is_client_error(), code / 100 == 4, "{code}");
+            assert_eq!(status.is_server_error(), code / 100 == 5, "{code}");
+        }
+    }
 }
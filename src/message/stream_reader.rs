@@ -1,70 +1,265 @@
 use tokio::io::{self, AsyncRead, AsyncReadExt};
 
+/// Size of the read buffer used by [`StreamReader::new`]
+pub const DEFAULT_BUFFER_SIZE: usize = 2048;
+
+/// Buffered reader used by the parsers
+///
+/// Bytes are read into a fixed buffer, where `start..end` is the part not yet consumed.
+/// Lines that fit in the buffer are returned as slices into it,
+/// only lines longer than the buffer are copied into a reused `Vec`.
 pub struct StreamReader<R> {
-    read: usize,
-    buf: [u8; 2048],
+    start: usize,
+    end: usize,
+    buf: Box<[u8]>,
+    line: Vec<u8>,
+    lenient_lf: bool,
     reader: R,
 }
 
 impl<R: AsyncRead + Unpin> StreamReader<R> {
     pub fn new(reader: R) -> Self {
+        Self::with_capacity(reader, DEFAULT_BUFFER_SIZE)
+    }
+
+    /// Creates a [`StreamReader`] with a read buffer of capacity bytes.
+    /// Lines longer than the buffer can still be read, but are slower
+    ///
+    /// # Panics
+    ///
+    /// Panics if capacity is 0
+    pub fn with_capacity(reader: R, capacity: usize) -> Self {
+        assert!(capacity > 0, "StreamReader capacity must be larger than 0");
         StreamReader {
-            read: 0,
-            buf: [0u8; 2048],
+            start: 0,
+            end: 0,
+            buf: vec![0u8; capacity].into_boxed_slice(),
+            line: Vec::new(),
+            lenient_lf: false,
             reader,
         }
     }
 
-    pub async fn read_line(&mut self) -> io::Result<Vec<u8>> {
-        let mut out = Vec::new();
-        let mut last_was_carrage_return = false;
+    /// Returns the inner reader, and the bytes that were read from it but not yet consumed
+    pub fn into_parts(self) -> (R, Vec<u8>) {
+        (self.reader, self.buf[self.start..self.end].to_vec())
+    }
+
+    /// Sets whether a bare LF also ends a line
+    ///
+    /// Off by default, so only CRLF ends a line.
+    /// RFC 9112 Section 2.2 allows accepting a bare LF, which some hand written clients send
+    pub fn set_lenient_lf(&mut self, lenient: bool) {
+        self.lenient_lf = lenient;
+    }
+
+    /// Checks if lenient parsing is on, see [`StreamReader::set_lenient_lf`]
+    pub(crate) fn is_lenient(&self) -> bool {
+        self.lenient_lf
+    }
+
+    /// Returns the next n bytes without consuming them, reading until they are buffered
+    ///
+    /// Used to detect the protocol before parsing, e.g. a TLS ClientHello or the HTTP/2 preface.
+    /// Fewer than n bytes are returned if EOF is reached first
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the underlying reader fails, or n is larger than the buffer capacity
+    pub async fn peek(&mut self, n: usize) -> io::Result<&[u8]> {
+        if n > self.buf.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Can't peek more bytes than the buffer capacity",
+            ));
+        }
+        if self.start + n > self.buf.len() {
+            self.buf.copy_within(self.start..self.end, 0);
+            self.end -= self.start;
+            self.start = 0;
+        }
+        while self.end - self.start < n {
+            let read = self.reader.read(&mut self.buf[self.end..]).await?;
+            if read == 0 {
+                break;
+            }
+            self.end += read;
+        }
+        let available = (self.end - self.start).min(n);
+        Ok(&self.buf[self.start..self.start + available])
+    }
+
+    /// Reads until CRLF, returning the line without the CRLF
+    ///
+    /// With [`StreamReader::set_lenient_lf`] a bare LF also ends the line
+    ///
+    /// The returned slice is only valid until the next read
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the underlying reader fails, or EOF is reached before CRLF
+    pub async fn read_line(&mut self) -> io::Result<&[u8]> {
+        let line = self.read_line_limited(usize::MAX).await?;
+        Ok(line.expect("No line is longer than usize::MAX"))
+    }
+
+    /// Same as [`StreamReader::read_line`], but returns None once the line is longer than max bytes
+    ///
+    /// At most about max bytes of the line are held in memory.
+    /// After None the reader is left in the middle of the line,
+    /// and the start of the line is kept, see [`StreamReader::overlong_line`]
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the underlying reader fails, or EOF is reached before CRLF
+    pub async fn read_line_limited(&mut self, max: usize) -> io::Result<Option<&[u8]>> {
+        self.line.clear();
+        let mut scan = self.start;
         loop {
-            for (i, &b) in self.buf[..self.read].iter().enumerate() {
-                if last_was_carrage_return && b == b'\n' {
-                    out.pop();
-                    let index = (i + 1).min(self.read);
-                    self.buf.copy_within(index..self.read, 0);
-                    self.read -= index;
-                    return Ok(out);
+            while let Some(i) = self.buf[scan..self.end].iter().position(|&b| b == b'\n') {
+                let lf = scan + i;
+                let after_cr = if lf > self.start {
+                    self.buf[lf - 1] == b'\r'
+                } else {
+                    self.line.last() == Some(&b'\r')
+                };
+                if !after_cr && !self.lenient_lf {
+                    scan = lf + 1;
+                    continue;
                 }
 
-                out.push(b);
+                let line_start = self.start;
+                let line_end = if lf > line_start {
+                    lf - usize::from(after_cr)
+                } else {
+                    lf
+                };
+                self.start = lf + 1;
+                if self.line.is_empty() {
+                    if line_end - line_start > max {
+                        self.line.extend_from_slice(&self.buf[line_start..line_end]);
+                        return Ok(None);
+                    }
+                    return Ok(Some(&self.buf[line_start..line_end]));
+                }
+                if lf > line_start {
+                    self.line.extend_from_slice(&self.buf[line_start..line_end]);
+                } else if after_cr {
+                    // The CR was the last byte of the previous buffer
+                    self.line.pop();
+                }
+                if self.line.len() > max {
+                    return Ok(None);
+                }
+                return Ok(Some(&self.line));
+            }
 
-                last_was_carrage_return = b == b'\r';
+            // No complete line is buffered, make room and read more
+            if self.start > 0 {
+                self.buf.copy_within(self.start..self.end, 0);
+                self.end -= self.start;
+                self.start = 0;
             }
+            // One more byte is allowed, as it can be the CR before the LF
+            if self.line.len() + self.end > max.saturating_add(1) {
+                self.line.extend_from_slice(&self.buf[..self.end]);
+                self.end = 0;
+                return Ok(None);
+            }
+            if self.end == self.buf.len() {
+                self.line.extend_from_slice(&self.buf[..self.end]);
+                self.end = 0;
+            }
+            scan = self.end;
 
-            let n = self.reader.read(&mut self.buf).await?;
+            let n = self.reader.read(&mut self.buf[self.end..]).await?;
             if n == 0 {
                 return Err(io::Error::new(
-                    io::ErrorKind::ConnectionAborted,
+                    io::ErrorKind::UnexpectedEof,
                     "Unexpected EOF",
                 ));
             }
-            self.read = n;
+            self.end += n;
         }
     }
 
+    /// Returns the start of the line that [`StreamReader::read_line_limited`] last found too long,
+    /// at least its first max bytes, e.g. to tell which part of it was too long
+    pub(crate) fn overlong_line(&self) -> &[u8] {
+        &self.line
+    }
+
+    /// Reads until EOF, returning None if there are more than max bytes
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the underlying reader fails
+    pub async fn read_to_end_limited(&mut self, max: usize) -> io::Result<Option<Vec<u8>>> {
+        let mut out = self.buf[self.start..self.end].to_vec();
+        self.start = 0;
+        self.end = 0;
+        if out.len() > max {
+            return Ok(None);
+        }
+        // One byte more than allowed is read to tell a body of exactly max bytes from a larger one
+        let limit = (max - out.len()).saturating_add(1) as u64;
+        (&mut self.reader).take(limit).read_to_end(&mut out).await?;
+        Ok((out.len() <= max).then_some(out))
+    }
+
+    /// Reads exactly n bytes
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the underlying reader fails, or EOF is reached before n bytes
     pub async fn read_n(&mut self, n: usize) -> io::Result<Vec<u8>> {
-        let mut buf = Vec::with_capacity(n);
-        let len = self.read.min(n);
-        if self.read > 0 {
-            buf.extend_from_slice(&self.buf[..len]);
-            self.buf.copy_within(len.., 0);
-            self.read -= len;
+        let mut out = Vec::new();
+        self.read_n_into(n, &mut out).await?;
+        Ok(out)
+    }
+
+    /// Reads exactly n bytes, appending them to out
+    ///
+    /// Memory is not reserved up front for the bytes that are not buffered,
+    /// so a large n will not allocate before the data has arrived
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the underlying reader fails, or EOF is reached before n bytes.
+    /// On error out is left as it was
+    pub async fn read_n_into(&mut self, n: usize, out: &mut Vec<u8>) -> io::Result<()> {
+        let old_len = out.len();
+        let buffered = (self.end - self.start).min(n);
+        out.extend_from_slice(&self.buf[self.start..self.start + buffered]);
+        self.start += buffered;
+        if self.start == self.end {
+            self.start = 0;
+            self.end = 0;
         }
 
-        let remaining = n.saturating_sub(len);
+        let remaining = n - buffered;
         if remaining == 0 {
-            return Ok(buf);
+            return Ok(());
         }
-        let mut b = vec![0u8; remaining];
-        self.reader.read_exact(&mut b).await?;
-
-        // copies into the buf from self.read
-        // if self.read is longer than n or buf len then it will return earlier
-        buf.extend_from_slice(&b);
 
-        Ok(buf)
+        let read = (&mut self.reader)
+            .take(remaining as u64)
+            .read_to_end(out)
+            .await;
+        match read {
+            Ok(read) if read == remaining => Ok(()),
+            Ok(_) => {
+                out.truncate(old_len);
+                Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "Unexpected EOF",
+                ))
+            }
+            Err(e) => {
+                out.truncate(old_len);
+                Err(e)
+            }
+        }
     }
 }
 
@@ -82,25 +277,25 @@ mod tests {
         let mut reader = StreamReader::new(&mut c);
 
         let out = reader.read_line().await?;
-        assert_eq!(String::from_utf8_lossy(&out), "GET / HTTP/1.1".to_string());
+        assert_eq!(String::from_utf8_lossy(out), "GET / HTTP/1.1".to_string());
 
         let out = reader.read_line().await?;
         assert_eq!(
-            String::from_utf8_lossy(&out),
+            String::from_utf8_lossy(out),
             "Host: localhost:42069".to_string()
         );
 
         let out = reader.read_line().await?;
         assert_eq!(
-            String::from_utf8_lossy(&out),
+            String::from_utf8_lossy(out),
             "User-Agent: curl/7.81.0".to_string()
         );
 
         let out = reader.read_line().await?;
-        assert_eq!(String::from_utf8_lossy(&out), "Accept: */*".to_string());
+        assert_eq!(String::from_utf8_lossy(out), "Accept: */*".to_string());
 
         let out = reader.read_line().await?;
-        assert_eq!(String::from_utf8_lossy(&out), "".to_string());
+        assert_eq!(String::from_utf8_lossy(out), "".to_string());
 
         Ok(())
     }
@@ -118,7 +313,106 @@ mod tests {
         let mut reader = StreamReader::new(&mut c);
 
         let out = reader.read_line().await?;
-        assert_eq!(out, input[..input.len().saturating_sub(2)]);
+        assert_eq!(out, &input[..input.len().saturating_sub(2)]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stream_reader_with_capacity() -> io::Result<()> {
+        let input = b"a long line\r\nb\r\n".to_vec();
+        let mut c = Cursor::new(input);
+        let mut reader = StreamReader::with_capacity(&mut c, 4);
+        assert_eq!(reader.buf.len(), 4);
+
+        assert_eq!(reader.read_line().await?, b"a long line");
+        assert_eq!(reader.read_line().await?, b"b");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stream_reader_crlf_split_between_reads() -> io::Result<()> {
+        use crate::message::test_utils::batch_reader::BatchReader;
+
+        let input = b"ab\r\ncd\r\n\r\n".to_vec();
+        for batch_size in 1..input.len() {
+            let mut reader = StreamReader::new(BatchReader::new(input.clone(), batch_size));
+            assert_eq!(reader.read_line().await?, b"ab");
+            assert_eq!(reader.read_line().await?, b"cd");
+            assert_eq!(reader.read_line().await?, b"");
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stream_reader_bare_lf() -> io::Result<()> {
+        let mut c = Cursor::new(b"a\nb\r\n");
+        let mut reader = StreamReader::new(&mut c);
+        assert_eq!(reader.read_line().await?, b"a\nb");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stream_reader_lenient_lf() -> io::Result<()> {
+        use crate::message::test_utils::batch_reader::BatchReader;
+
+        let input = b"ab\ncd\r\nefgh\n\n".to_vec();
+        for batch_size in 1..input.len() {
+            for capacity in [3, DEFAULT_BUFFER_SIZE] {
+                let batch_reader = BatchReader::new(input.clone(), batch_size);
+                let mut reader = StreamReader::with_capacity(batch_reader, capacity);
+                reader.set_lenient_lf(true);
+                assert_eq!(reader.read_line().await?, b"ab");
+                assert_eq!(reader.read_line().await?, b"cd");
+                assert_eq!(reader.read_line().await?, b"efgh");
+                assert_eq!(reader.read_line().await?, b"");
+            }
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_line_limited() -> io::Result<()> {
+        use crate::message::test_utils::batch_reader::BatchReader;
+
+        let input = b"abcd\r\nabcdef\r\n".to_vec();
+        for batch_size in 1..input.len() {
+            for capacity in [3, DEFAULT_BUFFER_SIZE] {
+                let batch_reader = BatchReader::new(input.clone(), batch_size);
+                let mut reader = StreamReader::with_capacity(batch_reader, capacity);
+                assert_eq!(reader.read_line_limited(4).await?, Some(&b"abcd"[..]));
+                assert_eq!(reader.read_line_limited(4).await?, None);
+                assert!(reader.overlong_line().starts_with(b"abcd"));
+            }
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_peek() -> io::Result<()> {
+        use crate::message::test_utils::batch_reader::BatchReader;
+
+        let input = b"GET / HTTP/1.1\r\n".to_vec();
+        for batch_size in 1..input.len() {
+            let mut reader =
+                StreamReader::with_capacity(BatchReader::new(input.clone(), batch_size), 8);
+            assert_eq!(reader.peek(3).await?, b"GET");
+            assert_eq!(reader.read_n(4).await?, b"GET ");
+            // Moves the unread bytes to the front to fit
+            assert_eq!(reader.peek(8).await?, b"/ HTTP/1");
+            assert_eq!(reader.read_line().await?, b"/ HTTP/1.1");
+            assert_eq!(reader.peek(1).await?, b"");
+        }
+
+        let mut c = Cursor::new(b"ab");
+        let mut reader = StreamReader::with_capacity(&mut c, 4);
+        assert!(reader.peek(5).await.is_err());
+        assert_eq!(reader.peek(4).await?, b"ab");
 
         Ok(())
     }
@@ -168,4 +462,73 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_read_n_from_leftover() -> io::Result<()> {
+        use crate::message::test_utils::batch_reader::BatchReader;
+
+        // A batch larger than the line leaves the following bytes buffered,
+        // which several read_n calls then consume
+        let input = b"head\r\nabcdefghij\r\ntail\r\n".to_vec();
+        for batch_size in 1..=input.len() {
+            for capacity in [4, 8, DEFAULT_BUFFER_SIZE] {
+                let batch_reader = BatchReader::new(input.clone(), batch_size);
+                let mut reader = StreamReader::with_capacity(batch_reader, capacity);
+                assert_eq!(reader.read_line().await?, b"head");
+                assert_eq!(reader.read_n(3).await?, b"abc");
+                assert_eq!(reader.read_n(0).await?, b"");
+                assert_eq!(reader.read_n(1).await?, b"d");
+                assert_eq!(reader.read_n(6).await?, b"efghij");
+                assert_eq!(reader.read_line().await?, b"");
+                assert_eq!(reader.read_n(2).await?, b"ta");
+                // EOF before all bytes leaves out as it was
+                let mut out = b"kept".to_vec();
+                assert!(reader.read_n_into(5, &mut out).await.is_err());
+                assert_eq!(out, b"kept");
+            }
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_keep_alive_messages() -> io::Result<()> {
+        use crate::message::test_utils::batch_reader::BatchReader;
+
+        // Three requests on one connection, with bodies that end inside a buffered batch
+        let messages: [(&[u8], &[u8]); 3] = [
+            (b"POST /a HTTP/1.1", b"hello"),
+            (b"PUT /bb HTTP/1.1", b"0123456789abcdefghij"),
+            (b"POST /c HTTP/1.1", b"x"),
+        ];
+        let mut input = Vec::new();
+        for (line, body) in messages {
+            input.extend_from_slice(line);
+            input.extend_from_slice(
+                format!("\r\nContent-Length: {}\r\n\r\n", body.len()).as_bytes(),
+            );
+            input.extend_from_slice(body);
+        }
+
+        for batch_size in [1, 2, 3, 7, 16, 33, 64, input.len()] {
+            for capacity in [5, 16, DEFAULT_BUFFER_SIZE] {
+                let batch_reader = BatchReader::new(input.clone(), batch_size);
+                let mut reader = StreamReader::with_capacity(batch_reader, capacity);
+                for (line, body) in messages {
+                    assert_eq!(reader.read_line().await?, line);
+                    let header = format!("Content-Length: {}", body.len());
+                    assert_eq!(reader.read_line().await?, header.as_bytes());
+                    assert_eq!(reader.read_line().await?, b"");
+
+                    let mut out = b"prefix".to_vec();
+                    reader.read_n_into(body.len(), &mut out).await?;
+                    assert_eq!(&out[..6], b"prefix");
+                    assert_eq!(&out[6..], body);
+                }
+                assert_eq!(reader.peek(1).await?, b"");
+            }
+        }
+
+        Ok(())
+    }
 }
@@ -1,4 +1,4 @@
-#[cfg(test)]
+#[cfg(any(test, feature = "test-util"))]
 pub mod batch_reader {
     use std::{
         io,
@@ -7,8 +7,8 @@ pub mod batch_reader {
     };
 
     use tokio::io::AsyncRead;
-    use tokio::io::AsyncReadExt;
 
+    /// Reader returning at most batch_size bytes per read, like a socket receiving packets
     pub struct BatchReader {
         src: Vec<u8>,
         batch_size: usize,
@@ -48,6 +48,7 @@ pub mod batch_reader {
     mod tests {
         use super::*;
         use pretty_assertions::assert_eq;
+        use tokio::io::AsyncReadExt;
 
         #[tokio::test]
         async fn test_request_parser() -> std::io::Result<()> {
@@ -96,3 +97,86 @@ pub mod batch_reader {
         }
     }
 }
+
+#[cfg(test)]
+pub mod counting_writer {
+    use std::{
+        io,
+        pin::Pin,
+        task::{Context, Poll},
+    };
+
+    use tokio::io::AsyncWrite;
+
+    /// Writer that counts the calls made to it
+    ///
+    /// `vectored` makes it support vectored writes,
+    /// and `max_write` limits how many bytes each write accepts
+    #[derive(Default)]
+    pub struct CountingWriter {
+        pub buf: Vec<u8>,
+        pub writes: usize,
+        pub flushes: usize,
+        pub vectored: bool,
+        pub max_write: Option<usize>,
+    }
+
+    impl CountingWriter {
+        fn accept(&mut self, buf: &[u8]) -> usize {
+            let n = buf.len().min(self.max_write.unwrap_or(usize::MAX));
+            self.buf.extend_from_slice(&buf[..n]);
+            n
+        }
+    }
+
+    impl AsyncWrite for CountingWriter {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            self.writes += 1;
+            Poll::Ready(Ok(self.accept(buf)))
+        }
+
+        fn poll_write_vectored(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            bufs: &[io::IoSlice<'_>],
+        ) -> Poll<io::Result<usize>> {
+            self.writes += 1;
+            if !self.vectored {
+                let buf = bufs
+                    .iter()
+                    .find(|b| !b.is_empty())
+                    .map_or(&[][..], |b| &**b);
+                return Poll::Ready(Ok(self.accept(buf)));
+            }
+            let mut budget = self.max_write.unwrap_or(usize::MAX);
+            let mut written = 0;
+            for buf in bufs {
+                let n = buf.len().min(budget);
+                self.buf.extend_from_slice(&buf[..n]);
+                written += n;
+                budget -= n;
+                if n < buf.len() {
+                    break;
+                }
+            }
+            Poll::Ready(Ok(written))
+        }
+
+        fn is_write_vectored(&self) -> bool {
+            self.vectored
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            self.flushes += 1;
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use std::fmt::Display;
+
+use crate::message::headers::is_valid_token;
+
+/// A single transfer coding from the `Transfer-Encoding` header
+///
+/// Follows RFC 9112 Section 7
+///
+/// transfer-coding = token *( OWS ";" OWS transfer-parameter )
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransferCoding {
+    Chunked,
+    Gzip,
+    Deflate,
+    Compress,
+    /// Any other coding, or a known coding with parameters, stored as received
+    Other(String),
+}
+
+impl TransferCoding {
+    /// Parses one coding, the name is case-insensitive
+    ///
+    /// Returns None if the name is not a valid token
+    pub fn parse(value: &str) -> Option<TransferCoding> {
+        let value = value.trim();
+        let name = value.split(';').next().unwrap_or(value).trim_end();
+        if name.is_empty() || !is_valid_token(name.as_bytes()) {
+            return None;
+        }
+        // A coding with parameters is kept as is, so `chunked;x=y` is never taken as chunked
+        if name.len() != value.len() {
+            return Some(TransferCoding::Other(value.to_string()));
+        }
+
+        Some(match name.to_ascii_lowercase().as_str() {
+            "chunked" => TransferCoding::Chunked,
+            "gzip" | "x-gzip" => TransferCoding::Gzip,
+            "deflate" => TransferCoding::Deflate,
+            "compress" | "x-compress" => TransferCoding::Compress,
+            _ => TransferCoding::Other(value.to_string()),
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            TransferCoding::Chunked => "chunked",
+            TransferCoding::Gzip => "gzip",
+            TransferCoding::Deflate => "deflate",
+            TransferCoding::Compress => "compress",
+            TransferCoding::Other(coding) => coding,
+        }
+    }
+}
+
+impl Display for TransferCoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The chain of codings in `Transfer-Encoding`, in the order they were applied
+///
+/// Transfer-Encoding = #transfer-coding
+///
+/// The server decodes chunked, and the other codings only when they are in
+/// [`ServerConfig::codecs`](crate::server::ServerConfig::codecs). After a chunked body is read,
+/// chunked is removed from the header and the remaining codings are left for the handler,
+/// see [`Request::transfer_encoding`](crate::message::Request::transfer_encoding)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransferEncoding(Vec<TransferCoding>);
+
+impl TransferEncoding {
+    /// Parses the value of a `Transfer-Encoding` header
+    ///
+    /// Returns None if the list is empty, or any coding is not a valid token
+    pub fn parse(value: &str) -> Option<TransferEncoding> {
+        let codings = value
+            .split(',')
+            .map(str::trim)
+            // Empty list elements are allowed by RFC 9110 Section 5.6.1
+            .filter(|c| !c.is_empty())
+            .map(TransferCoding::parse)
+            .collect::<Option<Vec<_>>>()?;
+        if codings.is_empty() {
+            return None;
+        }
+        Some(TransferEncoding(codings))
+    }
+
+    /// The codings in the order they were applied, the last one is the outermost
+    pub fn codings(&self) -> &[TransferCoding] {
+        &self.0
+    }
+
+    /// Checks if the body is framed by chunked encoding
+    ///
+    /// RFC 9112 Section 6.1: chunked has to be the final coding, and must not be applied more than once
+    pub fn is_chunked(&self) -> bool {
+        match self.0.split_last() {
+            Some((TransferCoding::Chunked, rest)) => !rest.contains(&TransferCoding::Chunked),
+            _ => false,
+        }
+    }
+
+    /// Removes the final chunked coding once it is decoded,
+    /// returning the remaining chain or None if nothing is left
+    pub(crate) fn without_chunked(mut self) -> Option<TransferEncoding> {
+        if self.0.last() == Some(&TransferCoding::Chunked) {
+            self.0.pop();
+        }
+        (!self.0.is_empty()).then_some(self)
+    }
+}
+
+impl Display for TransferEncoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, coding) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{coding}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_parse() {
+        let te = TransferEncoding::parse("GZIP, chunked").unwrap();
+        assert_eq!(
+            te.codings(),
+            &[TransferCoding::Gzip, TransferCoding::Chunked]
+        );
+        assert!(te.is_chunked());
+        assert_eq!(te.to_string(), "gzip, chunked");
+
+        let te = TransferEncoding::parse("chunked;foo=bar").unwrap();
+        assert_eq!(
+            te.codings(),
+            &[TransferCoding::Other("chunked;foo=bar".to_string())]
+        );
+        assert!(!te.is_chunked());
+
+        assert_eq!(TransferEncoding::parse(""), None);
+        assert_eq!(TransferEncoding::parse(" , "), None);
+        assert_eq!(TransferEncoding::parse("gz ip, chunked"), None);
+    }
+
+    #[test]
+    fn test_is_chunked() {
+        for (value, chunked) in [
+            ("chunked", true),
+            ("gzip, chunked", true),
+            ("deflate, gzip, chunked", true),
+            ("chunked, gzip", false),
+            ("chunked, chunked", false),
+            ("gzip", false),
+            ("xchunked", false),
+        ] {
+            let te = TransferEncoding::parse(value).unwrap();
+            assert_eq!(te.is_chunked(), chunked, "{value}");
+        }
+    }
+
+    #[test]
+    fn test_without_chunked() {
+        let te = TransferEncoding::parse("gzip, chunked").unwrap();
+        assert_eq!(
+            te.without_chunked(),
+            Some(TransferEncoding(vec![TransferCoding::Gzip]))
+        );
+
+        let te = TransferEncoding::parse("chunked").unwrap();
+        assert_eq!(te.without_chunked(), None);
+    }
+}
@@ -0,0 +1,147 @@
+use std::{
+    fmt,
+    future::Future,
+    io,
+    pin::Pin,
+    sync::{Mutex, PoisonError},
+    task::{Context, Poll},
+};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// Future returned by an upgrade callback, see [`Response::on_upgrade`](crate::message::Response::on_upgrade)
+pub type UpgradeFuture<'a> = Pin<Box<dyn Future<Output = ()> + Send + 'a>>;
+
+/// The raw stream of a connection after it switched protocols
+///
+/// Bytes the client sent after the request, that were already read into the buffer,
+/// are returned first by reads
+pub struct Upgraded<'a> {
+    buffered: Vec<u8>,
+    pos: usize,
+    reader: Box<dyn AsyncRead + Unpin + Send + 'a>,
+    writer: Box<dyn AsyncWrite + Unpin + Send + 'a>,
+}
+
+impl<'a> Upgraded<'a> {
+    /// Creates the upgraded stream from the parts of a [`Connection`](crate::message::Connection),
+    /// see [`Connection::into_parts`](crate::message::Connection::into_parts)
+    pub fn new<R, W>(reader: R, buffered: Vec<u8>, writer: W) -> Upgraded<'a>
+    where
+        R: AsyncRead + Unpin + Send + 'a,
+        W: AsyncWrite + Unpin + Send + 'a,
+    {
+        Upgraded {
+            buffered,
+            pos: 0,
+            reader: Box::new(reader),
+            writer: Box::new(writer),
+        }
+    }
+}
+
+impl fmt::Debug for Upgraded<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Upgraded")
+            .field("buffered", &(self.buffered.len() - self.pos))
+            .finish_non_exhaustive()
+    }
+}
+
+impl AsyncRead for Upgraded<'_> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        if self.pos < self.buffered.len() {
+            let n = (self.buffered.len() - self.pos).min(buf.remaining());
+            buf.put_slice(&self.buffered[self.pos..self.pos + n]);
+            self.pos += n;
+            return Poll::Ready(Ok(()));
+        }
+        Pin::new(&mut self.reader).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for Upgraded<'_> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.writer).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.writer).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.writer).poll_shutdown(cx)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.writer).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.writer.is_write_vectored()
+    }
+}
+
+type UpgradeFn = Box<dyn for<'a> FnOnce(Upgraded<'a>) -> UpgradeFuture<'a> + Send>;
+
+/// Callback taking over the connection after the response is sent
+///
+/// Kept in a Mutex so a [`Response`](crate::message::Response) is Sync,
+/// and can be written through a shared reference
+pub(crate) struct OnUpgrade(Mutex<UpgradeFn>);
+
+impl OnUpgrade {
+    pub(crate) fn new<F>(f: F) -> OnUpgrade
+    where
+        F: for<'a> FnOnce(Upgraded<'a>) -> UpgradeFuture<'a> + Send + 'static,
+    {
+        OnUpgrade(Mutex::new(Box::new(f)))
+    }
+
+    pub(crate) fn call(self, upgraded: Upgraded<'_>) -> UpgradeFuture<'_> {
+        let f = self.0.into_inner().unwrap_or_else(PoisonError::into_inner);
+        f(upgraded)
+    }
+}
+
+impl fmt::Debug for OnUpgrade {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("OnUpgrade")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_upgraded_reads_buffered_first() -> io::Result<()> {
+        let reader = io::Cursor::new(b"world".to_vec());
+        let mut writer = Vec::new();
+        let mut upgraded = Upgraded::new(reader, b"hello ".to_vec(), &mut writer);
+
+        let mut read = String::new();
+        upgraded.read_to_string(&mut read).await?;
+        assert_eq!(read, "hello world");
+
+        upgraded.write_all(b"reply").await?;
+        drop(upgraded);
+        assert_eq!(writer, b"reply");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,407 @@
+use std::fmt::Display;
+
+/// A request-target parsed into its components, RFC 3986 Section 3
+///
+/// URI = scheme ":" [ "//" authority ] path [ "?" query ] [ "#" fragment ]
+///
+/// Origin-form targets, such as `/path?query`, have no scheme or authority.
+/// The scheme and host are lowercased, as they are case-insensitive.
+/// Percent-encoded octets are validated, but not decoded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Uri {
+    scheme: Option<String>,
+    userinfo: Option<String>,
+    host: Option<String>,
+    port: Option<u16>,
+    path: String,
+    query: Option<String>,
+    fragment: Option<String>,
+}
+
+/// sub-delims = "!" / "$" / "&" / "'" / "(" / ")" / "*" / "+" / "," / ";" / "="
+const SUB_DELIMS: &[u8] = b"!$&'()*+,;=";
+
+/// Checks that value only has unreserved, pct-encoded, sub-delims and the extra characters
+///
+/// unreserved = ALPHA / DIGIT / "-" / "." / "_" / "~"
+/// pct-encoded = "%" HEXDIG HEXDIG
+fn is_valid_component(value: &str, extra: &[u8]) -> bool {
+    let bytes = value.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let valid = bytes.get(i + 1).is_some_and(u8::is_ascii_hexdigit)
+                    && bytes.get(i + 2).is_some_and(u8::is_ascii_hexdigit);
+                if !valid {
+                    return false;
+                }
+                i += 3;
+            }
+            b if b.is_ascii_alphanumeric()
+                || b"-._~".contains(&b)
+                || SUB_DELIMS.contains(&b)
+                || extra.contains(&b) =>
+            {
+                i += 1;
+            }
+            _ => return false,
+        }
+    }
+    true
+}
+
+/// Length of the scheme of an absolute-URI, if target starts with one
+///
+/// scheme = ALPHA *( ALPHA / DIGIT / "+" / "-" / "." )
+pub(crate) fn scheme_len(target: &str) -> Option<usize> {
+    let (scheme, _) = target.split_once(':')?;
+    let mut chars = scheme.chars();
+    let first = chars.next()?;
+    let valid = first.is_ascii_alphabetic()
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    valid.then_some(scheme.len())
+}
+
+/// Parses Host = uri-host [ ":" port ], RFC 9110 Section 7.2
+///
+/// uri-host is an IP-literal in brackets, or a reg-name of unreserved, pct-encoded
+/// and sub-delims characters, which also covers IPv4 addresses
+pub(crate) fn parse_host(value: &str) -> Option<(String, Option<u16>)> {
+    let (host, port) = match value.strip_prefix('[') {
+        Some(rest) => {
+            let (literal, port) = rest.split_once(']')?;
+            let valid = !literal.is_empty()
+                && literal
+                    .bytes()
+                    .all(|b| b.is_ascii_hexdigit() || matches!(b, b':' | b'.'));
+            if !valid {
+                return None;
+            }
+            (&value[..literal.len() + 2], port)
+        }
+        None => {
+            let end = value.find(':').unwrap_or(value.len());
+            (&value[..end], &value[end..])
+        }
+    };
+
+    let valid_host = !host.is_empty()
+        && (host.starts_with('[')
+            || host
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b"-._~%!$&'()*+,;=".contains(&b)));
+    if !valid_host {
+        return None;
+    }
+
+    let port = match port.strip_prefix(':') {
+        // An empty port is allowed, and means the default port
+        Some("") => None,
+        Some(port) if port.bytes().all(|b| b.is_ascii_digit()) => Some(port.parse().ok()?),
+        Some(_) => return None,
+        None if port.is_empty() => None,
+        None => return None,
+    };
+    Some((host.to_ascii_lowercase(), port))
+}
+
+/// Normalizes a path for matching, such as `/a//b/./c/../d` to `/a/b/d`
+///
+/// Empty segments from duplicate slashes are removed, and `.` and `..` segments are resolved
+/// as in RFC 3986 Section 5.2.4, also when their dots are percent-encoded as `%2E`.
+/// `..` never climbs above the root, so `/../etc` is `/etc`.
+/// A trailing slash is kept, and a path ending in a dot segment ends with a slash
+pub fn normalize_path(path: &str) -> String {
+    let mut segments = Vec::new();
+    let mut trailing_slash = false;
+    for segment in path.split('/') {
+        match segment.to_ascii_lowercase().replace("%2e", ".").as_str() {
+            "" | "." => trailing_slash = true,
+            ".." => {
+                segments.pop();
+                trailing_slash = true;
+            }
+            _ => {
+                segments.push(segment);
+                trailing_slash = false;
+            }
+        }
+    }
+
+    let mut normalized = String::with_capacity(path.len() + 1);
+    for segment in &segments {
+        normalized.push('/');
+        normalized.push_str(segment);
+    }
+    if trailing_slash || segments.is_empty() {
+        normalized.push('/');
+    }
+    normalized
+}
+
+impl Uri {
+    /// Parses an absolute-URI, such as `http://host:8080/path?query#fragment`,
+    /// or a path starting with `/` as in origin-form, such as `/path?query`
+    ///
+    /// Returns None if value is neither, or a component has characters the grammar does not allow.
+    /// A fragment is accepted, even though clients should not send it in a request-target
+    pub fn parse(value: &str) -> Option<Uri> {
+        let (rest, fragment) = match value.split_once('#') {
+            Some((rest, fragment)) => (rest, Some(fragment)),
+            None => (value, None),
+        };
+        let (rest, query) = match rest.split_once('?') {
+            Some((rest, query)) => (rest, Some(query)),
+            None => (rest, None),
+        };
+
+        let mut uri = Uri {
+            scheme: None,
+            userinfo: None,
+            host: None,
+            port: None,
+            path: String::new(),
+            query: query.map(str::to_string),
+            fragment: fragment.map(str::to_string),
+        };
+
+        let path = match scheme_len(rest) {
+            Some(len) => {
+                uri.scheme = Some(rest[..len].to_ascii_lowercase());
+                let rest = &rest[len + 1..];
+                match rest.strip_prefix("//") {
+                    Some(rest) => {
+                        let end = rest.find('/').unwrap_or(rest.len());
+                        uri.parse_authority(&rest[..end])?;
+                        &rest[end..]
+                    }
+                    None => rest,
+                }
+            }
+            None if rest.starts_with('/') => rest,
+            None => return None,
+        };
+
+        // pchar = unreserved / pct-encoded / sub-delims / ":" / "@"
+        // query = fragment = *( pchar / "/" / "?" )
+        let valid = is_valid_component(path, b":@/")
+            && query.is_none_or(|q| is_valid_component(q, b":@/?"))
+            && fragment.is_none_or(|f| is_valid_component(f, b":@/?"));
+        if !valid {
+            return None;
+        }
+        uri.path = path.to_string();
+        Some(uri)
+    }
+
+    /// authority = [ userinfo "@" ] host [ ":" port ]
+    fn parse_authority(&mut self, authority: &str) -> Option<()> {
+        let host = match authority.rsplit_once('@') {
+            Some((userinfo, host)) => {
+                if !is_valid_component(userinfo, b":") {
+                    return None;
+                }
+                self.userinfo = Some(userinfo.to_string());
+                host
+            }
+            None => authority,
+        };
+        let (host, port) = parse_host(host)?;
+        self.host = Some(host);
+        self.port = port;
+        Some(())
+    }
+
+    /// The scheme, such as `http`, None in origin-form
+    pub fn scheme(&self) -> Option<&str> {
+        self.scheme.as_deref()
+    }
+
+    /// The user information before the host, such as `user:password`
+    pub fn userinfo(&self) -> Option<&str> {
+        self.userinfo.as_deref()
+    }
+
+    /// The host, with brackets for IP-literals such as `[::1]`, None without an authority
+    pub fn host(&self) -> Option<&str> {
+        self.host.as_deref()
+    }
+
+    /// The port after the host, None if it is not given
+    pub fn port(&self) -> Option<u16> {
+        self.port
+    }
+
+    /// The path, which can be empty for absolute-URIs such as `http://host`
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// The query after `?`, without the `?`
+    pub fn query(&self) -> Option<&str> {
+        self.query.as_deref()
+    }
+
+    /// The fragment after `#`, without the `#`
+    pub fn fragment(&self) -> Option<&str> {
+        self.fragment.as_deref()
+    }
+}
+
+impl Display for Uri {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(scheme) = &self.scheme {
+            write!(f, "{scheme}:")?;
+        }
+        if let Some(host) = &self.host {
+            f.write_str("//")?;
+            if let Some(userinfo) = &self.userinfo {
+                write!(f, "{userinfo}@")?;
+            }
+            f.write_str(host)?;
+            if let Some(port) = self.port {
+                write!(f, ":{port}")?;
+            }
+        }
+        f.write_str(&self.path)?;
+        if let Some(query) = &self.query {
+            write!(f, "?{query}")?;
+        }
+        if let Some(fragment) = &self.fragment {
+            write!(f, "#{fragment}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_parse_absolute() {
+        let uri = Uri::parse("http://h:8080/p?q#f").unwrap();
+        assert_eq!(uri.scheme(), Some("http"));
+        assert_eq!(uri.userinfo(), None);
+        assert_eq!(uri.host(), Some("h"));
+        assert_eq!(uri.port(), Some(8080));
+        assert_eq!(uri.path(), "/p");
+        assert_eq!(uri.query(), Some("q"));
+        assert_eq!(uri.fragment(), Some("f"));
+        assert_eq!(uri.to_string(), "http://h:8080/p?q#f");
+
+        let uri = Uri::parse("HTTPS://user:pw@Example.COM").unwrap();
+        assert_eq!(uri.scheme(), Some("https"));
+        assert_eq!(uri.userinfo(), Some("user:pw"));
+        assert_eq!(uri.host(), Some("example.com"));
+        assert_eq!(uri.port(), None);
+        assert_eq!(uri.path(), "");
+        assert_eq!(uri.query(), None);
+
+        let uri = Uri::parse("http://[::1]:80/a/b?x=1?y=2").unwrap();
+        assert_eq!(uri.host(), Some("[::1]"));
+        assert_eq!(uri.port(), Some(80));
+        assert_eq!(uri.path(), "/a/b");
+        assert_eq!(uri.query(), Some("x=1?y=2"));
+
+        // Without an authority the rest is the path
+        let uri = Uri::parse("urn:isbn:0451450523").unwrap();
+        assert_eq!(uri.scheme(), Some("urn"));
+        assert_eq!(uri.host(), None);
+        assert_eq!(uri.path(), "isbn:0451450523");
+    }
+
+    #[test]
+    fn test_parse_origin() {
+        let uri = Uri::parse("/p?q").unwrap();
+        assert_eq!(uri.scheme(), None);
+        assert_eq!(uri.host(), None);
+        assert_eq!(uri.path(), "/p");
+        assert_eq!(uri.query(), Some("q"));
+        assert_eq!(uri.fragment(), None);
+        assert_eq!(uri.to_string(), "/p?q");
+
+        let uri = Uri::parse("/a%20b/c:d@e?").unwrap();
+        assert_eq!(uri.path(), "/a%20b/c:d@e");
+        assert_eq!(uri.query(), Some(""));
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        for invalid in [
+            "",
+            "p?q",
+            "1http://h/",
+            "http://h:port/",
+            "http://h:99999/",
+            "http://:80/",
+            "http://[::1/",
+            "http://us er@h/",
+            "/a b",
+            "/a%2",
+            "/a%zz",
+            "/a?b c",
+            "/a#b#c",
+            "/a\"b",
+        ] {
+            assert_eq!(Uri::parse(invalid), None, "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_parse_host() {
+        let host = |h: &str, port| Some((h.to_string(), port));
+        assert_eq!(
+            parse_host("localhost:42069"),
+            host("localhost", Some(42069))
+        );
+        assert_eq!(parse_host("127.0.0.1"), host("127.0.0.1", None));
+        assert_eq!(parse_host("[::1]:80"), host("[::1]", Some(80)));
+        assert_eq!(parse_host("[::1]"), host("[::1]", None));
+        assert_eq!(parse_host("example.com:"), host("example.com", None));
+
+        for invalid in [
+            "",
+            ":80",
+            "a:b:c",
+            "example.com:80:80",
+            "example.com:http",
+            "example.com:99999",
+            "exa mple.com",
+            "example.com/path",
+            "user@example.com",
+            "[::1",
+            "[::1]80",
+            "[]",
+        ] {
+            assert_eq!(parse_host(invalid), None, "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_normalize_path() {
+        for (path, expected) in [
+            ("/", "/"),
+            ("", "/"),
+            ("/a/b", "/a/b"),
+            ("/a//b", "/a/b"),
+            ("/a//b/", "/a/b/"),
+            ("//a///b//", "/a/b/"),
+            ("/a/../b", "/b"),
+            ("/a/./b", "/a/b"),
+            ("/a/b/..", "/a/"),
+            ("/a/b/.", "/a/b/"),
+            ("/../etc", "/etc"),
+            ("/../../etc/passwd", "/etc/passwd"),
+            ("/a/../../..", "/"),
+            ("/a/%2e%2E/b", "/b"),
+            ("/a/.%2e/../b", "/b"),
+            ("/a/..b/c.", "/a/..b/c."),
+            ("a/b", "/a/b"),
+        ] {
+            assert_eq!(normalize_path(path), expected, "{path}");
+        }
+    }
+}
@@ -2,16 +2,19 @@ use std::fmt::Display;
 
 use crate::message::error::VersionError;
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct HttpVersion(u8, u8);
 
 impl HttpVersion {
+    /// Parses the DIGIT "." DIGIT part of HTTP-version, RFC 9112 Section 2.3
+    ///
+    /// Any version with this form is parsed, so it is up to the caller to reject versions it
+    /// does not support
     pub fn from_bytes(bytes: &[u8]) -> Result<Self, VersionError> {
         match bytes {
-            b"1.0" => Ok(Self(1, 0)),
-            b"1.1" => Ok(Self(1, 1)),
-            b"2.0" => Ok(Self(2, 0)),
-            b"3.0" => Ok(Self(3, 0)),
+            [major, b'.', minor] if major.is_ascii_digit() && minor.is_ascii_digit() => {
+                Ok(Self(major - b'0', minor - b'0'))
+            }
             _ => Err(VersionError::InvalidHTTPVersion),
         }
     }
@@ -19,6 +22,31 @@ impl HttpVersion {
     pub fn new(major: u8, minor: u8) -> Self {
         Self(major, minor)
     }
+
+    pub fn major(&self) -> u8 {
+        self.0
+    }
+
+    pub fn minor(&self) -> u8 {
+        self.1
+    }
+
+    pub fn is_http_1_0(&self) -> bool {
+        *self == (1, 0)
+    }
+
+    /// Returns true if this version is major.minor or later
+    pub fn is_at_least(&self, major: u8, minor: u8) -> bool {
+        *self >= Self(major, minor)
+    }
+
+    /// Returns true if connections stay open unless `Connection: close` is sent
+    ///
+    /// HTTP/1.0 connections close after each response unless `Connection: keep-alive` is sent,
+    /// RFC 9112 Section 9.3
+    pub fn keep_alive_by_default(&self) -> bool {
+        self.is_at_least(1, 1)
+    }
 }
 
 impl Default for HttpVersion {
@@ -60,3 +88,37 @@ impl Display for HttpVersion {
         write!(f, "{}.{}", self.0, self.1)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_bytes() {
+        assert_eq!(HttpVersion::from_bytes(b"1.1").unwrap(), (1, 1));
+        assert_eq!(HttpVersion::from_bytes(b"9.9").unwrap(), (9, 9));
+        for input in [&b"1"[..], b"1.", b"11.1", b"1,1", b"a.b", b""] {
+            assert!(HttpVersion::from_bytes(input).is_err());
+        }
+    }
+
+    #[test]
+    fn test_is_at_least() {
+        let v = HttpVersion::new(1, 1);
+        assert!(v.is_at_least(1, 0));
+        assert!(v.is_at_least(1, 1));
+        assert!(!v.is_at_least(2, 0));
+        assert!(HttpVersion::new(2, 0).is_at_least(1, 1));
+        assert!(!HttpVersion::new(1, 0).is_at_least(1, 1));
+    }
+
+    #[test]
+    fn test_keep_alive_by_default() {
+        assert!(HttpVersion::new(1, 0).is_http_1_0());
+        assert!(!HttpVersion::new(1, 1).is_http_1_0());
+
+        assert!(!HttpVersion::new(1, 0).keep_alive_by_default());
+        assert!(HttpVersion::new(1, 1).keep_alive_by_default());
+        assert!(HttpVersion::new(2, 0).keep_alive_by_default());
+    }
+}
@@ -0,0 +1,114 @@
+use crate::message::{Method, Request, Response, StatusCode, base64, sha1};
+
+/// Appended to the key before hashing, RFC 6455 Section 1.3
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Returns true if any comma separated token of the field equals token, ignoring case
+fn has_token(req: &Request, name: &str, token: &str) -> bool {
+    req.headers.get(name).is_some_and(|v| {
+        v.split(',')
+            .any(|part| part.trim().eq_ignore_ascii_case(token))
+    })
+}
+
+/// Computes `Sec-WebSocket-Accept` from `Sec-WebSocket-Key`
+fn accept_key(key: &str) -> String {
+    base64::encode(&sha1::digest(format!("{key}{WEBSOCKET_GUID}").as_bytes()))
+}
+
+impl Request {
+    /// Returns true if this is a WebSocket opening handshake, RFC 6455 Section 4.2.1
+    ///
+    /// That is a HTTP/1.1 GET with `Upgrade: websocket`, `Connection: Upgrade`,
+    /// `Sec-WebSocket-Version: 13` and a `Sec-WebSocket-Key` of 16 base64 encoded bytes
+    pub fn is_websocket_upgrade(&self) -> bool {
+        self.line.method == Method::Get
+            && self.line.version == (1, 1)
+            && has_token(self, "Upgrade", "websocket")
+            && has_token(self, "Connection", "upgrade")
+            && self
+                .headers
+                .get("Sec-WebSocket-Version")
+                .is_some_and(|v| v.trim() == "13")
+            && self
+                .headers
+                .get("Sec-WebSocket-Key")
+                .and_then(|key| base64::decode(key.trim().as_bytes()))
+                .is_some_and(|key| key.len() == 16)
+    }
+}
+
+impl Response {
+    /// Creates the `101 Switching Protocols` response accepting a WebSocket handshake
+    ///
+    /// Set a callback with [`Response::on_upgrade`] to get the stream after the handshake.
+    /// Framing is left to the callback.
+    /// Returns None if the request is not a WebSocket handshake, see [`Request::is_websocket_upgrade`]
+    pub fn websocket_accept(req: &Request) -> Option<Response> {
+        if !req.is_websocket_upgrade() {
+            return None;
+        }
+        let key = req.headers.get("Sec-WebSocket-Key")?.trim();
+
+        let mut response = Response::new(StatusCode::SwitchingProtocols);
+        response.headers.set("Upgrade", "websocket");
+        response.headers.set("Connection", "Upgrade");
+        response
+            .headers
+            .set("Sec-WebSocket-Accept", accept_key(key));
+        Some(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::RequestBuilder;
+    use pretty_assertions::assert_eq;
+
+    fn handshake() -> RequestBuilder {
+        RequestBuilder::new(Method::Get, "/chat")
+            .header("Host", "server.example.com")
+            .header("Upgrade", "websocket")
+            .header("Connection", "keep-alive, Upgrade")
+            .header("Sec-WebSocket-Key", "dGhlIHNhbXBsZSBub25jZQ==")
+            .header("Sec-WebSocket-Version", "13")
+    }
+
+    #[test]
+    fn test_accept_key() {
+        // Example from RFC 6455 Section 1.3
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[test]
+    fn test_websocket_accept() {
+        let response = Response::websocket_accept(&handshake().build()).unwrap();
+        assert_eq!(
+            response.status_line.status_code,
+            StatusCode::SwitchingProtocols
+        );
+        assert_eq!(
+            response.headers.get("Sec-WebSocket-Accept"),
+            Some(&"s3pPLMBiTxaQ9kYGzzhZRbK+xOo=".to_string())
+        );
+        assert_eq!(
+            response.headers.get("Upgrade"),
+            Some(&"websocket".to_string())
+        );
+
+        let mut req = handshake().build();
+        req.headers.set("Sec-WebSocket-Version", "8");
+        assert!(Response::websocket_accept(&req).is_none());
+
+        let mut req = handshake().build();
+        req.headers.set("Sec-WebSocket-Key", "c2hvcnQ=");
+        assert!(Response::websocket_accept(&req).is_none());
+
+        let req = RequestBuilder::new(Method::Get, "/chat").build();
+        assert!(Response::websocket_accept(&req).is_none());
+    }
+}
@@ -0,0 +1,144 @@
+use std::io::{self, IoSlice};
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Bodies smaller than this are copied after the head,
+/// since a single copy is cheaper than a vectored write
+const VECTORED_MIN_BODY: usize = 1024;
+
+/// The most that is read from a body of unknown length for one chunk
+const MAX_CHUNK_SIZE: usize = 16 * 1024;
+
+/// Writes the serialized head followed by the body
+///
+/// Large bodies are written with vectored writes, so they are not copied into the head buffer.
+/// When the writer does not support vectored writes efficiently, or the body is small,
+/// the body is appended to the head and written with a single write
+///
+/// # Errors
+///
+/// Returns an error if the writer fails, or stops accepting bytes
+pub(crate) async fn write_message<W>(w: &mut W, mut head: Vec<u8>, body: &[u8]) -> io::Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    if body.len() < VECTORED_MIN_BODY || !w.is_write_vectored() {
+        head.extend_from_slice(body);
+        return w.write_all(&head).await;
+    }
+
+    let total = head.len() + body.len();
+    let mut written = 0;
+    while written < total {
+        let n = if written < head.len() {
+            let slices = [IoSlice::new(&head[written..]), IoSlice::new(body)];
+            w.write_vectored(&slices).await?
+        } else {
+            w.write(&body[written - head.len()..]).await?
+        };
+        if n == 0 {
+            return Err(io::ErrorKind::WriteZero.into());
+        }
+        written += n;
+    }
+    Ok(())
+}
+
+/// Appends data as one chunk of a chunked body, RFC 9112 Section 7.1
+///
+/// The size line, data and CRLF are put in one buffer, so each chunk is sent with one write.
+/// Empty data would be the last chunk, so it has to be skipped by the caller
+pub(crate) fn encode_chunk(out: &mut Vec<u8>, data: &[u8]) {
+    out.extend_from_slice(format!("{:x}\r\n", data.len()).as_bytes());
+    out.extend_from_slice(data);
+    out.extend_from_slice(b"\r\n");
+}
+
+/// Writes what is read from r as a chunked body, RFC 9112 Section 7.1,
+/// ending with the last chunk when r is exhausted
+///
+/// # Errors
+///
+/// Returns an error if r or the writer fails
+pub(crate) async fn write_chunked<R, W>(r: &mut R, w: &mut W) -> io::Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut buf = vec![0u8; MAX_CHUNK_SIZE];
+    let mut chunk = Vec::with_capacity(MAX_CHUNK_SIZE + 16);
+    loop {
+        let n = r.read(&mut buf).await?;
+        if n == 0 {
+            return w.write_all(b"0\r\n\r\n").await;
+        }
+        chunk.clear();
+        encode_chunk(&mut chunk, &buf[..n]);
+        w.write_all(&chunk).await?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::test_utils::counting_writer::CountingWriter;
+    use pretty_assertions::assert_eq;
+
+    #[tokio::test]
+    async fn test_write_message_vectored() -> io::Result<()> {
+        let body = vec![b'a'; VECTORED_MIN_BODY * 4];
+        let mut writer = CountingWriter {
+            vectored: true,
+            ..Default::default()
+        };
+        write_message(&mut writer, b"head".to_vec(), &body).await?;
+
+        assert_eq!(writer.writes, 1);
+        assert_eq!(&writer.buf[..4], b"head");
+        assert_eq!(&writer.buf[4..], &body[..]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_write_message_partial_vectored() -> io::Result<()> {
+        let body = vec![b'a'; VECTORED_MIN_BODY * 4];
+        let mut writer = CountingWriter {
+            vectored: true,
+            max_write: Some(3),
+            ..Default::default()
+        };
+        write_message(&mut writer, b"head".to_vec(), &body).await?;
+
+        assert_eq!(&writer.buf[..4], b"head");
+        assert_eq!(&writer.buf[4..], &body[..]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_write_message_not_vectored() -> io::Result<()> {
+        let body = vec![b'a'; VECTORED_MIN_BODY * 4];
+        let mut writer = CountingWriter::default();
+        write_message(&mut writer, b"head".to_vec(), &body).await?;
+
+        assert_eq!(writer.writes, 1);
+        assert_eq!(writer.buf.len(), 4 + body.len());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_write_chunked() -> io::Result<()> {
+        let mut reader = (&b"hello"[..]).chain(&b" world"[..]);
+        let mut w = Vec::new();
+        write_chunked(&mut reader, &mut w).await?;
+        assert_eq!(w, b"5\r\nhello\r\n6\r\n world\r\n0\r\n\r\n");
+
+        let mut w = Vec::new();
+        write_chunked(&mut &b""[..], &mut w).await?;
+        assert_eq!(w, b"0\r\n\r\n");
+
+        Ok(())
+    }
+}
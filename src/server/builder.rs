@@ -0,0 +1,281 @@
+use std::{io, sync::Arc, time::Duration};
+
+use bytes::Bytes;
+use tokio::runtime::Handle;
+
+use crate::{
+    message::{Codecs, RequestError, Response, StatusCode},
+    server::{ErrorHandler, ErrorPage, Handler, Listener, Metrics, Server, ServerConfig, bind},
+};
+
+/// Builds a [`Server`], created with [`Server::builder`]
+///
+/// Options not set keep the values of [`ServerConfig::default`]
+pub struct ServerBuilder {
+    addr: Option<String>,
+    handler: Option<Arc<dyn Handler>>,
+    config: ServerConfig,
+}
+
+impl ServerBuilder {
+    pub(crate) fn new() -> ServerBuilder {
+        ServerBuilder {
+            addr: None,
+            handler: None,
+            config: ServerConfig::default(),
+        }
+    }
+
+    /// Sets the address to listen on, such as `localhost:42069`
+    pub fn addr(mut self, addr: impl Into<String>) -> Self {
+        self.addr = Some(addr.into());
+        self
+    }
+
+    /// Sets the handler called for every request
+    pub fn handler(mut self, handler: impl Handler) -> Self {
+        self.handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// Replaces the whole configuration, options set before are overwritten
+    pub fn config(mut self, config: ServerConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// See [`ServerConfig::read_body_to_eof`]
+    pub fn read_body_to_eof(mut self, read_body_to_eof: bool) -> Self {
+        self.config.read_body_to_eof = read_body_to_eof;
+        self
+    }
+
+    /// See [`ServerConfig::max_body_size`]
+    pub fn max_body_size(mut self, max_body_size: usize) -> Self {
+        self.config.max_body_size = Some(max_body_size);
+        self
+    }
+
+    /// See [`ServerConfig::max_request_line_size`]
+    pub fn max_request_line_size(mut self, max_request_line_size: usize) -> Self {
+        self.config.max_request_line_size = Some(max_request_line_size);
+        self
+    }
+
+    /// See [`ServerConfig::max_header_size`]
+    pub fn max_header_size(mut self, max_header_size: usize) -> Self {
+        self.config.max_header_size = Some(max_header_size);
+        self
+    }
+
+    /// See [`ServerConfig::stream_chunked_bodies`]
+    pub fn stream_chunked_bodies(mut self, stream_chunked_bodies: bool) -> Self {
+        self.config.stream_chunked_bodies = stream_chunked_bodies;
+        self
+    }
+
+    /// See [`ServerConfig::codecs`]
+    pub fn codecs(mut self, codecs: Codecs) -> Self {
+        self.config.codecs = Some(Arc::new(codecs));
+        self
+    }
+
+    /// See [`ServerConfig::handler_timeout`]
+    pub fn handler_timeout(mut self, handler_timeout: Duration) -> Self {
+        self.config.handler_timeout = Some(handler_timeout);
+        self
+    }
+
+    /// See [`ServerConfig::read_timeout`]
+    pub fn read_timeout(mut self, read_timeout: Duration) -> Self {
+        self.config.read_timeout = Some(read_timeout);
+        self
+    }
+
+    /// See [`ServerConfig::keep_alive`]
+    pub fn keep_alive(mut self, keep_alive: bool) -> Self {
+        self.config.keep_alive = keep_alive;
+        self
+    }
+
+    /// See [`ServerConfig::max_keep_alive_requests`]
+    pub fn max_keep_alive_requests(mut self, max_keep_alive_requests: usize) -> Self {
+        self.config.max_keep_alive_requests = Some(max_keep_alive_requests);
+        self
+    }
+
+    /// See [`ServerConfig::max_connections`]
+    pub fn max_connections(mut self, max_connections: usize) -> Self {
+        self.config.max_connections = Some(max_connections);
+        self
+    }
+
+    /// See [`ServerConfig::runtime`]
+    pub fn runtime(mut self, runtime: Handle) -> Self {
+        self.config.runtime = Some(runtime);
+        self
+    }
+
+    /// See [`ServerConfig::error_handler`]
+    ///
+    /// ```ignore
+    /// let server = Server::builder()
+    ///     .error_handler(|e| match e {
+    ///         RequestError::Timeout => Some(Response::new(StatusCode::RequestTimeout)),
+    ///         _ => None,
+    ///     })
+    /// ```
+    pub fn error_handler<F>(mut self, error_handler: F) -> Self
+    where
+        F: Fn(&RequestError) -> Option<Response> + Send + Sync + 'static,
+    {
+        self.config.error_handler = Some(ErrorHandler::new(error_handler));
+        self
+    }
+
+    /// See [`ServerConfig::error_bodies`]
+    pub fn error_bodies(mut self, error_bodies: bool) -> Self {
+        self.config.error_bodies = error_bodies;
+        self
+    }
+
+    /// See [`ServerConfig::debug_errors`]
+    pub fn debug_errors(mut self, debug_errors: bool) -> Self {
+        self.config.debug_errors = debug_errors;
+        self
+    }
+
+    /// Sets the body of error responses with the status code, see [`ServerConfig::error_pages`]
+    ///
+    /// Will overwrite an earlier page for the same status code
+    pub fn error_page(
+        mut self,
+        status_code: StatusCode,
+        body: impl Into<Bytes>,
+        content_type: impl Into<String>,
+    ) -> Self {
+        set_error_page(
+            &mut self.config,
+            ErrorPage::new(status_code, body, content_type),
+        );
+        self
+    }
+
+    /// See [`ServerConfig::tcp_nodelay`]
+    pub fn tcp_nodelay(mut self, tcp_nodelay: bool) -> Self {
+        self.config.tcp_nodelay = tcp_nodelay;
+        self
+    }
+
+    /// See [`ServerConfig::listen_backlog`]
+    pub fn listen_backlog(mut self, listen_backlog: u32) -> Self {
+        self.config.listen_backlog = Some(listen_backlog);
+        self
+    }
+
+    /// See [`ServerConfig::reuse_address`]
+    pub fn reuse_address(mut self, reuse_address: bool) -> Self {
+        self.config.reuse_address = reuse_address;
+        self
+    }
+
+    /// See [`ServerConfig::metrics`]
+    pub fn metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.config.metrics = Some(metrics);
+        self
+    }
+
+    /// Binds to the address with the socket options of the config and creates the server
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if it can't bind to the address
+    ///
+    /// # Panics
+    ///
+    /// Panics if the address or the handler is not set
+    pub async fn build(self) -> io::Result<Server> {
+        let addr = self.addr.expect("ServerBuilder needs an addr");
+        let handler = self.handler.expect("ServerBuilder needs a handler");
+        let listener = bind(&addr, &self.config).await?;
+        Ok(Server {
+            handler,
+            config: self.config,
+            _addr: addr,
+            listener: Listener::Tcp(listener),
+        })
+    }
+}
+
+/// Adds the page to the config, replacing the page for the same status code
+pub(crate) fn set_error_page(config: &mut ServerConfig, page: ErrorPage) {
+    config
+        .error_pages
+        .retain(|p| p.status_code != page.status_code);
+    config.error_pages.push(page);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::message::{Request, Response, StatusCode};
+    use crate::server::{Server, ServerError};
+
+    fn ok(_: &Request) -> Result<Response, ServerError> {
+        Ok(Response::new(StatusCode::Ok))
+    }
+
+    #[tokio::test]
+    async fn test_builder() {
+        let server = Server::builder()
+            .addr("127.0.0.1:0")
+            .handler(ok)
+            .max_body_size(16)
+            .max_request_line_size(64)
+            .max_header_size(32)
+            .stream_chunked_bodies(true)
+            .read_timeout(Duration::from_secs(5))
+            .keep_alive(false)
+            .max_keep_alive_requests(100)
+            .max_connections(2)
+            .tcp_nodelay(false)
+            .listen_backlog(16)
+            .reuse_address(false)
+            .error_handler(|_| None)
+            .error_bodies(false)
+            .debug_errors(true)
+            .error_page(StatusCode::NotFound, "<h1>Not Found</h1>", "text/plain")
+            .error_page(StatusCode::NotFound, "<h1>Not Found</h1>", "text/html")
+            .build()
+            .await
+            .unwrap();
+
+        assert_eq!(server.config.max_body_size, Some(16));
+        assert_eq!(server.config.max_request_line_size, Some(64));
+        assert_eq!(server.config.max_header_size, Some(32));
+        assert!(server.config.stream_chunked_bodies);
+        assert_eq!(server.config.read_timeout, Some(Duration::from_secs(5)));
+        assert!(!server.config.keep_alive);
+        assert_eq!(server.config.max_keep_alive_requests, Some(100));
+        assert_eq!(server.config.max_connections, Some(2));
+        assert!(!server.config.tcp_nodelay);
+        assert_eq!(server.config.listen_backlog, Some(16));
+        assert!(!server.config.reuse_address);
+        assert!(server.config.error_handler.is_some());
+        assert!(!server.config.error_bodies);
+        assert!(server.config.debug_errors);
+        assert_eq!(server.config.error_pages.len(), 1);
+        assert_eq!(server.config.error_pages[0].content_type, "text/html");
+    }
+
+    #[tokio::test]
+    async fn test_builder_bind_error() {
+        let res = Server::builder()
+            .addr("not an address")
+            .handler(ok)
+            .build()
+            .await;
+        assert!(res.is_err());
+    }
+}
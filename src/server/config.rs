@@ -0,0 +1,231 @@
+use std::{fmt, sync::Arc, time::Duration};
+
+use tokio::runtime::Handle;
+
+use bytes::Bytes;
+
+use crate::message::{Codecs, DEFAULT_BUFFER_SIZE, RequestError, Response, StatusCode};
+use crate::server::Metrics;
+
+/// Body sent with error responses of a status code, see [`ServerConfig::error_pages`]
+#[derive(Debug, Clone)]
+pub struct ErrorPage {
+    pub status_code: StatusCode,
+    pub body: Bytes,
+    pub content_type: String,
+}
+
+impl ErrorPage {
+    pub fn new(
+        status_code: StatusCode,
+        body: impl Into<Bytes>,
+        content_type: impl Into<String>,
+    ) -> ErrorPage {
+        ErrorPage {
+            status_code,
+            body: body.into(),
+            content_type: content_type.into(),
+        }
+    }
+
+    /// Fills the body of response with the page, if it has the status code and an empty body
+    pub(crate) fn apply(pages: &[ErrorPage], response: &mut Response) {
+        if !response.body.is_empty() {
+            return;
+        }
+        let status_code = response.status_line.status_code;
+        if let Some(page) = pages.iter().find(|p| p.status_code == status_code) {
+            response.body = page.body.clone();
+            response
+                .headers
+                .set("Content-Type", page.content_type.as_str());
+        }
+    }
+}
+
+type ErrorHandlerFn = dyn Fn(&RequestError) -> Option<Response> + Send + Sync;
+
+/// Maps an error reading a request to the response sent before the connection is closed,
+/// see [`ServerConfig::error_handler`]
+#[derive(Clone)]
+pub struct ErrorHandler(Arc<ErrorHandlerFn>);
+
+impl ErrorHandler {
+    pub fn new<F>(f: F) -> ErrorHandler
+    where
+        F: Fn(&RequestError) -> Option<Response> + Send + Sync + 'static,
+    {
+        ErrorHandler(Arc::new(f))
+    }
+
+    pub(crate) fn call(&self, error: &RequestError) -> Option<Response> {
+        (self.0)(error)
+    }
+}
+
+impl fmt::Debug for ErrorHandler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ErrorHandler")
+    }
+}
+
+/// Configuration for a [`Server`](crate::server::Server)
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    /// Size in bytes of the read buffer for each connection
+    pub buffer_size: usize,
+    /// Answer `TRACE` requests by echoing the request back, instead of calling the handler
+    ///
+    /// Off by default, as echoing requests can leak headers to scripts (cross-site tracing).
+    /// Credentials and cookies are never echoed
+    pub trace: bool,
+    /// Accept a bare LF as a line ending in the request head, not only CRLF
+    ///
+    /// Off by default
+    pub lenient_line_endings: bool,
+    /// Unfold header values continued on the next line (obs-fold), instead of rejecting them
+    ///
+    /// Off by default, as obs-fold is deprecated by RFC 9112
+    pub lenient_obs_fold: bool,
+    /// Read the body of HTTP/1.0 `POST` and `PUT` requests without `Content-Length`
+    /// or `Transfer-Encoding` until the client closes the connection, as some embedded devices send them
+    ///
+    /// Off by default, so such requests have no body, RFC 9112 Section 6.3.
+    /// Nothing can follow a body read this way, so the response has `Connection: close`
+    /// and the connection is not kept alive.
+    /// The client has to only shut down its sending side to be able to read the response
+    pub read_body_to_eof: bool,
+    /// Largest request body accepted, larger requests get `413 Content Too Large`
+    ///
+    /// None, the default, accepts bodies of any size
+    pub max_body_size: Option<usize>,
+    /// Largest request line accepted, in bytes without CRLF.
+    /// Requests whose target makes the line too long get `414 URI Too Long`,
+    /// other lines that are too long get `400 Bad Request`
+    ///
+    /// None, the default, accepts request lines of any length
+    pub max_request_line_size: Option<usize>,
+    /// Largest request header section accepted, counting the bytes of the field lines,
+    /// larger requests get `431 Request Header Fields Too Large`.
+    /// The trailer section of a chunked body has the same limit
+    ///
+    /// None, the default, accepts header sections of any size
+    pub max_header_size: Option<usize>,
+    /// Let handlers read chunked request bodies while they arrive, with [`Request::body_stream`](crate::message::Request::body_stream)
+    ///
+    /// Off by default, so the whole body is read before the handler is called.
+    /// Bodies with `Content-Length` are always read up front.
+    /// If the handler returns before the body is read to the end, the connection is closed after the response
+    pub stream_chunked_bodies: bool,
+    /// Codecs that decode request bodies with transfer codings other than chunked, such as gzip
+    ///
+    /// A body with a coding that can't be decoded gets `400 Bad Request`.
+    /// None, the default, leaves the codings in `Transfer-Encoding` for the handler,
+    /// as are codings that are not in the registry
+    pub codecs: Option<Arc<Codecs>>,
+    /// How long a handler can run before it is cancelled, and the client gets `503 Service Unavailable`
+    ///
+    /// Only handlers that return to the runtime while waiting can be cancelled, such as async handlers.
+    /// None, the default, lets handlers run for as long as they need
+    pub handler_timeout: Option<Duration>,
+    /// How long to wait for the next request on a connection before closing it
+    ///
+    /// None, the default, waits forever
+    pub read_timeout: Option<Duration>,
+    /// Keep connections open between requests
+    ///
+    /// On by default. When off, every response has `Connection: close`
+    pub keep_alive: bool,
+    /// Maximum number of requests served on one keep-alive connection
+    ///
+    /// The last response has `Connection: close`, and the connection is closed after it.
+    /// None, the default, serves any number of requests
+    pub max_keep_alive_requests: Option<usize>,
+    /// Maximum number of connections served at the same time
+    ///
+    /// When reached, new connections wait to be accepted until another one closes.
+    /// None, the default, is unbounded
+    pub max_connections: Option<usize>,
+    /// Set `TCP_NODELAY` on accepted TCP connections, so responses are sent right away
+    /// instead of being held back to be combined with later writes
+    ///
+    /// On by default, as a response is usually the last write before waiting for the next request
+    pub tcp_nodelay: bool,
+    /// Maximum number of connections waiting to be accepted by the TCP listener
+    ///
+    /// The system can lower it, e.g. to `net.core.somaxconn` on Linux.
+    /// None, the default, uses 1024
+    pub listen_backlog: Option<u32>,
+    /// Set `SO_REUSEADDR` on the TCP listener, so a restarted server can bind to the address
+    /// while connections from the last run are in `TIME_WAIT`
+    ///
+    /// On by default. Only used on Unix, as it lets several sockets bind to the same port on Windows
+    pub reuse_address: bool,
+    /// Runtime that connections are spawned on
+    ///
+    /// None, the default, spawns them on the runtime calling [`Server::listen_and_serve`](crate::server::Server::listen_and_serve).
+    /// Use a dedicated runtime to control the number of worker threads
+    pub runtime: Option<Handle>,
+    /// Called when a request can't be read, before the connection is closed
+    ///
+    /// The error is one of the [`RequestError`] variants, such as [`RequestError::Timeout`],
+    /// [`RequestError::BodyTooLarge`] or [`RequestError::HeadersTooLarge`].
+    /// The returned response is sent instead of the default one, with `Connection: close`.
+    /// Returning None keeps the default: 413, 414 and 431 for the size limits, nothing after a timeout,
+    /// 400 for a malformed request line, header or body and 500 for IO errors. None, the default, always uses the default responses
+    pub error_handler: Option<ErrorHandler>,
+    /// Send the reason phrase as a plain-text body when the handler returns an error,
+    /// such as `Not Found` for `404 Not Found`
+    ///
+    /// On by default. Error pages are used instead when there is one for the status code.
+    /// When off, error responses have an empty body
+    pub error_bodies: bool,
+    /// Send the error message instead of the reason phrase in error bodies,
+    /// such as the message of [`ServerError::with_message`](crate::server::ServerError::with_message)
+    ///
+    /// Off by default, as error messages can leak internal details to clients.
+    /// Only has an effect with [`ServerConfig::error_bodies`]
+    pub debug_errors: bool,
+    /// Bodies for error responses, such as a HTML page for `404 Not Found`
+    ///
+    /// Used for every response with the status code and an empty body,
+    /// whether it is created by the server, a [`Router`](crate::server::Router) or a handler.
+    /// Empty by default, so error responses have no body
+    pub error_pages: Vec<ErrorPage>,
+    /// Counters for requests, connections, responses, bytes and parse errors,
+    /// shared with the caller who reads them with [`Metrics::snapshot`]
+    ///
+    /// None, the default, counts nothing
+    pub metrics: Option<Arc<Metrics>>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            buffer_size: DEFAULT_BUFFER_SIZE,
+            trace: false,
+            lenient_line_endings: false,
+            lenient_obs_fold: false,
+            read_body_to_eof: false,
+            max_body_size: None,
+            max_request_line_size: None,
+            max_header_size: None,
+            stream_chunked_bodies: false,
+            codecs: None,
+            handler_timeout: None,
+            read_timeout: None,
+            keep_alive: true,
+            max_keep_alive_requests: None,
+            max_connections: None,
+            tcp_nodelay: true,
+            listen_backlog: None,
+            reuse_address: true,
+            runtime: None,
+            error_handler: None,
+            error_bodies: true,
+            debug_errors: false,
+            error_pages: Vec::new(),
+            metrics: None,
+        }
+    }
+}
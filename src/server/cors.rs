@@ -0,0 +1,98 @@
+use crate::message::{Headers, Method, Request, Response, StatusCode};
+
+/// Cross-Origin Resource Sharing configuration for a [`Router`](crate::server::Router)
+///
+/// Allowed origins are echoed back in `Access-Control-Allow-Origin`.
+/// Preflight requests are answered with a 204, and the allowed methods
+/// default to the methods registered on the requested path.
+#[derive(Debug, Default, Clone)]
+pub struct Cors {
+    any_origin: bool,
+    origins: Vec<String>,
+    methods: Option<Vec<Method>>,
+    headers: Vec<String>,
+    max_age: Option<u32>,
+}
+
+impl Cors {
+    /// Creates a new [`Cors`] that allows no origins
+    pub fn new() -> Cors {
+        Cors::default()
+    }
+
+    pub fn allow_any_origin(mut self) -> Self {
+        self.any_origin = true;
+        self
+    }
+
+    pub fn allow_origin(mut self, origin: impl Into<String>) -> Self {
+        self.origins.push(origin.into());
+        self
+    }
+
+    /// Overrides the methods sent in `Access-Control-Allow-Methods`.
+    /// Without this the methods registered on the path is used
+    pub fn allow_methods(mut self, methods: &[Method]) -> Self {
+        self.methods = Some(methods.to_vec());
+        self
+    }
+
+    pub fn allow_header(mut self, header: impl Into<String>) -> Self {
+        self.headers.push(header.into());
+        self
+    }
+
+    /// Sets how many seconds a preflight response can be cached
+    pub fn max_age(mut self, seconds: u32) -> Self {
+        self.max_age = Some(seconds);
+        self
+    }
+
+    fn is_allowed(&self, origin: &str) -> bool {
+        self.any_origin || self.origins.iter().any(|o| o == origin)
+    }
+
+    /// Returns the origin of the request if it is allowed
+    pub(crate) fn allowed_origin<'a>(&self, req: &'a Request) -> Option<&'a String> {
+        req.headers.get("Origin").filter(|o| self.is_allowed(o))
+    }
+
+    pub(crate) fn is_preflight(req: &Request) -> bool {
+        *req.get_method() == Method::Options
+            && req.headers.get("Origin").is_some()
+            && req.headers.get("Access-Control-Request-Method").is_some()
+    }
+
+    /// Creates the 204 response to a preflight request
+    pub(crate) fn preflight(&self, origin: &str, route_methods: Vec<Method>) -> Response {
+        let methods = self.methods.clone().unwrap_or(route_methods);
+        let methods = methods
+            .iter()
+            .map(|m| m.to_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let mut response = Response::new(StatusCode::NoContent);
+        self.apply(origin, &mut response.headers);
+        response
+            .headers
+            .set("Access-Control-Allow-Methods", methods);
+        if !self.headers.is_empty() {
+            response
+                .headers
+                .set("Access-Control-Allow-Headers", self.headers.join(", "));
+        }
+        if let Some(max_age) = self.max_age {
+            response
+                .headers
+                .set("Access-Control-Max-Age", max_age.to_string());
+        }
+        response
+    }
+
+    /// Adds the headers telling the browser the origin is allowed
+    pub(crate) fn apply(&self, origin: &str, headers: &mut Headers) {
+        headers.set("Access-Control-Allow-Origin", origin);
+        headers.add("Vary", "Origin");
+    }
+}
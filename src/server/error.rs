@@ -2,10 +2,89 @@ use std::io;
 
 use thiserror::Error;
 
+#[cfg(feature = "serde")]
+use crate::message::FormError;
+use crate::message::{BodyError, Headers, RequestError, ResponseError, StatusCode};
+
 #[derive(Debug, Error)]
 pub enum ServerError {
     #[error("Internal Error")]
     InternalError,
+
+    /// Answers the request with the status code
+    #[error("Status: {} {}", .0.to_code(), .0.to_reason())]
+    Status(StatusCode),
+
+    /// Answers the request with the status code, the message is only sent with
+    /// [`ServerConfig::debug_errors`](crate::server::ServerConfig::debug_errors)
+    #[error("{1}")]
+    Message(StatusCode, String),
+
+    #[error("Request error: {0}")]
+    Request(#[from] RequestError),
+
+    #[error("Body error: {0}")]
+    Body(#[from] BodyError),
+
+    #[error("Response error: {0}")]
+    Response(#[from] ResponseError),
+
     #[error("IO: {0}")]
     IO(#[from] io::Error),
+
+    #[cfg(feature = "serde")]
+    #[error("Form error: {0}")]
+    Form(#[from] FormError),
+
+    #[cfg(feature = "h2")]
+    #[error("HTTP/2: {0}")]
+    H2(#[from] h2::Error),
+
+    /// Answers the request like the wrapped error, with the headers added to the response
+    #[error("{}", .0.0)]
+    WithHeaders(Box<(ServerError, Headers)>),
+}
+
+impl ServerError {
+    /// Creates an error that the server answers with the status code
+    pub fn with_status(status_code: StatusCode) -> ServerError {
+        ServerError::Status(status_code)
+    }
+
+    /// Creates an error that the server answers with the status code,
+    /// carrying a message for debugging
+    ///
+    /// ```ignore
+    /// let user = users.get(id).ok_or_else(|| {
+    ///     ServerError::with_message(StatusCode::NotFound, format!("No user with id {id}"))
+    /// })?;
+    /// ```
+    pub fn with_message(status_code: StatusCode, message: impl Into<String>) -> ServerError {
+        ServerError::Message(status_code, message.into())
+    }
+
+    /// The status code of the response the server sends for this error
+    ///
+    /// Requests over a limit or too slow get the matching status,
+    /// other malformed requests, bodies and forms are a 400 Bad Request, other errors are a 500
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            ServerError::Status(status_code) | ServerError::Message(status_code, _) => *status_code,
+            ServerError::Request(RequestError::Timeout) => StatusCode::RequestTimeout,
+            ServerError::Request(RequestError::BodyTooLarge) => StatusCode::ContentTooLarge,
+            ServerError::Request(RequestError::HeadersTooLarge) => {
+                StatusCode::RequestHeaderFieldsTooLarge
+            }
+            ServerError::Request(RequestError::UriTooLong) => StatusCode::UriTooLong,
+            ServerError::Request(_) | ServerError::Body(_) => StatusCode::BadRequest,
+            ServerError::InternalError | ServerError::Response(_) | ServerError::IO(_) => {
+                StatusCode::InternalServerError
+            }
+            #[cfg(feature = "serde")]
+            ServerError::Form(_) => StatusCode::BadRequest,
+            #[cfg(feature = "h2")]
+            ServerError::H2(_) => StatusCode::InternalServerError,
+            ServerError::WithHeaders(inner) => inner.0.status_code(),
+        }
+    }
 }
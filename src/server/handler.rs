@@ -0,0 +1,155 @@
+use std::{future::Future, pin::Pin, sync::Arc};
+
+use crate::message::{Request, Response};
+use crate::server::{IntoResponse, ServerError};
+
+/// A boxed future that can be sent between threads, as returned by async handlers
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Something that can turn a request into a response
+///
+/// Implemented for plain functions and closures with the signature
+/// `Fn(&Request) -> Result<impl IntoResponse, ServerError>`, as well as for [`Router`](crate::server::Router).
+/// Async functions can be used as handlers with [`async_handler`]
+pub trait Handler: Send + Sync + 'static {
+    fn handle<'a>(&'a self, req: &'a Request) -> BoxFuture<'a, Result<Response, ServerError>>;
+}
+
+impl<F, R> Handler for F
+where
+    F: Fn(&Request) -> Result<R, ServerError> + Send + Sync + 'static,
+    R: IntoResponse,
+{
+    fn handle<'a>(&'a self, req: &'a Request) -> BoxFuture<'a, Result<Response, ServerError>> {
+        let response = self(req).map(IntoResponse::into_response);
+        Box::pin(std::future::ready(response))
+    }
+}
+
+/// Handler running an async function, created with [`async_handler`]
+pub struct AsyncHandler<F>(F);
+
+/// Creates a handler from a function returning a boxed future,
+/// so the handler can `.await` without blocking the worker thread
+///
+/// ```ignore
+/// fn handle(req: &Request) -> BoxFuture<'_, Result<Response, ServerError>> {
+///     Box::pin(async move {
+///         let user = db.get_user(req.get_url()).await?;
+///         Ok(user.into_response())
+///     })
+/// }
+/// let server = Server::new("localhost:42069", async_handler(handle)).await;
+/// ```
+pub fn async_handler<F, R>(f: F) -> AsyncHandler<F>
+where
+    F: for<'a> Fn(&'a Request) -> BoxFuture<'a, Result<R, ServerError>> + Send + Sync + 'static,
+    R: IntoResponse + 'static,
+{
+    AsyncHandler(f)
+}
+
+impl<F, R> Handler for AsyncHandler<F>
+where
+    F: for<'a> Fn(&'a Request) -> BoxFuture<'a, Result<R, ServerError>> + Send + Sync + 'static,
+    R: IntoResponse + 'static,
+{
+    fn handle<'a>(&'a self, req: &'a Request) -> BoxFuture<'a, Result<Response, ServerError>> {
+        let future = (self.0)(req);
+        Box::pin(async move { future.await.map(IntoResponse::into_response) })
+    }
+}
+
+/// Handler with shared application state, created with [`with_state`]
+pub struct StateHandler<S, F> {
+    state: Arc<S>,
+    f: F,
+}
+
+/// Creates a handler that is passed a reference to `state` on every request
+///
+/// The state is shared between all connections, so it must be `Send + Sync`.
+/// Use interior mutability, e.g. a `Mutex`, for state that changes
+///
+/// ```ignore
+/// let hits = AtomicUsize::new(0);
+/// let handler = with_state(hits, |_req: &Request, hits: &AtomicUsize| {
+///     Ok(hits.fetch_add(1, Ordering::Relaxed).to_string())
+/// });
+/// ```
+pub fn with_state<S, F, R>(state: impl Into<Arc<S>>, f: F) -> StateHandler<S, F>
+where
+    S: Send + Sync + 'static,
+    F: Fn(&Request, &S) -> Result<R, ServerError> + Send + Sync + 'static,
+    R: IntoResponse,
+{
+    StateHandler {
+        state: state.into(),
+        f,
+    }
+}
+
+impl<S, F> StateHandler<S, F> {
+    /// Returns the shared state
+    pub fn state(&self) -> &Arc<S> {
+        &self.state
+    }
+}
+
+impl<S, F, R> Handler for StateHandler<S, F>
+where
+    S: Send + Sync + 'static,
+    F: Fn(&Request, &S) -> Result<R, ServerError> + Send + Sync + 'static,
+    R: IntoResponse,
+{
+    fn handle<'a>(&'a self, req: &'a Request) -> BoxFuture<'a, Result<Response, ServerError>> {
+        let response = (self.f)(req, &self.state).map(IntoResponse::into_response);
+        Box::pin(std::future::ready(response))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::{Method, RequestBuilder, StatusCode};
+    use pretty_assertions::assert_eq;
+
+    fn handle_async(req: &Request) -> BoxFuture<'_, Result<String, ServerError>> {
+        Box::pin(async move {
+            tokio::task::yield_now().await;
+            Ok(req.get_url().to_string())
+        })
+    }
+
+    #[tokio::test]
+    async fn test_async_handler() -> Result<(), ServerError> {
+        let handler = async_handler(handle_async);
+        let req = RequestBuilder::new(Method::Get, "/path").build();
+        let response = handler.handle(&req).await?;
+        assert_eq!(response.body, &b"/path"[..]);
+
+        let handler =
+            async_handler(|_| Box::pin(async move { Ok::<_, ServerError>(StatusCode::NoContent) }));
+        let response = handler.handle(&req).await?;
+        assert_eq!(response.status_line.status_code, StatusCode::NoContent);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_with_state() -> Result<(), ServerError> {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let hits = Arc::new(AtomicUsize::new(0));
+        let handler = with_state(hits.clone(), |_: &Request, hits: &AtomicUsize| {
+            Ok(hits.fetch_add(1, Ordering::Relaxed).to_string())
+        });
+        let req = RequestBuilder::new(Method::Get, "/").build();
+        assert_eq!(handler.handle(&req).await?.body, &b"0"[..]);
+        assert_eq!(handler.handle(&req).await?.body, &b"1"[..]);
+        assert_eq!(hits.load(Ordering::Relaxed), 2);
+        assert!(Arc::ptr_eq(handler.state(), &hits));
+
+        Ok(())
+    }
+}
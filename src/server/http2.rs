@@ -0,0 +1,354 @@
+//! HTTP/2 connections, enabled with the `h2` feature
+//!
+//! Framing, HPACK and flow control are done by the `h2` crate,
+//! requests and responses are converted to and from the types in [`message`](crate::message),
+//! so the same handlers serve both HTTP/1.1 and HTTP/2
+
+use std::{future::poll_fn, sync::Arc};
+
+use bytes::Bytes;
+use h2::{RecvStream, SendStream, server::SendResponse};
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use crate::message::{
+    BodyFraming, Headers, HttpVersion, Method, Request, RequestLine, Response, StatusCode,
+};
+use crate::server::{
+    ErrorPage, Handler, Metrics, ServerConfig, ServerError, call_handler, error_response,
+    metrics::MeteredStream, record,
+};
+
+/// The client connection preface, RFC 9113 Section 3.4
+pub const H2_PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+/// Fields that are specific to a HTTP/1.1 connection, which are not allowed in HTTP/2,
+/// RFC 9113 Section 8.2.2
+const CONNECTION_HEADERS: [&str; 5] = [
+    "connection",
+    "keep-alive",
+    "proxy-connection",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// Serves a HTTP/2 connection until it closes
+///
+/// The stream must start with the client preface, which is the case after TLS with ALPN `h2`,
+/// or for cleartext clients with prior knowledge.
+/// Each request stream is handled in its own task, so requests are multiplexed
+///
+/// # Errors
+///
+/// Returns an error if the handshake fails, or the connection has a protocol error
+pub async fn serve_h2_connection<S>(
+    stream: S,
+    handler: Arc<dyn Handler>,
+    config: &ServerConfig,
+) -> Result<(), ServerError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    match &config.metrics {
+        Some(metrics) => {
+            let stream = MeteredStream::new(stream, metrics.clone());
+            serve_h2_stream(stream, handler, config).await
+        }
+        None => serve_h2_stream(stream, handler, config).await,
+    }
+}
+
+async fn serve_h2_stream<S>(
+    stream: S,
+    handler: Arc<dyn Handler>,
+    config: &ServerConfig,
+) -> Result<(), ServerError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let config = Arc::new(config.clone());
+    let mut connection = h2::server::handshake(stream).await?;
+    while let Some(result) = connection.accept().await {
+        let (request, respond) = result?;
+        let handler = handler.clone();
+        let config = config.clone();
+        tokio::spawn(async move {
+            let handled = handle_stream(request, respond, handler.as_ref(), &config).await;
+            if let Err(e) = handled {
+                eprintln!("Error handling HTTP/2 stream: {e}");
+            }
+        });
+    }
+    Ok(())
+}
+
+async fn handle_stream(
+    request: http::Request<RecvStream>,
+    mut respond: SendResponse<Bytes>,
+    handler: &dyn Handler,
+    config: &ServerConfig,
+) -> Result<(), ServerError> {
+    let (response, head_only) = match read_request(request, config).await {
+        Ok(req) => {
+            record(config, Metrics::request);
+            (
+                call_handler(handler, &req, config).await,
+                *req.get_method() == Method::Head,
+            )
+        }
+        Err(e) => {
+            record(config, Metrics::parse_error);
+            (Err(e), false)
+        }
+    };
+    let mut response = response.unwrap_or_else(|e| {
+        eprintln!("Error handling request: {e:?}");
+        error_response(&e, config)
+    });
+    ErrorPage::apply(&config.error_pages, &mut response);
+    send_response(&mut respond, &response, head_only).await?;
+    record(config, |m| m.response(response.status_line.status_code));
+    Ok(())
+}
+
+/// Converts a HTTP/2 request into a [`Request`], reading the whole body
+///
+/// As for HTTP/1.1, the body is limited by [`ServerConfig::max_body_size`]
+/// and has to arrive within [`ServerConfig::read_timeout`]
+async fn read_request(
+    request: http::Request<RecvStream>,
+    config: &ServerConfig,
+) -> Result<Request, ServerError> {
+    let (parts, mut recv) = request.into_parts();
+    let bad_request = || ServerError::with_status(StatusCode::BadRequest);
+
+    let method = Method::parse(parts.method.as_str().as_bytes()).map_err(|_| bad_request())?;
+    let url = match parts.uri.path_and_query() {
+        Some(path) => path.as_str().to_string(),
+        // CONNECT only has an authority
+        None => parts
+            .uri
+            .authority()
+            .map(|a| a.to_string())
+            .unwrap_or_default(),
+    };
+
+    let mut headers = Headers::new();
+    for (name, value) in &parts.headers {
+        headers.add_bytes(name.as_str(), value.as_bytes());
+    }
+    // :authority replaces Host, RFC 9113 Section 8.3.1
+    if let Some(authority) = parts.uri.authority()
+        && headers.get("Host").is_none()
+    {
+        headers.set("Host", authority.as_str());
+    }
+
+    let body = match config.read_timeout {
+        Some(timeout) => tokio::time::timeout(timeout, read_body(&mut recv, config))
+            .await
+            .unwrap_or_else(|_| Err(ServerError::with_status(StatusCode::RequestTimeout)))?,
+        None => read_body(&mut recv, config).await?,
+    };
+
+    Ok(Request {
+        line: RequestLine::from_parts(method, url, HttpVersion::new(2, 0)),
+        headers,
+        body: body.into(),
+        interim: None,
+        body_stream: None,
+        body_file: None,
+        body_reader: None,
+        framing: BodyFraming::None,
+    })
+}
+
+/// Reads the DATA frames of a request, failing with `413 Content Too Large`
+/// as soon as the body is larger than [`ServerConfig::max_body_size`]
+async fn read_body(recv: &mut RecvStream, config: &ServerConfig) -> Result<Vec<u8>, ServerError> {
+    let mut body = Vec::new();
+    while let Some(data) = recv.data().await {
+        let data = data?;
+        let _ = recv.flow_control().release_capacity(data.len());
+        body.extend_from_slice(&data);
+        if config.max_body_size.is_some_and(|max| body.len() > max) {
+            return Err(ServerError::with_status(StatusCode::ContentTooLarge));
+        }
+    }
+    Ok(body)
+}
+
+/// Sends the response as HEADERS and DATA frames, and trailers as a final HEADERS frame
+///
+/// With head_only, as for a HEAD request, only the HEADERS frame is sent,
+/// still with the `content-length` of the body
+async fn send_response(
+    respond: &mut SendResponse<Bytes>,
+    response: &Response,
+    head_only: bool,
+) -> Result<(), ServerError> {
+    let status = response.status_line.status_code.as_u16();
+    let mut head = http::Response::builder().status(status);
+    for (name, value) in response.headers.iter() {
+        if !CONNECTION_HEADERS.contains(&name) && name != "content-length" {
+            head = head.header(name, value);
+        }
+    }
+    if !response.body.is_empty() {
+        head = head.header("content-length", response.body.len());
+    }
+    let head = head.body(()).map_err(|_| ServerError::InternalError)?;
+
+    if head_only {
+        respond.send_response(head, true)?;
+        return Ok(());
+    }
+
+    let trailers = response.trailers().map(header_map).transpose()?;
+    let end_of_stream = response.body.is_empty() && trailers.is_none();
+    let mut send = respond.send_response(head, end_of_stream)?;
+
+    send_body(&mut send, response.body.clone(), trailers.is_none()).await?;
+    if let Some(trailers) = trailers {
+        send.send_trailers(trailers)?;
+    }
+    Ok(())
+}
+
+/// Sends data, waiting for the peer to give flow control capacity
+async fn send_body(
+    send: &mut SendStream<Bytes>,
+    mut data: Bytes,
+    end_of_stream: bool,
+) -> Result<(), ServerError> {
+    while !data.is_empty() {
+        send.reserve_capacity(data.len());
+        let capacity = match poll_fn(|cx| send.poll_capacity(cx)).await {
+            Some(capacity) => capacity?,
+            None => return Err(ServerError::InternalError),
+        };
+        if capacity == 0 {
+            continue;
+        }
+        let chunk = data.split_to(capacity.min(data.len()));
+        send.send_data(chunk, end_of_stream && data.is_empty())?;
+    }
+    Ok(())
+}
+
+fn header_map(headers: &Headers) -> Result<http::HeaderMap, ServerError> {
+    let mut map = http::HeaderMap::new();
+    for (name, _) in headers.iter() {
+        let value = headers.get_bytes(name).unwrap_or_default();
+        let name = http::HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| ServerError::InternalError)?;
+        let value = http::HeaderValue::from_bytes(value).map_err(|_| ServerError::InternalError)?;
+        map.append(name, value);
+    }
+    Ok(map)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[tokio::test]
+    async fn test_h2_request_response() -> Result<(), ServerError> {
+        fn handler(req: &Request) -> Result<Response, ServerError> {
+            let mut response = Response::new(StatusCode::Ok);
+            response.headers.set("Connection", "keep-alive");
+            response.body = format!(
+                "{} {} {}",
+                req.get_method().to_str(),
+                req.get_url(),
+                String::from_utf8_lossy(req.get_body())
+            )
+            .into();
+            response.set_trailer("Checksum", "abc");
+            Ok(response)
+        }
+
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        tokio::spawn(async move {
+            serve_h2_connection(server, Arc::new(handler), &ServerConfig::default()).await
+        });
+
+        let (mut send_request, connection) = h2::client::handshake(client).await?;
+        tokio::spawn(connection);
+
+        let request = http::Request::post("http://localhost/echo?a=b")
+            .body(())
+            .unwrap();
+        let (response, mut send) = send_request.send_request(request, false)?;
+        send.send_data(Bytes::from_static(b"hello"), true)?;
+
+        let response = response.await?;
+        assert_eq!(response.status(), 200);
+        assert!(response.headers().get("connection").is_none());
+        assert_eq!(response.headers()["content-length"], "20");
+
+        let mut body = response.into_body();
+        let mut data = Vec::new();
+        while let Some(chunk) = body.data().await {
+            data.extend_from_slice(&chunk?);
+        }
+        assert_eq!(data, b"POST /echo?a=b hello");
+        let trailers = body.trailers().await?.unwrap();
+        assert_eq!(trailers["checksum"], "abc");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_h2_body_too_large() -> Result<(), ServerError> {
+        fn handler(_: &Request) -> Result<Response, ServerError> {
+            Ok(Response::new(StatusCode::Ok))
+        }
+
+        let config = ServerConfig {
+            max_body_size: Some(4),
+            ..ServerConfig::default()
+        };
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        tokio::spawn(async move { serve_h2_connection(server, Arc::new(handler), &config).await });
+
+        let (mut send_request, connection) = h2::client::handshake(client).await?;
+        tokio::spawn(connection);
+
+        let request = http::Request::post("http://localhost/").body(()).unwrap();
+        let (response, mut send) = send_request.send_request(request, false)?;
+        send.send_data(Bytes::from_static(b"hello"), false)?;
+
+        let response = response.await?;
+        assert_eq!(response.status(), 413);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_h2_body_read_timeout() -> Result<(), ServerError> {
+        fn handler(_: &Request) -> Result<Response, ServerError> {
+            Ok(Response::new(StatusCode::Ok))
+        }
+
+        let config = ServerConfig {
+            read_timeout: Some(std::time::Duration::from_millis(10)),
+            ..ServerConfig::default()
+        };
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        tokio::spawn(async move { serve_h2_connection(server, Arc::new(handler), &config).await });
+
+        let (mut send_request, connection) = h2::client::handshake(client).await?;
+        tokio::spawn(connection);
+
+        // The body is never finished
+        let request = http::Request::post("http://localhost/").body(()).unwrap();
+        let (response, mut send) = send_request.send_request(request, false)?;
+        send.send_data(Bytes::from_static(b"he"), false)?;
+
+        let response = response.await?;
+        assert_eq!(response.status(), 408);
+
+        Ok(())
+    }
+}
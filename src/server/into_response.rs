@@ -0,0 +1,93 @@
+use crate::message::{Response, StatusCode};
+
+/// Conversion into a [`Response`], letting handlers return simple values
+///
+/// - Text (`&str`, `String`) is a 200 with `Content-Type: text/plain; charset=utf-8`
+/// - Bytes (`Vec<u8>`) is a 200 with `Content-Type: application/octet-stream`
+/// - A [`StatusCode`] is a response with that status and no body
+/// - `(StatusCode, String)` is a text response with that status
+pub trait IntoResponse {
+    fn into_response(self) -> Response;
+}
+
+fn text(status_code: StatusCode, body: String) -> Response {
+    let mut response = Response::new(status_code);
+    response
+        .headers
+        .set("Content-Type", "text/plain; charset=utf-8");
+    response.body = body.into();
+    response
+}
+
+impl IntoResponse for Response {
+    fn into_response(self) -> Response {
+        self
+    }
+}
+
+impl IntoResponse for &str {
+    fn into_response(self) -> Response {
+        text(StatusCode::Ok, self.to_string())
+    }
+}
+
+impl IntoResponse for String {
+    fn into_response(self) -> Response {
+        text(StatusCode::Ok, self)
+    }
+}
+
+impl IntoResponse for Vec<u8> {
+    fn into_response(self) -> Response {
+        let mut response = Response::new(StatusCode::Ok);
+        response
+            .headers
+            .set("Content-Type", "application/octet-stream");
+        response.body = self.into();
+        response
+    }
+}
+
+impl IntoResponse for StatusCode {
+    fn into_response(self) -> Response {
+        Response::new(self)
+    }
+}
+
+impl IntoResponse for (StatusCode, String) {
+    fn into_response(self) -> Response {
+        text(self.0, self.1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_into_response() {
+        let response = "Hello".into_response();
+        assert_eq!(response.status_line.status_code, StatusCode::Ok);
+        assert_eq!(response.body, &b"Hello"[..]);
+        assert_eq!(
+            response.headers.get("Content-Type"),
+            Some(&"text/plain; charset=utf-8".to_string())
+        );
+
+        let response = vec![1, 2, 3].into_response();
+        assert_eq!(response.body, vec![1, 2, 3]);
+        assert_eq!(
+            response.headers.get("Content-Type"),
+            Some(&"application/octet-stream".to_string())
+        );
+
+        let response = StatusCode::NotFound.into_response();
+        assert_eq!(response.status_line.status_code, StatusCode::NotFound);
+        assert!(response.body.is_empty());
+
+        let response = (StatusCode::BadRequest, "Missing id".to_string()).into_response();
+        assert_eq!(response.status_line.status_code, StatusCode::BadRequest);
+        assert_eq!(response.body, &b"Missing id"[..]);
+    }
+}
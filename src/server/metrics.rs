@@ -0,0 +1,212 @@
+use std::{
+    io,
+    pin::Pin,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    task::{Context, Poll},
+};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use crate::message::StatusCode;
+
+/// Counters updated by the server while it serves connections, see [`ServerConfig::metrics`](crate::server::ServerConfig::metrics)
+///
+/// Every counter is an atomic, so updating them never blocks a connection.
+/// Read them with [`Metrics::snapshot`], e.g. to export them to Prometheus
+///
+/// ```ignore
+/// let metrics = Arc::new(Metrics::new());
+/// let server = Server::builder()
+///     .handler(handle_request)
+///     .metrics(metrics.clone())
+///     .build()
+///     .await?;
+/// println!("{:?}", metrics.snapshot());
+/// ```
+#[derive(Debug, Default)]
+pub struct Metrics {
+    requests: AtomicU64,
+    active_connections: AtomicU64,
+    responses: [AtomicU64; 5],
+    bytes_read: AtomicU64,
+    bytes_written: AtomicU64,
+    parse_errors: AtomicU64,
+}
+
+/// The values of the [`Metrics`] counters at one point in time
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MetricsSnapshot {
+    /// Requests read from clients
+    pub requests: u64,
+    /// Connections being served
+    pub active_connections: u64,
+    /// Final responses sent, by status class, from 1xx at index 0 to 5xx at index 4
+    pub responses: [u64; 5],
+    /// Bytes read from connections, including the bytes of upgraded connections
+    pub bytes_read: u64,
+    /// Bytes written to connections, including the bytes of upgraded connections
+    pub bytes_written: u64,
+    /// Requests that could not be read, because they are malformed or exceed a limit
+    pub parse_errors: u64,
+}
+
+impl Metrics {
+    pub fn new() -> Metrics {
+        Metrics::default()
+    }
+
+    /// Reads every counter
+    ///
+    /// The counters are read one at a time, so a snapshot taken while requests are served
+    /// can be off by the requests in flight
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            requests: self.requests.load(Ordering::Relaxed),
+            active_connections: self.active_connections.load(Ordering::Relaxed),
+            responses: self.responses.each_ref().map(|c| c.load(Ordering::Relaxed)),
+            bytes_read: self.bytes_read.load(Ordering::Relaxed),
+            bytes_written: self.bytes_written.load(Ordering::Relaxed),
+            parse_errors: self.parse_errors.load(Ordering::Relaxed),
+        }
+    }
+
+    pub(crate) fn request(&self) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn response(&self, status_code: StatusCode) {
+        let class = usize::from(status_code.as_u16() / 100).clamp(1, 5);
+        self.responses[class - 1].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn parse_error(&self) {
+        self.parse_errors.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// A connection whose bytes are counted, which is active until it is dropped
+pub(crate) struct MeteredStream<S> {
+    inner: Pin<Box<S>>,
+    metrics: Arc<Metrics>,
+}
+
+impl<S> MeteredStream<S> {
+    pub(crate) fn new(inner: S, metrics: Arc<Metrics>) -> MeteredStream<S> {
+        metrics.active_connections.fetch_add(1, Ordering::Relaxed);
+        MeteredStream {
+            inner: Box::pin(inner),
+            metrics,
+        }
+    }
+}
+
+impl<S> Drop for MeteredStream<S> {
+    fn drop(&mut self) {
+        self.metrics
+            .active_connections
+            .fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl<S: AsyncRead> AsyncRead for MeteredStream<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let before = buf.filled().len();
+        let poll = self.inner.as_mut().poll_read(cx, buf);
+        let read = buf.filled().len() - before;
+        self.metrics
+            .bytes_read
+            .fetch_add(read as u64, Ordering::Relaxed);
+        poll
+    }
+}
+
+impl<S: AsyncWrite> AsyncWrite for MeteredStream<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let poll = self.inner.as_mut().poll_write(cx, buf);
+        if let Poll::Ready(Ok(n)) = poll {
+            self.metrics
+                .bytes_written
+                .fetch_add(n as u64, Ordering::Relaxed);
+        }
+        poll
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let poll = self.inner.as_mut().poll_write_vectored(cx, bufs);
+        if let Poll::Ready(Ok(n)) = poll {
+            self.metrics
+                .bytes_written
+                .fetch_add(n as u64, Ordering::Relaxed);
+        }
+        poll
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.inner.as_mut().poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.inner.as_mut().poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[test]
+    fn test_response_classes() {
+        let metrics = Metrics::new();
+        for status in [
+            StatusCode::SwitchingProtocols,
+            StatusCode::Ok,
+            StatusCode::NoContent,
+            StatusCode::NotFound,
+            StatusCode::InternalServerError,
+        ] {
+            metrics.response(status);
+        }
+        assert_eq!(metrics.snapshot().responses, [1, 2, 0, 1, 1]);
+    }
+
+    #[tokio::test]
+    async fn test_metered_stream() -> io::Result<()> {
+        let metrics = Arc::new(Metrics::new());
+        let (mut client, server) = tokio::io::duplex(64);
+        let mut stream = MeteredStream::new(server, metrics.clone());
+        assert_eq!(metrics.snapshot().active_connections, 1);
+
+        client.write_all(b"hello").await?;
+        let mut buf = [0u8; 5];
+        stream.read_exact(&mut buf).await?;
+        stream.write_all(b"hi").await?;
+
+        drop(stream);
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.bytes_read, 5);
+        assert_eq!(snapshot.bytes_written, 2);
+        assert_eq!(snapshot.active_connections, 0);
+        Ok(())
+    }
+}
@@ -1,64 +1,282 @@
+mod builder;
+mod config;
+mod cors;
 mod error;
+mod handler;
+#[cfg(feature = "h2")]
+mod http2;
+mod into_response;
+mod metrics;
+mod router;
 
+use std::any::Any;
 use std::io;
+use std::net::SocketAddr;
+use std::panic::{self, AssertUnwindSafe};
+#[cfg(unix)]
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
 
+pub use builder::ServerBuilder;
+pub use config::{ErrorHandler, ErrorPage, ServerConfig};
+pub use cors::Cors;
 pub use error::ServerError;
+pub use handler::{AsyncHandler, BoxFuture, Handler, StateHandler, async_handler, with_state};
+#[cfg(feature = "h2")]
+pub use http2::{H2_PREFACE, serve_h2_connection};
+pub use into_response::IntoResponse;
+pub use metrics::{Metrics, MetricsSnapshot};
+pub use router::Router;
 
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
-use tokio::net::TcpListener;
+#[cfg(unix)]
+use tokio::net::UnixListener;
+use tokio::net::{TcpListener, TcpSocket};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore, mpsc, mpsc::OwnedPermit};
 
-use crate::message::{Connection, Request, RequestError, Response, ResponseBuilder, StatusCode};
+use crate::message::{
+    BodyError, BodyStream, Bytes, Connection, Headers, HttpVersion, Method, Request, RequestError,
+    Response, ResponseBuilder, StatusCode, StreamedBody, UpgradeFuture, Upgraded,
+};
+use metrics::MeteredStream;
 
 pub trait Stream: AsyncRead + AsyncWrite + Unpin + Send {}
 impl<T: AsyncRead + AsyncWrite + Unpin + Send> Stream for T {}
 
 /// HTTP Server
 ///
-/// Uses a threadpool to handle requests
+/// Spawns a task for each connection, see [`ServerConfig`] for the runtime and connection limit
 ///
 pub struct Server {
-    handler: Handler,
+    handler: Arc<dyn Handler>,
+    config: ServerConfig,
     _addr: String,
-    listener: TcpListener,
+    listener: Listener,
 }
 
-type Handler = fn(&Request) -> Result<Response, ServerError>;
+/// The socket a [`Server`] accepts connections on
+enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener),
+}
 
 impl Server {
-    pub async fn new(addr: &str, handler: Handler) -> Server {
-        let listener = TcpListener::bind(addr)
+    /// Returns a [`ServerBuilder`] to configure a server step by step
+    ///
+    /// ```ignore
+    /// let server = Server::builder()
+    ///     .addr("localhost:42069")
+    ///     .handler(handle_request)
+    ///     .max_body_size(1024 * 1024)
+    ///     .build()
+    ///     .await?;
+    /// ```
+    pub fn builder() -> ServerBuilder {
+        ServerBuilder::new()
+    }
+
+    pub async fn new(addr: &str, handler: impl Handler) -> Server {
+        Server::with_config(addr, handler, ServerConfig::default()).await
+    }
+
+    /// Creates a server whose handler gets a reference to the shared `state` on every request
+    pub async fn with_state<S, F, R>(addr: &str, state: impl Into<Arc<S>>, handler: F) -> Server
+    where
+        S: Send + Sync + 'static,
+        F: Fn(&Request, &S) -> Result<R, ServerError> + Send + Sync + 'static,
+        R: IntoResponse,
+    {
+        Server::new(addr, with_state(state, handler)).await
+    }
+
+    pub async fn with_config(addr: &str, handler: impl Handler, config: ServerConfig) -> Server {
+        let listener = bind(addr, &config)
             .await
             .expect("Could not bind to addr: {addr}");
         Server {
-            handler,
+            handler: Arc::new(handler),
+            config,
             _addr: addr.to_string(),
-            listener,
+            listener: Listener::Tcp(listener),
         }
     }
 
-    /// Listens to incoming streams, sending them to the threadpool
+    /// Sets the body of error responses with the status code,
+    /// such as a friendly HTML page for `404 Not Found`, see [`ServerConfig::error_pages`]
+    ///
+    /// ```ignore
+    /// let server = Server::new("localhost:42069", router)
+    ///     .await
+    ///     .error_page(StatusCode::NotFound, "<h1>Not Found</h1>", "text/html");
+    /// ```
+    pub fn error_page(
+        mut self,
+        status_code: StatusCode,
+        body: impl Into<Bytes>,
+        content_type: impl Into<String>,
+    ) -> Server {
+        builder::set_error_page(
+            &mut self.config,
+            ErrorPage::new(status_code, body, content_type),
+        );
+        self
+    }
+
+    /// Creates a server listening on a Unix domain socket at path
+    ///
+    /// Binding fails if a file already exists at path,
+    /// so a socket left behind by an earlier run must be removed first
     ///
     /// # Panics
     ///
-    /// Panics if it can't send the job to the threadpool
+    /// Panics if it can't bind to path
+    #[cfg(unix)]
+    pub fn new_unix(path: impl AsRef<Path>, handler: impl Handler) -> Server {
+        let path = path.as_ref();
+        let listener = UnixListener::bind(path)
+            .unwrap_or_else(|e| panic!("Could not bind to {}: {e}", path.display()));
+        Server {
+            handler: Arc::new(handler),
+            config: ServerConfig::default(),
+            _addr: path.to_string_lossy().into_owned(),
+            listener: Listener::Unix(listener),
+        }
+    }
+
+    /// Listens to incoming streams, spawning a task for each of them
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if accepting a connection fails
     pub async fn listen_and_serve(&self) -> Result<(), ServerError> {
-        let addr = self.listener.local_addr().unwrap();
-        println!("Listening to: {:?}", addr);
-        let handler = self.handler;
+        let limit = self
+            .config
+            .max_connections
+            .map(|max| Arc::new(Semaphore::new(max)));
 
-        loop {
-            let (mut stream, _) = self.listener.accept().await?;
-            let addr = stream.peer_addr().unwrap();
-            println!("Got request from: {:?}", addr);
+        match &self.listener {
+            Listener::Tcp(listener) => {
+                println!("Listening to: {:?}", listener.local_addr()?);
+                loop {
+                    let permit = acquire(&limit).await;
+                    let (stream, peer) = listener.accept().await?;
+                    println!("Got request from: {:?}", peer);
+                    if let Err(e) = stream.set_nodelay(self.config.tcp_nodelay) {
+                        eprintln!("Failed to set TCP_NODELAY: {e}");
+                    }
+                    #[cfg(feature = "h2")]
+                    self.spawn_tcp_connection(stream, permit);
+                    #[cfg(not(feature = "h2"))]
+                    self.spawn_connection(stream, permit);
+                }
+            }
+            #[cfg(unix)]
+            Listener::Unix(listener) => {
+                println!("Listening to: {:?}", listener.local_addr()?);
+                loop {
+                    let permit = acquire(&limit).await;
+                    // Unix peers are usually unnamed, which is printed as "(unnamed)"
+                    let (stream, peer) = listener.accept().await?;
+                    println!("Got request from: {:?}", peer);
+                    self.spawn_connection(stream, permit);
+                }
+            }
+        }
+    }
 
-            tokio::spawn(async move {
-                let (r, w) = stream.split();
-                let connection = Connection::<_, _, Request>::new(r, w);
-                handle_connection(connection, handler).await;
-                println!("Closing connection");
-            });
+    /// Serves the connection with HTTP/2 if the client starts with the HTTP/2 preface,
+    /// which cleartext clients with prior knowledge do, otherwise with HTTP/1.1
+    #[cfg(feature = "h2")]
+    fn spawn_tcp_connection(
+        &self,
+        stream: tokio::net::TcpStream,
+        permit: Option<OwnedSemaphorePermit>,
+    ) {
+        let handler = self.handler.clone();
+        let config = self.config.clone();
+        self.spawn(async move {
+            if has_h2_preface(&stream, &config).await {
+                if let Err(e) = serve_h2_connection(stream, handler, &config).await {
+                    eprintln!("HTTP/2 connection error: {e}");
+                }
+            } else {
+                serve_connection(stream, handler.as_ref(), &config).await;
+            }
+            println!("Closing connection");
+            drop(permit);
+        });
+    }
+
+    fn spawn_connection<S: Stream + 'static>(
+        &self,
+        stream: S,
+        permit: Option<OwnedSemaphorePermit>,
+    ) {
+        let handler = self.handler.clone();
+        let config = self.config.clone();
+        self.spawn(async move {
+            serve_connection(stream, handler.as_ref(), &config).await;
+            println!("Closing connection");
+            drop(permit);
+        });
+    }
+
+    /// Spawns on the configured runtime, or the current one
+    fn spawn<F>(&self, future: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        match &self.config.runtime {
+            Some(runtime) => runtime.spawn(future),
+            None => tokio::spawn(future),
+        };
+    }
+}
+
+/// Backlog of the TCP listener when [`ServerConfig::listen_backlog`] is not set
+const DEFAULT_LISTEN_BACKLOG: u32 = 1024;
+
+/// Binds a TCP listener to addr with the socket options of the config
+///
+/// Every address addr resolves to is tried in turn, until one can be bound
+///
+/// # Errors
+///
+/// Returns an error if addr can't be resolved, or the error of the last address if none can be bound
+pub(crate) async fn bind(addr: &str, config: &ServerConfig) -> io::Result<TcpListener> {
+    let mut last_error = None;
+    for addr in tokio::net::lookup_host(addr).await? {
+        match bind_socket(addr, config) {
+            Ok(listener) => return Ok(listener),
+            Err(e) => last_error = Some(e),
         }
     }
+    Err(last_error.unwrap_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidInput, "Address resolved to nothing")
+    }))
+}
+
+fn bind_socket(addr: SocketAddr, config: &ServerConfig) -> io::Result<TcpListener> {
+    let socket = match addr {
+        SocketAddr::V4(_) => TcpSocket::new_v4()?,
+        SocketAddr::V6(_) => TcpSocket::new_v6()?,
+    };
+    #[cfg(unix)]
+    socket.set_reuseaddr(config.reuse_address)?;
+    socket.bind(addr)?;
+    socket.listen(config.listen_backlog.unwrap_or(DEFAULT_LISTEN_BACKLOG))
+}
+
+/// Waits for a connection slot when the number of connections is limited
+async fn acquire(limit: &Option<Arc<Semaphore>>) -> Option<OwnedSemaphorePermit> {
+    match limit {
+        // The semaphore is never closed
+        Some(limit) => limit.clone().acquire_owned().await.ok(),
+        None => None,
+    }
 }
 
 async fn internal_error<R, W>(connection: &mut Connection<R, W, Request>)
@@ -68,8 +286,8 @@ where
 {
     let mut builder = ResponseBuilder::new();
     builder.set_status_code(StatusCode::InternalServerError);
-    let mut response = builder.build();
-    let r = connection.respond(&mut response).await;
+    let response = builder.build();
+    let r = connection.respond(&response).await;
     if let Err(e) = r {
         eprintln!("Failed to write internal error to tcp stream");
         eprintln!("{e}");
@@ -77,50 +295,280 @@ where
     }
 }
 
+/// Serves requests on a single connection until it closes, without a [`Server`]
+///
+/// This allows driving connections from other listeners than TCP,
+/// such as Unix sockets or in-memory streams
+///
+/// ```ignore
+/// let listener = UnixListener::bind("/tmp/http.sock")?;
+/// loop {
+///     let (stream, _) = listener.accept().await?;
+///     tokio::spawn(async move {
+///         serve_connection(stream, &handle_request, &ServerConfig::default()).await;
+///     });
+/// }
+/// ```
+pub async fn serve_connection<S>(stream: S, handler: &dyn Handler, config: &ServerConfig)
+where
+    S: AsyncRead + AsyncWrite + Send,
+{
+    match &config.metrics {
+        Some(metrics) => {
+            let stream = MeteredStream::new(stream, metrics.clone());
+            serve_stream(stream, handler, config).await;
+        }
+        None => serve_stream(stream, handler, config).await,
+    }
+}
+
+async fn serve_stream<S>(stream: S, handler: &dyn Handler, config: &ServerConfig)
+where
+    S: AsyncRead + AsyncWrite + Send,
+{
+    let (r, w) = tokio::io::split(stream);
+    let mut connection = Connection::<_, _, Request>::with_capacity(r, w, config.buffer_size);
+    connection.set_lenient_lf(config.lenient_line_endings);
+    connection.set_lenient_obs_fold(config.lenient_obs_fold);
+    connection.set_read_body_to_eof(config.read_body_to_eof);
+    connection.set_max_body_size(config.max_body_size);
+    connection.set_max_request_line_size(config.max_request_line_size);
+    connection.set_max_header_size(config.max_header_size);
+    connection.set_stream_chunked(config.stream_chunked_bodies);
+    connection.set_codecs(config.codecs.clone());
+    handle_connection(connection, handler, config).await;
+}
+
+/// Serves a connection with the protocol negotiated with TLS ALPN,
+/// HTTP/2 for `h2` and HTTP/1.1 otherwise
+///
+/// # Errors
+///
+/// Returns an error if a HTTP/2 connection has a protocol error
+#[cfg(feature = "h2")]
+pub async fn serve_connection_alpn<S>(
+    stream: S,
+    alpn: Option<&[u8]>,
+    handler: Arc<dyn Handler>,
+    config: &ServerConfig,
+) -> Result<(), ServerError>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send,
+{
+    if alpn == Some(b"h2") {
+        return serve_h2_connection(stream, handler, config).await;
+    }
+    serve_connection(stream, handler.as_ref(), config).await;
+    Ok(())
+}
+
+/// Returns true if the client sent the HTTP/2 preface, without consuming it
+///
+/// No HTTP/1.1 method starts with "PRI", so only the start of the preface is needed.
+/// A client that doesn't send it within [`ServerConfig::read_timeout`] is served with HTTP/1.1,
+/// which then times out reading the request
+#[cfg(feature = "h2")]
+async fn has_h2_preface(stream: &tokio::net::TcpStream, config: &ServerConfig) -> bool {
+    let peek = async {
+        let mut buf = [0u8; 3];
+        loop {
+            match stream.peek(&mut buf).await {
+                Ok(0) | Err(_) => return false,
+                Ok(n) if buf[..n] != H2_PREFACE[..n] => return false,
+                Ok(n) if n == buf.len() => return true,
+                // Only part of the start has arrived, and peeking again returns at once,
+                // since the socket stays readable while the data is not consumed
+                Ok(_) => tokio::time::sleep(H2_PREFACE_POLL).await,
+            }
+        }
+    };
+    match config.read_timeout {
+        Some(timeout) => tokio::time::timeout(timeout, peek).await.unwrap_or(false),
+        None => peek.await,
+    }
+}
+
+/// How long to wait before peeking again when only part of the HTTP/2 preface has arrived
+#[cfg(feature = "h2")]
+const H2_PREFACE_POLL: std::time::Duration = std::time::Duration::from_millis(5);
+
 /// Tries to read request
 /// Then passes it to the handler
 /// Then writes the returning response to the stream
 ///
 /// If any of the above failes, it will write an InternalServerError response to the stream
-async fn handle_connection<R, W>(mut connection: Connection<R, W, Request>, handler: Handler)
-where
-    R: AsyncReadExt + Unpin,
-    W: AsyncWriteExt + Unpin,
+async fn handle_connection<R, W>(
+    mut connection: Connection<R, W, Request>,
+    handler: &dyn Handler,
+    config: &ServerConfig,
+) where
+    R: AsyncReadExt + Unpin + Send,
+    W: AsyncWriteExt + Unpin + Send,
 {
+    let mut served = 0;
     loop {
-        let request = connection.read().await;
+        let request = match config.read_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, connection.read())
+                .await
+                .unwrap_or(Err(RequestError::Timeout)),
+            None => connection.read().await,
+        };
 
-        let request = match request {
+        let mut request = match request {
             Ok(req) => req,
+            // The client closed the connection between requests
+            Err(RequestError::ConnectionClosed) => break,
+            // The client is gone in the middle of a request, so no response can be sent
             Err(RequestError::IO(e))
                 if e.kind() == io::ErrorKind::UnexpectedEof
                     || e.kind() == io::ErrorKind::ConnectionAborted
                     || e.kind() == io::ErrorKind::BrokenPipe =>
             {
-                eprintln!("IO error handling request: {e}");
+                eprintln!("Connection closed while reading request: {e}");
                 break;
             }
-            Err(_) => {
-                internal_error(&mut connection).await;
+            Err(e) => {
+                eprintln!("Error reading request: {e}");
+                if !matches!(e, RequestError::Timeout | RequestError::IO(_)) {
+                    record(config, Metrics::parse_error);
+                }
+                let response = match &config.error_handler {
+                    Some(error_handler) => error_handler.call(&e),
+                    None => None,
+                };
+                // The rest of the request is not read, so the connection can't be reused
+                if let Some(mut response) = response.or_else(|| read_error_response(&e)) {
+                    ErrorPage::apply(&config.error_pages, &mut response);
+                    response.headers.set("Connection", "close");
+                    record(config, |m| m.response(response.status_line.status_code));
+                    if let Err(e) = connection.respond(&response).await {
+                        eprintln!("Failed to write response: {e}");
+                    }
+                }
                 break;
             }
         };
 
-        let response = handler(&request);
+        record(config, Metrics::request);
+
+        // Only HTTP/1.x is spoken here, HTTP/2 is detected before the connection is handled
+        if request.line.version.major() != 1 {
+            let mut response = Response::new(StatusCode::HttpVersionNotSupported);
+            ErrorPage::apply(&config.error_pages, &mut response);
+            response.headers.set("Connection", "close");
+            record(config, |m| m.response(response.status_line.status_code));
+            if let Err(e) = connection.respond(&response).await {
+                eprintln!("Failed to write response: {e}");
+            }
+            break;
+        }
+
+        let (interim_tx, mut interim_rx) = mpsc::unbounded_channel();
+        request.interim = Some(interim_tx);
+
+        // A streamed body is read from the connection when the handler has room for the next chunk
+        let mut body_tx = None;
+        if connection.is_reading_body() {
+            let (tx, body_stream) = BodyStream::channel();
+            request.body_stream = Some(Arc::new(Mutex::new(Some(body_stream))));
+            body_tx = Some(tx);
+        }
+
+        let response = if config.trace && *request.get_method() == Method::Trace {
+            Ok(trace_response(&request))
+        } else {
+            // Interim responses are written while the handler is running
+            let mut handling = call_handler(handler, &request, config);
+            loop {
+                tokio::select! {
+                    biased;
+                    Some((status, headers)) = interim_rx.recv() => {
+                        send_interim(&mut connection, status, &headers).await;
+                    }
+                    response = &mut handling => break response,
+                    permit = reserve_chunk(&body_tx) => {
+                        // The stream was dropped by the handler
+                        let Ok(permit) = permit else {
+                            body_tx = None;
+                            continue;
+                        };
+                        // The handler can return before the client sends the next chunk
+                        let chunk = tokio::select! {
+                            biased;
+                            response = &mut handling => break response,
+                            chunk = connection.read_chunk() => chunk,
+                        };
+                        if !deliver_chunk(permit, chunk) {
+                            body_tx = None;
+                        }
+                    }
+                }
+            }
+        };
+        // Sent just before the handler returned
+        while let Ok((status, headers)) = interim_rx.try_recv() {
+            send_interim(&mut connection, status, &headers).await;
+        }
 
         let mut response = match response {
             Ok(resp) => resp,
             Err(e) => {
                 eprintln!("Error handling request: {e:?}");
-                internal_error(&mut connection).await;
-                break;
+                error_response(&e, config)
             }
         };
 
-        if connection.respond(&mut response).await.is_err() {
+        ErrorPage::apply(&config.error_pages, &mut response);
+        served += 1;
+        let last_request = config
+            .max_keep_alive_requests
+            .is_some_and(|max| served >= max);
+        // The rest of an unread streamed body would be taken as the next request,
+        // and nothing can follow a body read until the client closed the connection
+        if !config.keep_alive
+            || last_request
+            || connection.is_reading_body()
+            || connection.is_at_eof()
+        {
+            response.headers.set("Connection", "close");
+        }
+        match_request_version(&request, &mut response);
+        // Nothing follows the head of a response to HEAD, such as the events of an event stream
+        let upgrade = response
+            .take_upgrade()
+            .filter(|_| *request.get_method() != Method::Head);
+
+        // The body of a response to HEAD is not sent, but its length is
+        let written = if *request.get_method() == Method::Head {
+            connection.respond_head(&response).await
+        } else {
+            connection.respond(&response).await
+        };
+        if written.is_err() {
             internal_error(&mut connection).await;
             break;
         }
+        record(config, |m| m.response(response.status_line.status_code));
+
+        // The handler takes over the stream, e.g. after switching protocols or for a tunnel
+        if let Some(upgrade) = upgrade {
+            if body_tx.is_some() {
+                // The rest of the body is still read into the handler's stream
+                // while the response is written, see Response::duplex,
+                // so the callback gets no reader
+                let (mut body, w) = connection.split_body();
+                let running = upgrade.call(Upgraded::new(tokio::io::empty(), Vec::new(), w));
+                forward_body(running, &mut body, body_tx).await;
+            } else {
+                let (r, buffered, w) = connection.into_parts();
+                upgrade.call(Upgraded::new(r, buffered, w)).await;
+            }
+            break;
+        }
+        // After switching protocols the connection no longer speaks HTTP/1.1
+        if response.status_line.status_code == StatusCode::SwitchingProtocols {
+            break;
+        }
 
         if should_close(&request, &response) {
             break;
@@ -128,8 +576,257 @@ where
     }
 }
 
+/// Calls the handler, failing with `503 Service Unavailable` if it runs longer than
+/// [`ServerConfig::handler_timeout`], which cancels it
+///
+/// A panic in the handler is caught and logged, and fails with `500 Internal Server Error`,
+/// so the client gets a response instead of the connection being dropped
+pub(crate) fn call_handler<'a>(
+    handler: &'a dyn Handler,
+    req: &'a Request,
+    config: &ServerConfig,
+) -> BoxFuture<'a, Result<Response, ServerError>> {
+    // Plain function handlers run when they are called, async handlers when they are polled
+    let handling = match panic::catch_unwind(AssertUnwindSafe(|| handler.handle(req))) {
+        Ok(handling) => Box::pin(CatchUnwind(handling)),
+        Err(payload) => return Box::pin(std::future::ready(Err(handler_panicked(payload)))),
+    };
+    let Some(timeout) = config.handler_timeout else {
+        return handling;
+    };
+    Box::pin(async move {
+        tokio::time::timeout(timeout, handling)
+            .await
+            .unwrap_or_else(|_| {
+                eprintln!("Handler timed out after {timeout:?}");
+                Err(ServerError::with_status(StatusCode::ServiceUnavailable))
+            })
+    })
+}
+
+/// Polls the future of a handler, turning a panic into an error, see [`call_handler`]
+struct CatchUnwind<'a>(BoxFuture<'a, Result<Response, ServerError>>);
+
+impl Future for CatchUnwind<'_> {
+    type Output = Result<Response, ServerError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let handling = &mut self.0;
+        match panic::catch_unwind(AssertUnwindSafe(|| handling.as_mut().poll(cx))) {
+            Ok(poll) => poll,
+            Err(payload) => Poll::Ready(Err(handler_panicked(payload))),
+        }
+    }
+}
+
+/// Logs the message of a handler panic, and returns the error it is answered with
+fn handler_panicked(payload: Box<dyn Any + Send>) -> ServerError {
+    let message = payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic");
+    eprintln!("Handler panicked: {message}");
+    ServerError::with_message(
+        StatusCode::InternalServerError,
+        format!("Handler panicked: {message}"),
+    )
+}
+
+/// Updates the metrics, if the server counts them
+pub(crate) fn record(config: &ServerConfig, update: impl FnOnce(&Metrics)) {
+    if let Some(metrics) = &config.metrics {
+        update(metrics);
+    }
+}
+
+/// Waits until the handler has room for the next chunk of a streamed body,
+/// never completing when no body is streamed
+async fn reserve_chunk(
+    body_tx: &Option<mpsc::Sender<Result<Bytes, BodyError>>>,
+) -> Result<OwnedPermit<Result<Bytes, BodyError>>, mpsc::error::SendError<()>> {
+    match body_tx {
+        Some(tx) => tx.clone().reserve_owned().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Sends the result of reading the next chunk of a streamed body to the handler
+///
+/// Returns false when no more chunks follow, and the sender is dropped to end the stream
+fn deliver_chunk(
+    permit: OwnedPermit<Result<Bytes, BodyError>>,
+    chunk: Result<Option<Bytes>, BodyError>,
+) -> bool {
+    match chunk {
+        Ok(Some(chunk)) => {
+            permit.send(Ok(chunk));
+            true
+        }
+        Ok(None) => false,
+        Err(e) => {
+            permit.send(Err(e));
+            false
+        }
+    }
+}
+
+/// Reads the rest of a streamed body into the handler's stream until the upgrade callback returns
+async fn forward_body<R>(
+    mut running: UpgradeFuture<'_>,
+    body: &mut StreamedBody<'_, R>,
+    mut body_tx: Option<mpsc::Sender<Result<Bytes, BodyError>>>,
+) where
+    R: AsyncReadExt + Unpin,
+{
+    loop {
+        tokio::select! {
+            biased;
+            _ = &mut running => return,
+            permit = reserve_chunk(&body_tx) => {
+                // The stream was dropped by the handler
+                let Ok(permit) = permit else {
+                    body_tx = None;
+                    continue;
+                };
+                let chunk = tokio::select! {
+                    biased;
+                    _ = &mut running => return,
+                    chunk = body.read_chunk() => chunk,
+                };
+                if !deliver_chunk(permit, chunk) {
+                    body_tx = None;
+                }
+            }
+        }
+    }
+}
+
+/// The default response when a request can't be read, None closes without a response
+fn read_error_response(e: &RequestError) -> Option<Response> {
+    match e {
+        RequestError::Timeout => None,
+        RequestError::BodyTooLarge => Some(Response::new(StatusCode::ContentTooLarge)),
+        RequestError::Body(BodyError::Decode(_)) => Some(Response::bad_request()),
+        RequestError::HeadersTooLarge => {
+            Some(Response::new(StatusCode::RequestHeaderFieldsTooLarge))
+        }
+        RequestError::UriTooLong => Some(Response::new(StatusCode::UriTooLong)),
+        RequestError::IO(_) | RequestError::Body(BodyError::IO(_)) => {
+            Some(Response::internal_error())
+        }
+        RequestError::RequestLine(_)
+        | RequestError::Header(_)
+        | RequestError::Body(_)
+        | RequestError::MalformedRequest
+        | RequestError::MalformedChunkedSize
+        | RequestError::MalformedChunkedBody => Some(Response::bad_request()),
+        RequestError::ConnectionClosed => None,
+    }
+}
+
+/// Headers that TRACE never echoes back, since they carry credentials
+const TRACE_HIDDEN_HEADERS: [&str; 4] = [
+    "Authorization",
+    "Proxy-Authorization",
+    "Cookie",
+    "Set-Cookie",
+];
+
+/// Creates the response to a TRACE request, RFC 9110 Section 9.3.8
+///
+/// The body is the request line and headers as received, without the request body
+fn trace_response(req: &Request) -> Response {
+    let mut headers = req.headers.clone();
+    for name in TRACE_HIDDEN_HEADERS {
+        headers.remove(name);
+    }
+
+    let mut body = Vec::new();
+    if req.line.encode(&mut body).is_err() || headers.encode(&mut body).is_err() {
+        return Response::internal_error();
+    }
+    let mut response = Response::new(StatusCode::Ok);
+    response.body = body.into();
+    response.headers.set("Content-Type", "message/http");
+    response
+}
+
+/// Creates the response for an error returned by the handler
+///
+/// The body is the error page for the status code, otherwise a plain-text body
+/// as set by [`ServerConfig::error_bodies`] and [`ServerConfig::debug_errors`].
+/// The connection is closed after server errors, headers carried by
+/// [`ServerError::WithHeaders`] are added to the response
+fn error_response(e: &ServerError, config: &ServerConfig) -> Response {
+    let status_code = e.status_code();
+    let mut response = Response::new(status_code);
+    if status_code.is_server_error() {
+        response.headers.set("Connection", "close");
+    }
+    ErrorPage::apply(&config.error_pages, &mut response);
+    if response.body.is_empty() && config.error_bodies {
+        let body = if config.debug_errors {
+            e.to_string()
+        } else {
+            status_code.to_reason()
+        };
+        response.body = body.into();
+        response
+            .headers
+            .set("Content-Type", "text/plain; charset=utf-8");
+    }
+    let mut e = e;
+    while let ServerError::WithHeaders(inner) = e {
+        let (inner, headers) = inner.as_ref();
+        for (name, value) in headers.iter() {
+            response.headers.add(name, value);
+        }
+        e = inner;
+    }
+    response
+}
+
+/// Writes an interim 1xx response sent by the handler, logging if it fails
+///
+/// A failed write is noticed again when the final response is written
+async fn send_interim<R, W>(
+    connection: &mut Connection<R, W, Request>,
+    status: StatusCode,
+    headers: &Headers,
+) where
+    R: AsyncReadExt + Unpin,
+    W: AsyncWriteExt + Unpin,
+{
+    if let Err(e) = connection.send_interim(status, headers).await {
+        eprintln!("Error sending interim response: {e}");
+    }
+}
+
+/// Answers an HTTP/1.0 request with an HTTP/1.0 status line
+///
+/// HTTP/1.0 clients only keep the connection open when told explicitly,
+/// so `Connection: keep-alive` is added when the connection is not closed
+fn match_request_version(req: &Request, resp: &mut Response) {
+    if !req.line.version.is_http_1_0() {
+        return;
+    }
+    resp.status_line.version = HttpVersion::new(1, 0);
+    // HTTP/1.0 has no chunked encoding to send trailers with
+    resp.remove_trailers();
+    if !should_close(req, resp) {
+        resp.headers.set("Connection", "keep-alive");
+    }
+}
+
+/// Checks if the connection is closed after the response
+///
+/// HTTP/1.1 connections stay open unless the request or the response has `Connection: close`,
+/// HTTP/1.0 connections only stay open when the request has `Connection: keep-alive`
 fn should_close(req: &Request, resp: &Response) -> bool {
-    if req.line.version == (1, 0) && !req.headers.field_contains_value("Connection", "keep-alive") {
+    if !req.line.version.keep_alive_by_default()
+        && !req.headers.field_contains_value("Connection", "keep-alive")
+    {
         return true;
     }
     if req.headers.field_contains_value("Connection", "close") {
@@ -148,6 +845,7 @@ mod test {
     use tokio::net::TcpStream;
 
     use super::*;
+    use crate::message::{BodyError, RequestBuilder, ResponseError};
 
     fn fake_handler(_: &Request) -> Result<Response, ServerError> {
         let mut builder = ResponseBuilder::new();
@@ -160,14 +858,43 @@ mod test {
     }
 
     impl Server {
-        pub async fn test(handler: Handler) -> Server {
+        pub async fn test(handler: impl Handler) -> Server {
             let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
             Server {
-                handler,
+                handler: Arc::new(handler),
+                config: ServerConfig::default(),
                 _addr: "".to_string(),
-                listener,
+                listener: Listener::Tcp(listener),
             }
         }
+
+        fn tcp_listener(&self) -> &TcpListener {
+            match &self.listener {
+                Listener::Tcp(listener) => listener,
+                #[cfg(unix)]
+                Listener::Unix(_) => panic!("Test server is not listening on TCP"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bind() -> io::Result<()> {
+        let config = ServerConfig {
+            listen_backlog: Some(1),
+            reuse_address: false,
+            ..ServerConfig::default()
+        };
+        let listener = bind("127.0.0.1:0", &config).await?;
+        let addr = listener.local_addr()?;
+
+        let client = tokio::net::TcpStream::connect(addr).await?;
+        let (stream, _) = listener.accept().await?;
+        assert_eq!(stream.peer_addr()?, client.local_addr()?);
+
+        // The address is in use
+        let err = bind(&addr.to_string(), &config).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AddrInUse);
+        Ok(())
     }
 
     #[tokio::test]
@@ -185,102 +912,1086 @@ mod test {
             Ok(builder.build())
         }
 
-        handle_connection(connection, test_handler).await;
+        handle_connection(connection, &test_handler, &ServerConfig::default()).await;
 
         let written = v.into_inner();
         assert!(String::from_utf8_lossy(&written).contains("ok"));
     }
 
     #[tokio::test]
-    async fn test_server_handles_request() {
-        let server = Server::test(fake_handler).await;
-        let addr = server.listener.local_addr().unwrap();
+    async fn test_handle_connection_error_status() {
+        use std::io::Cursor;
 
-        tokio::spawn(async move {
-            if let Ok((mut stream, _)) = server.listener.accept().await {
-                let (r, w) = stream.split();
-                let connection = Connection::<_, _, Request>::new(r, w);
-                handle_connection(connection, server.handler).await;
+        let input = b"GET /a HTTP/1.1\r\n\r\nGET /b HTTP/1.1\r\n\r\n".to_vec();
+        let mut v = Cursor::new(Vec::new());
+        let connection = Connection::<_, _, Request>::new(Cursor::new(input), &mut v);
+
+        fn test_handler(req: &Request) -> Result<Response, ServerError> {
+            match req.get_url() {
+                "/a" => Err(ServerError::with_status(StatusCode::NotFound)),
+                _ => Err(ServerError::InternalError),
             }
-        });
+        }
 
-        let mut stream = TcpStream::connect(addr).await.unwrap();
-        stream
-            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
-            .await
-            .unwrap();
+        handle_connection(connection, &test_handler, &ServerConfig::default()).await;
 
-        let mut buf = Vec::new();
-        stream.read_to_end(&mut buf).await.unwrap();
+        let written = String::from_utf8_lossy(v.get_ref()).to_string();
+        assert_eq!(
+            written,
+            "HTTP/1.1 404 Not Found\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: 9\r\n\r\nNot Found\
+HTTP/1.1 500 Internal Server Error\r\nConnection: close\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: 21\r\n\r\nInternal Server Error"
+        );
+    }
 
-        let mut builder = ResponseBuilder::new();
-        builder.add_to_body(b"Hello").unwrap();
-        let mut response = builder.build();
+    #[test]
+    fn test_error_response_body() {
+        let e = ServerError::with_message(StatusCode::NotFound, "No user with id 7");
+        let mut config = ServerConfig::default();
+        let response = error_response(&e, &config);
+        assert_eq!(response.status_line.status_code, StatusCode::NotFound);
+        assert_eq!(response.body, &b"Not Found"[..]);
 
-        let mut expected = Vec::new();
-        response.write_to(&mut expected).await.unwrap();
-        let output = String::from_utf8_lossy(&buf);
-        let expected = String::from_utf8_lossy(&expected);
+        config.debug_errors = true;
+        let response = error_response(&e, &config);
+        assert_eq!(response.body, &b"No user with id 7"[..]);
+        let response = error_response(&io::Error::other("disk full").into(), &config);
+        assert_eq!(response.body, &b"IO: disk full"[..]);
+
+        config.error_bodies = false;
+        let response = error_response(&e, &config);
+        assert!(response.body.is_empty());
+        assert_eq!(response.headers.get("Content-Type"), None);
 
-        assert_eq!(output, expected,);
+        // An error page is used instead of the plain-text body
+        config.error_bodies = true;
+        config.error_pages = vec![ErrorPage::new(
+            StatusCode::NotFound,
+            "<h1>Not Found</h1>",
+            "text/html",
+        )];
+        let response = error_response(&e, &config);
+        assert_eq!(response.body, &b"<h1>Not Found</h1>"[..]);
     }
 
-    async fn read_one_response(stream: &mut TcpStream) -> String {
-        let mut buf = Vec::new();
-        let mut tmp = [0u8; 512];
+    #[test]
+    fn test_error_status_code() {
+        let e = ServerError::with_status(StatusCode::NotFound);
+        assert_eq!(e.status_code(), StatusCode::NotFound);
 
-        loop {
-            let n = stream.read(&mut tmp).await.unwrap();
-            if n == 0 {
-                break; // server closed (unexpected for keep-alive)
-            }
-            buf.extend_from_slice(&tmp[..n]);
+        let e = ServerError::with_message(StatusCode::Unauthorized, "taken");
+        assert_eq!(e.status_code(), StatusCode::Unauthorized);
+        assert_eq!(e.to_string(), "taken");
 
-            // crude but works: detect full HTTP response by CRLF CRLF
-            if buf.windows(4).any(|w| w == b"\r\n\r\n") {
-                break;
-            }
+        let e: ServerError = BodyError::MalformedChunkedBody.into();
+        assert_eq!(e.status_code(), StatusCode::BadRequest);
+
+        let e: ServerError = RequestError::HeadersTooLarge.into();
+        assert_eq!(e.status_code(), StatusCode::RequestHeaderFieldsTooLarge);
+
+        let e: ServerError = io::Error::other("failed").into();
+        assert_eq!(e.status_code(), StatusCode::InternalServerError);
+    }
+
+    #[tokio::test]
+    async fn test_handle_connection_into_response() {
+        use std::io::Cursor;
+
+        let input = b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n".to_vec();
+        let mut v = Cursor::new(Vec::new());
+        let connection = Connection::<_, _, Request>::new(Cursor::new(input), &mut v);
+
+        fn test_handler(_: &Request) -> Result<&'static str, ServerError> {
+            Ok("ok")
         }
 
-        String::from_utf8_lossy(&buf).to_string()
+        handle_connection(connection, &test_handler, &ServerConfig::default()).await;
+
+        let written = String::from_utf8_lossy(v.get_ref()).to_string();
+        assert_eq!(
+            written,
+            "HTTP/1.1 200 Ok\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: 2\r\n\r\nok"
+        );
     }
 
     #[tokio::test]
-    async fn test_server_handles_keep_alive() {
-        let server = Server::test(fake_handler_no_body).await;
-        let addr = server.listener.local_addr().unwrap();
+    async fn test_handle_connection_handler_timeout() {
+        use std::io::Cursor;
 
-        tokio::spawn(async move {
-            if let Ok((mut stream, _)) = server.listener.accept().await {
-                let (r, w) = stream.split();
-                let connection = Connection::<_, _, Request>::new(r, w);
-                handle_connection(connection, server.handler).await;
-            }
+        let handler = async_handler(|_req: &Request| {
+            Box::pin(async {
+                std::future::pending::<()>().await;
+                Ok("never")
+            })
         });
+        let config = ServerConfig {
+            handler_timeout: Some(std::time::Duration::from_millis(20)),
+            ..ServerConfig::default()
+        };
+        let input = b"GET /slow HTTP/1.1\r\n\r\n";
+        let mut v = Cursor::new(Vec::new());
+        let connection = Connection::<_, _, Request>::new(Cursor::new(input.to_vec()), &mut v);
 
-        let mut stream = TcpStream::connect(addr).await.unwrap();
-        stream
-            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
-            .await
-            .unwrap();
+        handle_connection(connection, &handler, &config).await;
 
-        let resp1 = read_one_response(&mut stream).await;
+        let written = String::from_utf8_lossy(v.get_ref()).to_string();
+        assert_eq!(
+            written,
+            "HTTP/1.1 503 Service Unavailable\r\nConnection: close\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: 19\r\n\r\nService Unavailable"
+        );
+    }
 
-        let mut response = ResponseBuilder::new().build();
+    #[tokio::test]
+    async fn test_handle_connection_handler_panic() {
+        use std::io::Cursor;
 
-        let mut expected = Vec::new();
-        response.write_to(&mut expected).await.unwrap();
-        let expected = String::from_utf8_lossy(&expected);
+        fn sync_panic(_: &Request) -> Result<Response, ServerError> {
+            panic!("sync handler failed");
+        }
+        let async_panic = async_handler(|_req: &Request| {
+            Box::pin(async {
+                tokio::task::yield_now().await;
+                if true {
+                    panic!("async handler failed");
+                }
+                Ok("never")
+            })
+        });
+        let handlers: [&dyn Handler; 2] = [&sync_panic, &async_panic];
 
-        assert_eq!(resp1, expected);
+        for handler in handlers {
+            let input = b"GET / HTTP/1.1\r\n\r\nGET / HTTP/1.1\r\n\r\n";
+            let mut v = Cursor::new(Vec::new());
+            let connection = Connection::<_, _, Request>::new(Cursor::new(input.to_vec()), &mut v);
 
-        stream
-            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
-            .await
-            .unwrap();
+            handle_connection(connection, handler, &ServerConfig::default()).await;
 
-        let resp2 = read_one_response(&mut stream).await;
+            let written = String::from_utf8_lossy(v.get_ref()).to_string();
+            assert_eq!(
+                written,
+                "HTTP/1.1 500 Internal Server Error\r\nConnection: close\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: 21\r\n\r\nInternal Server Error"
+            );
+        }
+    }
 
-        assert_eq!(resp2, expected);
+    #[tokio::test]
+    async fn test_serve_connection_metrics() -> io::Result<()> {
+        let metrics = Arc::new(Metrics::new());
+        let config = ServerConfig {
+            metrics: Some(metrics.clone()),
+            ..ServerConfig::default()
+        };
+        let input = b"GET / HTTP/1.1\r\n\r\nGET /\r\n\r\n";
+        let (mut client, server) = tokio::io::duplex(1024);
+        client.write_all(input).await?;
+        serve_connection(server, &fake_handler, &config).await;
+
+        let mut output = Vec::new();
+        client.read_to_end(&mut output).await?;
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.requests, 1);
+        assert_eq!(snapshot.parse_errors, 1);
+        assert_eq!(snapshot.responses[1], 1);
+        assert_eq!(snapshot.responses.iter().sum::<u64>(), 2);
+        assert_eq!(snapshot.bytes_read, input.len() as u64);
+        assert_eq!(snapshot.bytes_written, output.len() as u64);
+        assert_eq!(snapshot.active_connections, 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_handle_connection_http_1_0() {
+        use std::io::Cursor;
+
+        let input = b"GET / HTTP/1.0\r\nHost: localhost\r\n\r\n".to_vec();
+        let mut v = Cursor::new(Vec::new());
+        let connection = Connection::<_, _, Request>::new(Cursor::new(input), &mut v);
+
+        handle_connection(connection, &fake_handler_no_body, &ServerConfig::default()).await;
+
+        let written = String::from_utf8_lossy(v.get_ref()).to_string();
+        assert_eq!(written, "HTTP/1.0 200 Ok\r\n\r\n");
+    }
+
+    #[tokio::test]
+    async fn test_handle_connection_body_to_eof() {
+        use std::io::Cursor;
+
+        fn echo(req: &Request) -> Result<Response, ServerError> {
+            let mut response = Response::new(StatusCode::Ok);
+            response.body = req.body_bytes();
+            Ok(response)
+        }
+
+        let input = b"POST /upload HTTP/1.0\r\nConnection: keep-alive\r\n\r\ndata".to_vec();
+        let mut v = Cursor::new(Vec::new());
+        let mut connection = Connection::<_, _, Request>::new(Cursor::new(input), &mut v);
+        connection.set_read_body_to_eof(true);
+
+        handle_connection(connection, &echo, &ServerConfig::default()).await;
+
+        let written = String::from_utf8_lossy(v.get_ref()).to_string();
+        assert_eq!(
+            written,
+            "HTTP/1.0 200 Ok\r\nConnection: close\r\nContent-Length: 4\r\n\r\ndata"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handle_connection_head() {
+        use std::io::Cursor;
+
+        let input = b"GET / HTTP/1.1\r\n\r\nHEAD / HTTP/1.1\r\nConnection: close\r\n\r\n".to_vec();
+        let mut v = Cursor::new(Vec::new());
+        let connection = Connection::<_, _, Request>::new(Cursor::new(input), &mut v);
+
+        handle_connection(connection, &fake_handler, &ServerConfig::default()).await;
+
+        let written = String::from_utf8_lossy(v.get_ref()).to_string();
+        let (get, head) = written.split_at(written.find("Hello").unwrap() + "Hello".len());
+        assert_eq!(get, "HTTP/1.1 200 Ok\r\nContent-Length: 5\r\n\r\nHello");
+        // The same Content-Length as the GET, without the body
+        assert_eq!(head, "HTTP/1.1 200 Ok\r\nContent-Length: 5\r\n\r\n");
+    }
+
+    #[tokio::test]
+    async fn test_handle_connection_error_page() {
+        use std::io::Cursor;
+
+        fn not_found(req: &Request) -> Result<Response, ServerError> {
+            let mut response = Response::new(StatusCode::NotFound);
+            if req.get_url() == "/custom" {
+                response.body = Bytes::from_static(b"custom");
+            }
+            Ok(response)
+        }
+
+        let config = ServerConfig {
+            error_pages: vec![ErrorPage::new(
+                StatusCode::NotFound,
+                "<h1>Not Found</h1>",
+                "text/html",
+            )],
+            ..Default::default()
+        };
+        let input = b"GET / HTTP/1.1\r\n\r\nGET /custom HTTP/1.1\r\nConnection: close\r\n\r\n";
+        let mut v = Cursor::new(Vec::new());
+        let connection = Connection::<_, _, Request>::new(Cursor::new(input.to_vec()), &mut v);
+
+        handle_connection(connection, &not_found, &config).await;
+
+        let written = String::from_utf8_lossy(v.get_ref()).to_string();
+        assert_eq!(
+            written,
+            "HTTP/1.1 404 Not Found\r\nContent-Type: text/html\r\nContent-Length: 18\r\n\r\n<h1>Not Found</h1>\
+            HTTP/1.1 404 Not Found\r\nContent-Length: 6\r\n\r\ncustom"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handle_connection_http_1_0_keep_alive() {
+        use std::io::Cursor;
+
+        let input = b"GET / HTTP/1.0\r\nConnection: keep-alive\r\n\r\n".to_vec();
+        let mut v = Cursor::new(Vec::new());
+        let connection = Connection::<_, _, Request>::new(Cursor::new(input), &mut v);
+
+        handle_connection(connection, &fake_handler_no_body, &ServerConfig::default()).await;
+
+        let written = String::from_utf8_lossy(v.get_ref()).to_string();
+        assert_eq!(written, "HTTP/1.0 200 Ok\r\nConnection: keep-alive\r\n\r\n");
+    }
+
+    #[tokio::test]
+    async fn test_handle_connection_interim_and_trailers() {
+        use std::io::Cursor;
+
+        fn handler(req: &Request) -> Result<Response, ServerError> {
+            let mut hints = Headers::new();
+            hints.set("Link", "</style.css>; rel=preload");
+            req.send_interim(StatusCode::EarlyHints, hints);
+
+            let mut response = Response::new(StatusCode::Ok);
+            response.set_trailer("Checksum", "abc");
+            Ok(response)
+        }
+
+        let input = b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n".to_vec();
+        let mut v = Cursor::new(Vec::new());
+        let connection = Connection::<_, _, Request>::new(Cursor::new(input), &mut v);
+        handle_connection(connection, &handler, &ServerConfig::default()).await;
+        assert_eq!(
+            String::from_utf8_lossy(v.get_ref()),
+            "HTTP/1.1 103 Early Hints\r\nLink: </style.css>; rel=preload\r\n\r\n\
+            HTTP/1.1 200 Ok\r\nTrailer: Checksum\r\nTransfer-Encoding: chunked\r\n\r\n\
+            0\r\nChecksum: abc\r\n\r\n"
+        );
+
+        // HTTP/1.0 clients get neither
+        let input = b"GET / HTTP/1.0\r\n\r\n".to_vec();
+        let mut v = Cursor::new(Vec::new());
+        let connection = Connection::<_, _, Request>::new(Cursor::new(input), &mut v);
+        handle_connection(connection, &handler, &ServerConfig::default()).await;
+        assert_eq!(
+            String::from_utf8_lossy(v.get_ref()),
+            "HTTP/1.0 200 Ok\r\n\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handle_connection_trace() {
+        use std::io::Cursor;
+
+        let input =
+            b"TRACE /a HTTP/1.1\r\nHost: localhost\r\nCookie: secret\r\nConnection: close\r\n\r\n";
+
+        let mut v = Cursor::new(Vec::new());
+        let connection = Connection::<_, _, Request>::new(Cursor::new(input.to_vec()), &mut v);
+        handle_connection(connection, &fake_handler_no_body, &ServerConfig::default()).await;
+        let written = String::from_utf8_lossy(v.get_ref()).to_string();
+        assert_eq!(written, "HTTP/1.1 200 Ok\r\n\r\n");
+
+        let config = ServerConfig {
+            trace: true,
+            ..ServerConfig::default()
+        };
+        let mut v = Cursor::new(Vec::new());
+        let connection = Connection::<_, _, Request>::new(Cursor::new(input.to_vec()), &mut v);
+        handle_connection(connection, &fake_handler_no_body, &config).await;
+        let written = String::from_utf8_lossy(v.get_ref()).to_string();
+        let body = "TRACE /a HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n";
+        assert_eq!(
+            written,
+            format!(
+                "HTTP/1.1 200 Ok\r\nContent-Type: message/http\r\nContent-Length: {}\r\n\r\n{body}",
+                body.len()
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_serve_connection_duplex() -> Result<(), ResponseError> {
+        let (client, server) = tokio::io::duplex(1024);
+        let serve = tokio::spawn(async move {
+            serve_connection(server, &fake_handler, &ServerConfig::default()).await;
+        });
+
+        let mut connection = Connection::<_, _, Response>::from_stream(client);
+        let req = RequestBuilder::new(Method::Get, "/").build();
+        connection.send(&req).await?;
+        let response = connection.read().await?;
+        assert_eq!(response.status_line.status_code, StatusCode::Ok);
+
+        drop(connection);
+        serve.await.unwrap();
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_serve_connection_stream_chunked() {
+        let (seen_tx, mut seen) = mpsc::unbounded_channel();
+        let handler = async_handler(move |req: &Request| {
+            let seen_tx = seen_tx.clone();
+            Box::pin(async move {
+                let Some(mut body) = req.body_stream() else {
+                    return Ok(format!("buffered {}", req.get_body().len()));
+                };
+                assert!(req.body_stream().is_none());
+                let mut len = 0;
+                while let Some(chunk) = body.next_chunk().await {
+                    let chunk = chunk?;
+                    len += chunk.len();
+                    seen_tx.send(chunk).unwrap();
+                }
+                Ok(format!("streamed {len}"))
+            })
+        });
+        let config = ServerConfig {
+            stream_chunked_bodies: true,
+            ..ServerConfig::default()
+        };
+
+        let (mut client, server) = tokio::io::duplex(1024);
+        let serve = tokio::spawn(async move {
+            serve_connection(server, &handler, &config).await;
+        });
+
+        client
+            .write_all(b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n")
+            .await
+            .unwrap();
+        // Each chunk reaches the handler before the rest of the body is sent
+        assert_eq!(seen.recv().await.unwrap(), "hello");
+        client.write_all(b"6\r\n world\r\n").await.unwrap();
+        assert_eq!(seen.recv().await.unwrap(), " world");
+        client
+            .write_all(b"0\r\n\r\nPOST / HTTP/1.1\r\nContent-Length: 3\r\n\r\nabc")
+            .await
+            .unwrap();
+
+        let mut connection = Connection::<_, _, Response>::from_stream(client);
+        let response = connection.read().await.unwrap();
+        assert_eq!(response.body, &b"streamed 11"[..]);
+        assert_eq!(response.headers.get("Connection"), None);
+        let response = connection.read().await.unwrap();
+        assert_eq!(response.body, &b"buffered 3"[..]);
+
+        drop(connection);
+        serve.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_serve_connection_stream_chunked_unread() {
+        let config = ServerConfig {
+            stream_chunked_bodies: true,
+            ..ServerConfig::default()
+        };
+        let (mut client, server) = tokio::io::duplex(1024);
+        let serve = tokio::spawn(async move {
+            serve_connection(server, &fake_handler, &config).await;
+        });
+
+        // The handler answers without reading the body, which the client is still sending
+        client
+            .write_all(b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n")
+            .await
+            .unwrap();
+        let mut connection = Connection::<_, _, Response>::from_stream(client);
+        let response = connection.read().await.unwrap();
+        assert_eq!(response.status_line.status_code, StatusCode::Ok);
+        assert_eq!(
+            response.headers.get("Connection"),
+            Some(&"close".to_string())
+        );
+
+        serve.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_serve_connection_duplex_body() {
+        let handler = async_handler(|req: &Request| {
+            let body = req.body_stream();
+            let response = Response::duplex(req, move |writer| {
+                Box::pin(async move {
+                    let mut body = body.unwrap();
+                    while let Some(Ok(chunk)) = body.next_chunk().await {
+                        writer.write(&chunk.to_ascii_uppercase()).await.unwrap();
+                    }
+                })
+            });
+            Box::pin(async move { Ok(response) })
+        });
+        let config = ServerConfig {
+            stream_chunked_bodies: true,
+            ..ServerConfig::default()
+        };
+        let (mut client, server) = tokio::io::duplex(1024);
+        let serve = tokio::spawn(async move {
+            serve_connection(server, &handler, &config).await;
+        });
+
+        async fn read_until(client: &mut tokio::io::DuplexStream, end: &str) -> String {
+            let mut received = Vec::new();
+            while !received.ends_with(end.as_bytes()) {
+                let mut buf = [0u8; 256];
+                let n = client.read(&mut buf).await.unwrap();
+                assert!(n > 0, "Connection closed before {end:?}");
+                received.extend_from_slice(&buf[..n]);
+            }
+            String::from_utf8(received).unwrap()
+        }
+
+        client
+            .write_all(b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n")
+            .await
+            .unwrap();
+        // Each chunk is echoed before the next one is sent
+        let head = read_until(&mut client, "5\r\nHELLO\r\n").await;
+        assert!(head.starts_with("HTTP/1.1 200 Ok\r\n"));
+        assert!(head.contains("Transfer-Encoding: chunked\r\n"));
+        client.write_all(b"6\r\n world\r\n").await.unwrap();
+        read_until(&mut client, "6\r\n WORLD\r\n").await;
+        client.write_all(b"0\r\n\r\n").await.unwrap();
+        read_until(&mut client, "0\r\n\r\n").await;
+
+        // The connection is closed after the response
+        assert_eq!(client.read(&mut [0u8; 16]).await.unwrap(), 0);
+        serve.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_handle_connection_max_body_size() {
+        use std::io::Cursor;
+
+        let input = b"POST / HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello".to_vec();
+        let mut v = Cursor::new(Vec::new());
+        let config = ServerConfig {
+            max_body_size: Some(4),
+            ..Default::default()
+        };
+        let mut connection = Connection::<_, _, Request>::new(Cursor::new(input), &mut v);
+        connection.set_max_body_size(config.max_body_size);
+
+        handle_connection(connection, &fake_handler, &config).await;
+
+        let written = String::from_utf8(v.into_inner()).unwrap();
+        assert!(written.starts_with("HTTP/1.1 413 Content Too Large\r\n"));
+        assert!(written.contains("Connection: close\r\n"));
+    }
+
+    #[tokio::test]
+    async fn test_handle_connection_unsupported_version() {
+        use std::io::Cursor;
+
+        let input = b"GET / HTTP/9.9\r\n\r\n".to_vec();
+        let mut v = Cursor::new(Vec::new());
+        let connection = Connection::<_, _, Request>::new(Cursor::new(input), &mut v);
+
+        handle_connection(connection, &fake_handler, &ServerConfig::default()).await;
+
+        let written = String::from_utf8(v.into_inner()).unwrap();
+        assert!(written.starts_with("HTTP/1.1 505 HTTP Version Not Supported\r\n"));
+        assert!(!written.contains("Hello"));
+    }
+
+    #[tokio::test]
+    async fn test_handle_connection_no_keep_alive() {
+        use std::io::Cursor;
+
+        // The second request is never answered
+        let input = b"GET / HTTP/1.1\r\n\r\nGET / HTTP/1.1\r\n\r\n".to_vec();
+        let mut v = Cursor::new(Vec::new());
+        let connection = Connection::<_, _, Request>::new(Cursor::new(input), &mut v);
+        let config = ServerConfig {
+            keep_alive: false,
+            ..Default::default()
+        };
+
+        handle_connection(connection, &fake_handler, &config).await;
+
+        let written = String::from_utf8(v.into_inner()).unwrap();
+        assert_eq!(written.matches("HTTP/1.1 200").count(), 1);
+        assert!(written.contains("Connection: close\r\n"));
+    }
+
+    #[tokio::test]
+    async fn test_serve_connection_max_keep_alive_requests() {
+        let config = ServerConfig {
+            max_keep_alive_requests: Some(2),
+            ..ServerConfig::default()
+        };
+        let (client, server) = tokio::io::duplex(1024);
+        let serve = tokio::spawn(async move {
+            serve_connection(server, &fake_handler, &config).await;
+        });
+
+        let mut connection = Connection::<_, _, Response>::from_stream(client);
+        let req = RequestBuilder::new(Method::Get, "/").build();
+        connection.send(&req).await.unwrap();
+        let response = connection.read().await.unwrap();
+        assert_eq!(response.headers.get("Connection"), None);
+
+        connection.send(&req).await.unwrap();
+        let response = connection.read().await.unwrap();
+        assert_eq!(
+            response.headers.get("Connection"),
+            Some(&"close".to_string())
+        );
+
+        // The connection is closed after the last response
+        serve.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_serve_connection_read_timeout() {
+        let (client, server) = tokio::io::duplex(1024);
+        let config = ServerConfig {
+            read_timeout: Some(std::time::Duration::from_millis(10)),
+            ..Default::default()
+        };
+
+        // Returns without the client sending anything or closing
+        serve_connection(server, &fake_handler, &config).await;
+        drop(client);
+    }
+
+    #[tokio::test]
+    async fn test_serve_connection_error_handler() {
+        let (mut client, server) = tokio::io::duplex(1024);
+        let config = ServerConfig {
+            read_timeout: Some(std::time::Duration::from_millis(10)),
+            error_handler: Some(ErrorHandler::new(|e| match e {
+                RequestError::Timeout => Some(Response::new(StatusCode::RequestTimeout)),
+                _ => None,
+            })),
+            ..Default::default()
+        };
+
+        serve_connection(server, &fake_handler, &config).await;
+        let mut written = String::new();
+        client.read_to_string(&mut written).await.unwrap();
+        assert!(written.starts_with("HTTP/1.1 408 Request Timeout\r\n"));
+        assert!(written.contains("Connection: close\r\n"));
+    }
+
+    #[tokio::test]
+    async fn test_handle_connection_head_event_stream() {
+        use crate::message::EventSender;
+        use std::io::Cursor;
+
+        let senders = Arc::new(Mutex::new(Vec::<EventSender>::new()));
+        let handler = {
+            let senders = senders.clone();
+            move |req: &Request| -> Result<Response, ServerError> {
+                let (response, sender) = Response::event_stream(req);
+                senders.lock().unwrap().push(sender);
+                Ok(response)
+            }
+        };
+
+        let input = b"HEAD /events HTTP/1.1\r\n\r\n".to_vec();
+        let mut v = Cursor::new(Vec::new());
+        let connection = Connection::<_, _, Request>::new(Cursor::new(input), &mut v);
+
+        handle_connection(connection, &handler, &ServerConfig::default()).await;
+
+        let written = String::from_utf8(v.into_inner()).unwrap();
+        assert!(written.starts_with("HTTP/1.1 200 Ok\r\n"));
+        assert!(written.ends_with("\r\n\r\n"));
+        // The events are never written
+        assert!(senders.lock().unwrap()[0].is_closed());
+    }
+
+    #[tokio::test]
+    async fn test_handle_connection_malformed_request() {
+        use std::io::Cursor;
+
+        let inputs: [&[u8]; 3] = [
+            b"GET / HTTP/1.1 extra\r\n\r\n",
+            b"GET / HTTP/1.1\r\nBad\r\n\r\n",
+            b"POST / HTTP/1.1\r\nContent-Length: 1\r\nContent-Length: 2\r\n\r\nab",
+        ];
+        for input in inputs {
+            let mut v = Cursor::new(Vec::new());
+            let connection = Connection::<_, _, Request>::new(Cursor::new(input.to_vec()), &mut v);
+
+            handle_connection(connection, &fake_handler, &ServerConfig::default()).await;
+
+            let written = String::from_utf8(v.into_inner()).unwrap();
+            assert!(
+                written.starts_with("HTTP/1.1 400 Bad Request\r\n"),
+                "{written}"
+            );
+            assert!(written.contains("Connection: close\r\n"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handle_connection_max_header_size() {
+        use std::io::Cursor;
+
+        let input = b"GET / HTTP/1.1\r\nHost: localhost\r\nCookie: aaaaaaaaaa\r\n\r\n".to_vec();
+        let mut v = Cursor::new(Vec::new());
+        let config = ServerConfig::default();
+        let mut connection = Connection::<_, _, Request>::new(Cursor::new(input), &mut v);
+        connection.set_max_header_size(Some(20));
+
+        handle_connection(connection, &fake_handler, &config).await;
+
+        let written = String::from_utf8(v.into_inner()).unwrap();
+        assert!(written.starts_with("HTTP/1.1 431 Request Header Fields Too Large\r\n"));
+        assert!(written.contains("Connection: close\r\n"));
+    }
+
+    #[tokio::test]
+    async fn test_serve_connection_max_request_line_size() {
+        let config = ServerConfig {
+            max_request_line_size: Some(8 * 1024),
+            ..ServerConfig::default()
+        };
+        let long = "a".repeat(20 * 1024);
+        for (line, status) in [
+            (format!("GET /{long} HTTP/1.1"), "414 URI Too Long"),
+            (format!("GET{long} / HTTP/1.1"), "400 Bad Request"),
+            (format!("GET / HTTP/1.1{long}"), "400 Bad Request"),
+        ] {
+            let (mut client, server) = tokio::io::duplex(64 * 1024);
+            let config = config.clone();
+            let serve = tokio::spawn(async move {
+                serve_connection(server, &fake_handler, &config).await;
+            });
+
+            client
+                .write_all(format!("{line}\r\nHost: localhost\r\n\r\n").as_bytes())
+                .await
+                .unwrap();
+            let mut connection = Connection::<_, _, Response>::from_stream(client);
+            let response = connection.read().await.unwrap();
+            assert_eq!(
+                response.status_line.to_string(),
+                format!("HTTP/1.1 {status}")
+            );
+            assert_eq!(
+                response.headers.get("Connection"),
+                Some(&"close".to_string())
+            );
+            serve.await.unwrap();
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_server_unix_socket() {
+        use tokio::net::UnixStream;
+
+        let path = std::env::temp_dir().join(format!("rust-http-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let server = Server::new_unix(&path, fake_handler);
+        let serve = tokio::spawn(async move { server.listen_and_serve().await });
+
+        let mut stream = UnixStream::connect(&path).await.unwrap();
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut buf = Vec::new();
+        stream.read_to_end(&mut buf).await.unwrap();
+        let output = String::from_utf8_lossy(&buf);
+        assert!(output.starts_with("HTTP/1.1 200 Ok\r\n"), "{output}");
+        assert!(output.ends_with("Hello"), "{output}");
+
+        serve.abort();
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_serve_connection_websocket_upgrade() {
+        fn handler(req: &Request) -> Result<Response, ServerError> {
+            let mut response = Response::websocket_accept(req)
+                .ok_or(ServerError::with_status(StatusCode::BadRequest))?;
+            response.on_upgrade(|mut stream| {
+                Box::pin(async move {
+                    let mut buf = [0u8; 4];
+                    stream.read_exact(&mut buf).await.unwrap();
+                    stream.write_all(&buf).await.unwrap();
+                })
+            });
+            Ok(response)
+        }
+
+        let (mut client, server) = tokio::io::duplex(1024);
+        let serve = tokio::spawn(async move {
+            serve_connection(server, &handler, &ServerConfig::default()).await;
+        });
+
+        // The first frame is sent together with the handshake, so it is already buffered
+        client
+            .write_all(
+                b"GET /chat HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+                Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\nping",
+            )
+            .await
+            .unwrap();
+        let mut buf = Vec::new();
+        client.read_to_end(&mut buf).await.unwrap();
+        serve.await.unwrap();
+
+        assert_eq!(
+            String::from_utf8_lossy(&buf),
+            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+            Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n\r\nping"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_serve_connection_connect_tunnel() {
+        fn handler(req: &Request) -> Result<Response, ServerError> {
+            assert_eq!(req.line.authority(), Some("example.com:443"));
+            let mut response = Response::new(StatusCode::Ok);
+            response.on_upgrade(|mut stream| {
+                Box::pin(async move {
+                    let mut buf = Vec::new();
+                    stream.read_to_end(&mut buf).await.unwrap();
+                    stream.write_all(&buf).await.unwrap();
+                })
+            });
+            Ok(response)
+        }
+
+        let (mut client, server) = tokio::io::duplex(1024);
+        let serve = tokio::spawn(async move {
+            serve_connection(server, &handler, &ServerConfig::default()).await;
+        });
+
+        client
+            .write_all(b"CONNECT example.com:443 HTTP/1.1\r\nHost: example.com:443\r\n\r\n")
+            .await
+            .unwrap();
+        // Looks like a request, but is tunneled instead of parsed
+        client.write_all(b"GET / HTTP/1.1\r\n\r\n").await.unwrap();
+        client.shutdown().await.unwrap();
+
+        let mut buf = Vec::new();
+        client.read_to_end(&mut buf).await.unwrap();
+        serve.await.unwrap();
+
+        assert_eq!(
+            String::from_utf8_lossy(&buf),
+            "HTTP/1.1 200 Ok\r\n\r\nGET / HTTP/1.1\r\n\r\n"
+        );
+    }
+
+    #[cfg(feature = "h2")]
+    #[tokio::test]
+    async fn test_server_h2_prior_knowledge() {
+        let server = Server::test(fake_handler).await;
+        let addr = server.tcp_listener().local_addr().unwrap();
+        let serve = tokio::spawn(async move { server.listen_and_serve().await });
+
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let (mut send_request, connection) = h2::client::handshake(stream).await.unwrap();
+        tokio::spawn(connection);
+
+        let request = http::Request::get("http://localhost/").body(()).unwrap();
+        let (response, _) = send_request.send_request(request, true).unwrap();
+        let response = response.await.unwrap();
+        assert_eq!(response.status(), 200);
+
+        let mut body = response.into_body();
+        let mut data = Vec::new();
+        while let Some(chunk) = body.data().await {
+            data.extend_from_slice(&chunk.unwrap());
+        }
+        assert_eq!(data, b"Hello");
+
+        serve.abort();
+    }
+
+    #[cfg(feature = "h2")]
+    #[tokio::test]
+    async fn test_server_h2_partial_preface_times_out() {
+        let mut server = Server::test(fake_handler).await;
+        server.config.read_timeout = Some(std::time::Duration::from_millis(50));
+        let addr = server.tcp_listener().local_addr().unwrap();
+        let serve = tokio::spawn(async move { server.listen_and_serve().await });
+
+        // Stalls after the start of the preface
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(b"PR").await.unwrap();
+
+        let mut buf = Vec::new();
+        tokio::time::timeout(
+            std::time::Duration::from_secs(1),
+            stream.read_to_end(&mut buf),
+        )
+        .await
+        .expect("the connection is closed after the read timeout")
+        .unwrap();
+        assert!(buf.is_empty());
+
+        serve.abort();
+    }
+
+    #[tokio::test]
+    async fn test_server_max_connections() {
+        let mut server = Server::test(fake_handler).await;
+        server.config.max_connections = Some(1);
+        let addr = server.tcp_listener().local_addr().unwrap();
+        let serve = tokio::spawn(async move { server.listen_and_serve().await });
+
+        let request = b"GET / HTTP/1.1\r\n\r\n";
+        let mut first = TcpStream::connect(addr).await.unwrap();
+        first.write_all(request).await.unwrap();
+        let mut buf = [0u8; 64];
+        assert!(first.read(&mut buf).await.unwrap() > 0);
+
+        // Waits until the first connection, which is kept alive, closes
+        let second = tokio::spawn(async move {
+            let mut second = TcpStream::connect(addr).await.unwrap();
+            second.write_all(request).await.unwrap();
+            let mut buf = [0u8; 64];
+            second.read(&mut buf).await.unwrap()
+        });
+        for _ in 0..100 {
+            tokio::task::yield_now().await;
+        }
+        assert!(!second.is_finished());
+
+        drop(first);
+        assert!(second.await.unwrap() > 0);
+        serve.abort();
+    }
+
+    #[tokio::test]
+    async fn test_server_runtime() {
+        fn handler(_: &Request) -> Result<String, ServerError> {
+            Ok(std::thread::current()
+                .name()
+                .unwrap_or_default()
+                .to_string())
+        }
+
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("http-worker")
+            .enable_all()
+            .build()
+            .unwrap();
+        let mut server = Server::test(handler).await;
+        server.config.runtime = Some(runtime.handle().clone());
+        let addr = server.tcp_listener().local_addr().unwrap();
+        let serve = tokio::spawn(async move { server.listen_and_serve().await });
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut buf = Vec::new();
+        stream.read_to_end(&mut buf).await.unwrap();
+        assert!(String::from_utf8_lossy(&buf).ends_with("\r\n\r\nhttp-worker"));
+
+        serve.abort();
+        runtime.shutdown_background();
+    }
+
+    #[tokio::test]
+    async fn test_server_handles_request() {
+        let server = Server::test(fake_handler).await;
+        let addr = server.tcp_listener().local_addr().unwrap();
+
+        tokio::spawn(async move {
+            if let Ok((mut stream, _)) = server.tcp_listener().accept().await {
+                let (r, w) = stream.split();
+                let connection = Connection::<_, _, Request>::new(r, w);
+                handle_connection(connection, server.handler.as_ref(), &server.config).await;
+            }
+        });
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+
+        let mut buf = Vec::new();
+        stream.read_to_end(&mut buf).await.unwrap();
+
+        let mut builder = ResponseBuilder::new();
+        builder.add_to_body(b"Hello").unwrap();
+        let expected = builder.build().to_bytes().unwrap();
+
+        assert_eq!(
+            String::from_utf8_lossy(&buf),
+            String::from_utf8_lossy(&expected)
+        );
+    }
+
+    async fn read_one_response(stream: &mut TcpStream) -> String {
+        let mut buf = Vec::new();
+        let mut tmp = [0u8; 512];
+
+        loop {
+            let n = stream.read(&mut tmp).await.unwrap();
+            if n == 0 {
+                break; // server closed (unexpected for keep-alive)
+            }
+            buf.extend_from_slice(&tmp[..n]);
+
+            // crude but works: detect full HTTP response by CRLF CRLF
+            if buf.windows(4).any(|w| w == b"\r\n\r\n") {
+                break;
+            }
+        }
+
+        String::from_utf8_lossy(&buf).to_string()
+    }
+
+    #[tokio::test]
+    async fn test_server_handles_keep_alive() {
+        let server = Server::test(fake_handler_no_body).await;
+        let addr = server.tcp_listener().local_addr().unwrap();
+
+        tokio::spawn(async move {
+            if let Ok((mut stream, _)) = server.tcp_listener().accept().await {
+                let (r, w) = stream.split();
+                let connection = Connection::<_, _, Request>::new(r, w);
+                handle_connection(connection, server.handler.as_ref(), &server.config).await;
+            }
+        });
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+
+        let resp1 = read_one_response(&mut stream).await;
+
+        let expected = ResponseBuilder::new().build().to_bytes().unwrap();
+        let expected = String::from_utf8_lossy(&expected);
+
+        assert_eq!(resp1, expected);
+
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+
+        let resp2 = read_one_response(&mut stream).await;
+
+        assert_eq!(resp2, expected);
+    }
+
+    #[tokio::test]
+    async fn test_server_keep_alive_until_response_close() {
+        fn handler(req: &Request) -> Result<Response, ServerError> {
+            let mut response = Response::new(StatusCode::Ok);
+            if req.get_url() == "/close" {
+                response.headers.set("Connection", "close");
+            }
+            Ok(response)
+        }
+        let server = Server::test(handler).await;
+        let addr = server.tcp_listener().local_addr().unwrap();
+
+        tokio::spawn(async move {
+            if let Ok((mut stream, _)) = server.tcp_listener().accept().await {
+                let (r, w) = stream.split();
+                let connection = Connection::<_, _, Request>::new(r, w);
+                handle_connection(connection, server.handler.as_ref(), &server.config).await;
+            }
+        });
+
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let mut connection = Connection::<_, _, Response>::from_stream(stream);
+
+        // Without `Connection` in the response, an HTTP/1.1 connection stays open
+        for _ in 0..2 {
+            let req = RequestBuilder::new(Method::Get, "/")
+                .header("Host", "localhost")
+                .build();
+            connection.send(&req).await.unwrap();
+            let response = connection.read().await.unwrap();
+            assert_eq!(response.status_line.status_code, StatusCode::Ok);
+            assert_eq!(response.headers.get("Connection"), None);
+        }
+
+        let req = RequestBuilder::new(Method::Get, "/close")
+            .header("Host", "localhost")
+            .build();
+        connection.send(&req).await.unwrap();
+        let response = connection.read().await.unwrap();
+        assert_eq!(
+            response.headers.get("Connection"),
+            Some(&"close".to_string())
+        );
+
+        // The server closes the socket after the response with `Connection: close`
+        let (mut reader, buffered, _) = connection.into_parts();
+        assert!(buffered.is_empty());
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).await.unwrap();
+        assert!(rest.is_empty());
     }
 }
@@ -0,0 +1,592 @@
+use crate::message::{Headers, Method, Request, Response, StatusCode, TargetForm, normalize_path};
+use crate::server::{BoxFuture, Cors, Handler, ServerError};
+
+struct Route {
+    path: String,
+    handlers: Vec<(Method, Box<dyn Handler>)>,
+}
+
+impl Route {
+    /// Finds the handler for method, using the `GET` handler for `HEAD` when none is registered
+    ///
+    /// The server leaves out the body of the response to a `HEAD`, RFC 9110 Section 9.3.2
+    fn handler(&self, method: &Method) -> Option<&dyn Handler> {
+        self.registered(method).or_else(|| match method {
+            Method::Head => self.registered(&Method::Get),
+            _ => None,
+        })
+    }
+
+    fn registered(&self, method: &Method) -> Option<&dyn Handler> {
+        self.handlers
+            .iter()
+            .find(|(m, _)| m == method)
+            .map(|(_, h)| h.as_ref())
+    }
+
+    /// The registered methods, with `HEAD` after `GET` when it is answered by the `GET` handler
+    fn methods(&self) -> impl Iterator<Item = Method> + '_ {
+        let implicit_head = self.registered(&Method::Head).is_none();
+        self.handlers.iter().flat_map(move |(m, _)| {
+            let head = (*m == Method::Get && implicit_head).then_some(Method::Head);
+            std::iter::once(*m).chain(head)
+        })
+    }
+}
+
+/// Dispatches requests to handlers based on method and path
+///
+/// Paths are matched ignoring the query, after [`normalize_path`] collapses duplicate slashes
+/// and resolves `.` and `..` segments, so `/a//b` and `/a/../a/b` match `/a/b`.
+/// Trailing slashes are significant unless [`Router::strip_trailing_slash`] is set.
+/// Handlers still see the target as it was received.
+/// Unknown paths get `404 Not Found`, or go to the [`Router::fallback`] handler, and known paths without a handler for the method
+/// get `405 Method Not Allowed` with an `Allow` header.
+/// `HEAD` is answered by the `GET` handler of the path, and
+/// `OPTIONS` is answered automatically with an `Allow` header,
+/// unless a handler is registered for them.
+/// `OPTIONS *` lists every method the router can handle.
+///
+/// Routers added with [`Router::host`] get the requests for their host,
+/// the routes of this router are used for every other host
+#[derive(Default)]
+pub struct Router {
+    routes: Vec<Route>,
+    hosts: Vec<(String, Router)>,
+    cors: Option<Cors>,
+    strip_trailing_slash: bool,
+    fallback: Option<Box<dyn Handler>>,
+}
+
+impl Router {
+    pub fn new() -> Router {
+        Router {
+            routes: Vec::new(),
+            hosts: Vec::new(),
+            cors: None,
+            strip_trailing_slash: false,
+            fallback: None,
+        }
+    }
+
+    /// Dispatches requests for host to router, matched against the hostname of [`Request::host`]
+    ///
+    /// The hostname is compared ignoring case and the port.
+    /// Will overwrite an earlier router for the same host
+    ///
+    /// ```ignore
+    /// let router = Router::new()
+    ///     .host("api.example.com", Router::new().get("/users", users))
+    ///     .get("/", index);
+    /// ```
+    pub fn host(mut self, host: impl Into<String>, router: Router) -> Self {
+        let host = host.into().to_ascii_lowercase();
+        match self.hosts.iter_mut().find(|(h, _)| *h == host) {
+            Some(entry) => entry.1 = router,
+            None => self.hosts.push((host, router)),
+        }
+        self
+    }
+
+    /// Enables CORS for every route
+    pub fn cors(mut self, cors: Cors) -> Self {
+        self.cors = Some(cors);
+        self
+    }
+
+    /// Handles requests for paths without a route, instead of answering `404 Not Found`
+    ///
+    /// Such as serving the `index.html` of a single-page app, or proxying to another service
+    ///
+    /// ```ignore
+    /// fn index(req: &Request) -> Result<Response, ServerError> {
+    ///     Ok(Response::file(req, "dist/index.html")?)
+    /// }
+    ///
+    /// let router = Router::new().get("/api/users", users).fallback(index);
+    /// ```
+    pub fn fallback(mut self, handler: impl Handler) -> Self {
+        self.fallback = Some(Box::new(handler));
+        self
+    }
+
+    /// Matches paths ignoring a trailing slash, so `/a/` and `/a` go to the same route
+    pub fn strip_trailing_slash(mut self, strip: bool) -> Self {
+        self.strip_trailing_slash = strip;
+        self
+    }
+
+    /// Registers a handler for the method and path, which is normalized like request paths.
+    /// Will overwrite an earlier handler for the same method and path
+    pub fn route(mut self, method: Method, path: impl Into<String>, handler: impl Handler) -> Self {
+        let path = normalize_path(&path.into());
+        let handler: Box<dyn Handler> = Box::new(handler);
+        match self.routes.iter_mut().find(|r| r.path == path) {
+            Some(route) => match route.handlers.iter_mut().find(|(m, _)| *m == method) {
+                Some(entry) => entry.1 = handler,
+                None => route.handlers.push((method, handler)),
+            },
+            None => self.routes.push(Route {
+                path,
+                handlers: vec![(method, handler)],
+            }),
+        }
+        self
+    }
+
+    pub fn get(self, path: impl Into<String>, handler: impl Handler) -> Self {
+        self.route(Method::Get, path, handler)
+    }
+
+    pub fn post(self, path: impl Into<String>, handler: impl Handler) -> Self {
+        self.route(Method::Post, path, handler)
+    }
+
+    pub fn put(self, path: impl Into<String>, handler: impl Handler) -> Self {
+        self.route(Method::Put, path, handler)
+    }
+
+    pub fn delete(self, path: impl Into<String>, handler: impl Handler) -> Self {
+        self.route(Method::Delete, path, handler)
+    }
+
+    fn find(&self, path: &str) -> Option<&Route> {
+        let path = normalize_path(path);
+        if self.strip_trailing_slash {
+            let path = without_trailing_slash(&path);
+            return self
+                .routes
+                .iter()
+                .find(|r| without_trailing_slash(&r.path) == path);
+        }
+        self.routes.iter().find(|r| r.path == path)
+    }
+
+    /// Every method registered on any path
+    fn all_methods(&self) -> Vec<Method> {
+        let mut methods = Vec::new();
+        for method in self.routes.iter().flat_map(|r| r.methods()) {
+            if !methods.contains(&method) {
+                methods.push(method);
+            }
+        }
+        methods
+    }
+}
+
+/// Removes the trailing slash of a normalized path, except from the root
+fn without_trailing_slash(path: &str) -> &str {
+    match path.strip_suffix('/') {
+        Some(rest) if !rest.is_empty() => rest,
+        _ => path,
+    }
+}
+
+/// Creates a 200 response to an OPTIONS request, listing the allowed methods
+fn options_response(mut methods: Vec<Method>) -> Response {
+    if !methods.contains(&Method::Options) {
+        methods.push(Method::Options);
+    }
+    let allow = methods
+        .iter()
+        .map(|m| m.to_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut response = Response::new(StatusCode::Ok);
+    response.headers.set("Allow", allow);
+    response
+}
+
+/// Creates a 405 response for a path that exists, listing its methods sorted by name,
+/// RFC 9110 Section 15.5.6
+fn method_not_allowed(mut methods: Vec<Method>) -> Response {
+    methods.sort_by(|a, b| a.to_str().cmp(b.to_str()));
+    let allow = methods
+        .iter()
+        .map(|m| m.to_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut response = Response::method_not_allowed();
+    response.headers.set("Allow", allow);
+    response
+}
+
+impl Router {
+    async fn dispatch(&self, req: &Request) -> Result<Response, ServerError> {
+        let method = req.get_method();
+        if req.line.target_form() == TargetForm::Asterisk {
+            if *method == Method::Options {
+                return Ok(options_response(self.all_methods()));
+            }
+            return Ok(Response::new(StatusCode::BadRequest));
+        }
+
+        let Some(route) = self.find(req.line.path()) else {
+            return match &self.fallback {
+                Some(fallback) => fallback.handle(req).await,
+                None => Ok(Response::not_found()),
+            };
+        };
+
+        if let Some(handler) = route.handler(method) {
+            return handler.handle(req).await;
+        }
+        if *method == Method::Options {
+            return Ok(options_response(route.methods().collect()));
+        }
+
+        Ok(method_not_allowed(route.methods().collect()))
+    }
+
+    async fn handle_cors(&self, req: &Request) -> Result<Response, ServerError> {
+        let Some(cors) = &self.cors else {
+            return self.dispatch(req).await;
+        };
+        let Some(origin) = cors.allowed_origin(req) else {
+            return self.dispatch(req).await;
+        };
+
+        if Cors::is_preflight(req) {
+            if let Some(route) = self.find(req.line.path()) {
+                return Ok(cors.preflight(origin, route.methods().collect()));
+            }
+            return self.dispatch(req).await;
+        }
+
+        match self.dispatch(req).await {
+            Ok(mut response) => {
+                cors.apply(origin, &mut response.headers);
+                Ok(response)
+            }
+            // The server builds the error response, without the headers the page can't read it
+            Err(e) => {
+                let mut headers = Headers::new();
+                cors.apply(origin, &mut headers);
+                Err(ServerError::WithHeaders(Box::new((e, headers))))
+            }
+        }
+    }
+}
+
+impl Handler for Router {
+    fn handle<'a>(&'a self, req: &'a Request) -> BoxFuture<'a, Result<Response, ServerError>> {
+        if !self.hosts.is_empty()
+            && let Some((host, _)) = req.host()
+            && let Some((_, router)) = self.hosts.iter().find(|(h, _)| *h == host)
+        {
+            return router.handle(req);
+        }
+        Box::pin(self.handle_cors(req))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::{Bytes, RequestBuilder};
+    use crate::server::{ServerConfig, error_response};
+    use pretty_assertions::assert_eq;
+
+    fn ok(_: &Request) -> Result<Response, ServerError> {
+        Ok(Response::new(StatusCode::Ok))
+    }
+
+    fn bad(_: &Request) -> Result<Response, ServerError> {
+        Ok(Response::new(StatusCode::BadRequest))
+    }
+
+    fn missing(_: &Request) -> Result<Response, ServerError> {
+        Err(ServerError::with_status(StatusCode::NotFound))
+    }
+
+    #[tokio::test]
+    async fn test_router_dispatch() -> Result<(), ServerError> {
+        let router = Router::new().get("/", ok).post("/", bad).get("/a", bad);
+
+        let req = RequestBuilder::new(Method::Get, "/").build();
+        assert_eq!(
+            router.handle(&req).await?.status_line.status_code,
+            StatusCode::Ok
+        );
+
+        let req = RequestBuilder::new(Method::Post, "/").build();
+        let status = router.handle(&req).await?.status_line.status_code;
+        assert_eq!(status, StatusCode::BadRequest);
+
+        let req = RequestBuilder::new(Method::Get, "/a?x=1").build();
+        let status = router.handle(&req).await?.status_line.status_code;
+        assert_eq!(status, StatusCode::BadRequest);
+
+        let req = RequestBuilder::new(Method::Get, "http://localhost/a?x=1").build();
+        let status = router.handle(&req).await?.status_line.status_code;
+        assert_eq!(status, StatusCode::BadRequest);
+
+        let req = RequestBuilder::new(Method::Get, "/b").build();
+        let status = router.handle(&req).await?.status_line.status_code;
+        assert_eq!(status, StatusCode::NotFound);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_router_method_not_allowed() -> Result<(), ServerError> {
+        let router = Router::new().get("/x", ok);
+
+        let req = RequestBuilder::new(Method::Post, "/x").build();
+        let response = router.handle(&req).await?;
+        assert_eq!(
+            response.status_line.status_code,
+            StatusCode::MethodNotAllowed
+        );
+        assert_eq!(
+            response.headers.get("Allow"),
+            Some(&"GET, HEAD".to_string())
+        );
+
+        let router = Router::new().put("/x", ok).get("/x", ok).delete("/x", ok);
+        let req = RequestBuilder::new(Method::Post, "/x").build();
+        let response = router.handle(&req).await?;
+        assert_eq!(
+            response.headers.get("Allow"),
+            Some(&"DELETE, GET, HEAD, PUT".to_string())
+        );
+
+        let req = RequestBuilder::new(Method::Post, "/y").build();
+        let status = router.handle(&req).await?.status_line.status_code;
+        assert_eq!(status, StatusCode::NotFound);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_router_options() -> Result<(), ServerError> {
+        let router = Router::new().get("/", ok).post("/", ok).put("/a", ok);
+
+        let req = RequestBuilder::new(Method::Options, "/").build();
+        let response = router.handle(&req).await?;
+        assert_eq!(response.status_line.status_code, StatusCode::Ok);
+        assert_eq!(
+            response.headers.get("Allow"),
+            Some(&"GET, HEAD, POST, OPTIONS".to_string())
+        );
+
+        let req = RequestBuilder::new(Method::Options, "*").build();
+        let response = router.handle(&req).await?;
+        assert_eq!(response.status_line.status_code, StatusCode::Ok);
+        assert_eq!(
+            response.headers.get("Allow"),
+            Some(&"GET, HEAD, POST, PUT, OPTIONS".to_string())
+        );
+
+        let req = RequestBuilder::new(Method::Options, "/missing").build();
+        let status = router.handle(&req).await?.status_line.status_code;
+        assert_eq!(status, StatusCode::NotFound);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_router_cors() -> Result<(), ServerError> {
+        let cors = Cors::new()
+            .allow_origin("http://example.com")
+            .allow_header("Content-Type")
+            .max_age(60);
+        let router = Router::new()
+            .get("/", ok)
+            .put("/", ok)
+            .get("/missing", missing)
+            .cors(cors);
+
+        let req = RequestBuilder::new(Method::Options, "/")
+            .header("Origin", "http://example.com")
+            .header("Access-Control-Request-Method", "PUT")
+            .build();
+        let response = router.handle(&req).await?;
+        assert_eq!(response.status_line.status_code, StatusCode::NoContent);
+        assert_eq!(
+            response.headers.get("Access-Control-Allow-Origin"),
+            Some(&"http://example.com".to_string())
+        );
+        assert_eq!(
+            response.headers.get("Access-Control-Allow-Methods"),
+            Some(&"GET, HEAD, PUT".to_string())
+        );
+        assert_eq!(
+            response.headers.get("Access-Control-Allow-Headers"),
+            Some(&"Content-Type".to_string())
+        );
+        assert_eq!(
+            response.headers.get("Access-Control-Max-Age"),
+            Some(&"60".to_string())
+        );
+
+        let req = RequestBuilder::new(Method::Get, "/")
+            .header("Origin", "http://example.com")
+            .build();
+        let response = router.handle(&req).await?;
+        assert_eq!(response.status_line.status_code, StatusCode::Ok);
+        assert_eq!(
+            response.headers.get("Access-Control-Allow-Origin"),
+            Some(&"http://example.com".to_string())
+        );
+        assert_eq!(response.headers.get("Vary"), Some(&"Origin".to_string()));
+
+        let req = RequestBuilder::new(Method::Get, "/")
+            .header("Origin", "http://other.com")
+            .build();
+        let response = router.handle(&req).await?;
+        assert_eq!(response.headers.get("Access-Control-Allow-Origin"), None);
+
+        // Errors get the headers too, so the page can read the error response
+        let req = RequestBuilder::new(Method::Get, "/missing")
+            .header("Origin", "http://example.com")
+            .build();
+        let e = router.handle(&req).await.unwrap_err();
+        assert_eq!(e.status_code(), StatusCode::NotFound);
+        let response = error_response(&e, &ServerConfig::default());
+        assert_eq!(response.status_line.status_code, StatusCode::NotFound);
+        assert_eq!(response.body, &b"Not Found"[..]);
+        assert_eq!(
+            response.headers.get("Access-Control-Allow-Origin"),
+            Some(&"http://example.com".to_string())
+        );
+        assert_eq!(response.headers.get("Vary"), Some(&"Origin".to_string()));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_router_host() -> Result<(), ServerError> {
+        let router = Router::new()
+            .host("api.example.com", Router::new().get("/", bad))
+            .get("/", ok);
+
+        for (host, expected) in [
+            ("API.example.com:8080", StatusCode::BadRequest),
+            ("example.com", StatusCode::Ok),
+            ("invalid host", StatusCode::Ok),
+        ] {
+            let req = RequestBuilder::new(Method::Get, "/")
+                .header("Host", host)
+                .build();
+            let status = router.handle(&req).await?.status_line.status_code;
+            assert_eq!(status, expected, "{host}");
+        }
+
+        // Without routes of its own, unknown hosts are not found
+        let router = Router::new().host("api.example.com", Router::new().get("/", ok));
+        let req = RequestBuilder::new(Method::Get, "/")
+            .header("Host", "other.com")
+            .build();
+        let status = router.handle(&req).await?.status_line.status_code;
+        assert_eq!(status, StatusCode::NotFound);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_router_head() -> Result<(), ServerError> {
+        fn index(_: &Request) -> Result<Response, ServerError> {
+            let mut response = Response::new(StatusCode::Ok);
+            response.body = Bytes::from_static(b"index");
+            Ok(response)
+        }
+
+        let router = Router::new().get("/", index).post("/form", ok);
+
+        // The body is left for the server to drop, so Content-Length matches the GET
+        let req = RequestBuilder::new(Method::Head, "/").build();
+        let response = router.handle(&req).await?;
+        assert_eq!(response.status_line.status_code, StatusCode::Ok);
+        assert_eq!(response.body, &b"index"[..]);
+
+        let req = RequestBuilder::new(Method::Head, "/form").build();
+        let response = router.handle(&req).await?;
+        assert_eq!(
+            response.status_line.status_code,
+            StatusCode::MethodNotAllowed
+        );
+        assert_eq!(response.headers.get("Allow"), Some(&"POST".to_string()));
+
+        // A registered HEAD handler is used instead
+        let router = Router::new().get("/", index).route(Method::Head, "/", bad);
+        let req = RequestBuilder::new(Method::Head, "/").build();
+        let status = router.handle(&req).await?.status_line.status_code;
+        assert_eq!(status, StatusCode::BadRequest);
+
+        let req = RequestBuilder::new(Method::Options, "/").build();
+        let response = router.handle(&req).await?;
+        assert_eq!(
+            response.headers.get("Allow"),
+            Some(&"GET, HEAD, OPTIONS".to_string())
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_router_options_registered() -> Result<(), ServerError> {
+        let router = Router::new().get("/", ok).route(Method::Options, "/", bad);
+
+        let req = RequestBuilder::new(Method::Options, "/").build();
+        let status = router.handle(&req).await?.status_line.status_code;
+        assert_eq!(status, StatusCode::BadRequest);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_router_normalized_path() -> Result<(), ServerError> {
+        let router = Router::new().get("/a/b", ok).get("/etc", bad);
+        for (url, expected) in [
+            ("/a//b", StatusCode::Ok),
+            ("//a/./b?x=1", StatusCode::Ok),
+            ("/c/../a/b", StatusCode::Ok),
+            ("/a/../b", StatusCode::NotFound),
+            ("/../etc", StatusCode::BadRequest),
+            ("/a/b/", StatusCode::NotFound),
+        ] {
+            let req = RequestBuilder::new(Method::Get, url).build();
+            let status = router.handle(&req).await?.status_line.status_code;
+            assert_eq!(status, expected, "{url}");
+        }
+
+        let router = Router::new()
+            .strip_trailing_slash(true)
+            .get("/", bad)
+            .get("/a/b/", ok);
+        for url in ["/a/b", "/a//b/", "/a/b/."] {
+            let req = RequestBuilder::new(Method::Get, url).build();
+            let status = router.handle(&req).await?.status_line.status_code;
+            assert_eq!(status, StatusCode::Ok, "{url}");
+        }
+        let req = RequestBuilder::new(Method::Get, "/a/..").build();
+        let status = router.handle(&req).await?.status_line.status_code;
+        assert_eq!(status, StatusCode::BadRequest);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_router_fallback() -> Result<(), ServerError> {
+        fn index(req: &Request) -> Result<Response, ServerError> {
+            let mut response = Response::new(StatusCode::Ok);
+            response.body = format!("index {}", req.get_url()).into();
+            Ok(response)
+        }
+
+        let router = Router::new().post("/api", bad).fallback(index);
+
+        let req = RequestBuilder::new(Method::Get, "/app/settings?tab=1").build();
+        let response = router.handle(&req).await?;
+        assert_eq!(response.status_line.status_code, StatusCode::Ok);
+        assert_eq!(response.body, &b"index /app/settings?tab=1"[..]);
+
+        // Known paths are not sent to the fallback
+        let req = RequestBuilder::new(Method::Get, "/api").build();
+        let status = router.handle(&req).await?.status_line.status_code;
+        assert_eq!(status, StatusCode::MethodNotAllowed);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,125 @@
+//! Utilities for testing handlers without sockets, enabled with the `test-util` feature
+//!
+//! ```ignore
+//! #[tokio::test]
+//! async fn test_hello() {
+//!     let mut client = TestClient::new(router());
+//!     let req = RequestBuilder::new(Method::Get, "/hello").build();
+//!     let response = client.send(&req).await.unwrap();
+//!     assert_eq!(response.body, &b"Hello World"[..]);
+//! }
+//! ```
+
+use tokio::io::{AsyncWriteExt, DuplexStream, ReadHalf, Sink, WriteHalf};
+use tokio::task::JoinHandle;
+
+use crate::message::{Connection, Request, Response, ResponseError};
+use crate::server::{Handler, ServerConfig, serve_connection};
+
+pub use crate::message::test_utils::batch_reader::BatchReader;
+pub use tokio::io::duplex;
+
+/// Size of the in-memory buffer between the client and the server
+const DUPLEX_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Client connected to a handler through an in-memory stream
+///
+/// The handler is served the same way as by [`Server`](crate::server::Server),
+/// in a task that ends when the client is dropped
+pub struct TestClient {
+    writer: WriteHalf<DuplexStream>,
+    connection: Connection<ReadHalf<DuplexStream>, Sink, Response>,
+    server: JoinHandle<()>,
+}
+
+impl TestClient {
+    pub fn new(handler: impl Handler) -> TestClient {
+        TestClient::with_config(handler, ServerConfig::default())
+    }
+
+    /// Creates a client for a handler served with config
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a tokio runtime
+    pub fn with_config(handler: impl Handler, config: ServerConfig) -> TestClient {
+        let (client, server) = duplex(DUPLEX_BUFFER_SIZE);
+        let server = tokio::spawn(async move {
+            serve_connection(server, &handler, &config).await;
+        });
+
+        let (reader, writer) = tokio::io::split(client);
+        TestClient {
+            writer,
+            // Requests are written directly to the writer, so the connection only reads
+            connection: Connection::new(reader, tokio::io::sink()),
+            server,
+        }
+    }
+
+    /// Sends the request, and reads the response
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the server closed the connection, or sent an invalid response
+    pub async fn send(&mut self, req: &Request) -> Result<Response, ResponseError> {
+        req.write_to(&mut self.writer).await?;
+        self.writer.flush().await?;
+        self.connection.read().await
+    }
+
+    /// Sends raw bytes, e.g. a malformed request, and reads the response
+    ///
+    /// # Errors
+    ///
+    /// Same as [`TestClient::send`]
+    pub async fn send_raw(&mut self, bytes: &[u8]) -> Result<Response, ResponseError> {
+        self.writer.write_all(bytes).await?;
+        self.writer.flush().await?;
+        self.connection.read().await
+    }
+
+    /// Returns true if the server has closed the connection
+    pub fn is_closed(&self) -> bool {
+        self.server.is_finished()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::{Method, RequestBuilder, StatusCode};
+    use crate::server::{Router, ServerError};
+    use pretty_assertions::assert_eq;
+
+    #[tokio::test]
+    async fn test_client() -> Result<(), ResponseError> {
+        let router = Router::new().post("/echo", |req: &Request| {
+            Ok::<_, ServerError>(req.get_body().to_vec())
+        });
+        let mut client = TestClient::new(router);
+
+        let req = RequestBuilder::new(Method::Post, "/echo")
+            .body("hello")
+            .build();
+        let response = client.send(&req).await?;
+        assert_eq!(response.status_line.status_code, StatusCode::Ok);
+        assert_eq!(response.body, &b"hello"[..]);
+
+        // The connection is kept alive between requests
+        let response = client.send_raw(b"GET /missing HTTP/1.1\r\n\r\n").await?;
+        assert_eq!(response.status_line.status_code, StatusCode::NotFound);
+
+        let response = client.send_raw(b"GET / HTTP/1.1\r\nBad\r\n\r\n").await?;
+        assert_eq!(response.status_line.status_code, StatusCode::BadRequest);
+        for _ in 0..10 {
+            if client.is_closed() {
+                break;
+            }
+            tokio::task::yield_now().await;
+        }
+        assert!(client.is_closed());
+
+        Ok(())
+    }
+}